@@ -0,0 +1,434 @@
+use crate::{
+    model::{DataElement, DicomDate, DicomValue, NumericParseMode, PrivateTag},
+    CommonResult,
+};
+
+// 按照tag查找数据元素，找不到时返回错误而不是默认值
+pub fn find_data_element<'a>(
+    data_elements: &'a [DataElement],
+    tag: &str,
+) -> CommonResult<&'a DataElement> {
+    data_elements
+        .iter()
+        .find(|data_element| data_element.tag == tag)
+        .ok_or_else(|| format!("{} is missing", tag).into())
+}
+
+// 元素存在并且值不为空（Type 2的属性允许为空）
+pub fn has_value(data_elements: &[DataElement], tag: &str) -> bool {
+    match find_data_element(data_elements, tag).map(|v| &v.data) {
+        Ok(DicomValue::String(v)) => !v.is_empty(),
+        Ok(DicomValue::Bytes(v)) => !v.is_empty(),
+        Ok(DicomValue::Float(v)) => !v.is_empty(),
+        Ok(DicomValue::Double(v)) => !v.is_empty(),
+        Ok(DicomValue::I16(v)) => !v.is_empty(),
+        Ok(DicomValue::I32(v)) => !v.is_empty(),
+        Ok(DicomValue::I64(v)) => !v.is_empty(),
+        Ok(DicomValue::U16(v)) => !v.is_empty(),
+        Ok(DicomValue::U32(v)) => !v.is_empty(),
+        Ok(DicomValue::U64(v)) => !v.is_empty(),
+        Ok(DicomValue::Sequence(v)) => !v.is_empty(),
+        Ok(DicomValue::Fragments(v)) => !v.is_empty(),
+        Ok(DicomValue::U16Pair(_)) => true,
+        Err(_) => false,
+    }
+}
+
+// 宽松模式下解析失败的值已经被跳过了，此时VM已经不可信，直接报错
+fn check_value_errors(data_element: &DataElement) -> CommonResult<()> {
+    if let Some(value_error) = data_element.value_errors.first() {
+        return Err(format!(
+            "{} has invalid value #{} {:?}: {}",
+            data_element.tag, value_error.index, value_error.raw, value_error.message
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+// 把数据元素中的值统一转换成i64
+// US/UL/SS/SL/IS都可以无损转换，FL/FD/DS只有在没有小数部分时才允许转换
+pub fn element_to_i64s(data_element: &DataElement) -> CommonResult<Vec<i64>> {
+    check_value_errors(data_element)?;
+
+    let not_integral = || {
+        format!(
+            "{} ({}) has a non-integral value",
+            data_element.tag, data_element.vr
+        )
+    };
+
+    let result = match &data_element.data {
+        DicomValue::U16(v) => v.iter().map(|v| *v as i64).collect(),
+        DicomValue::U32(v) => v.iter().map(|v| *v as i64).collect(),
+        DicomValue::I16(v) => v.iter().map(|v| *v as i64).collect(),
+        DicomValue::I32(v) => v.iter().map(|v| *v as i64).collect(),
+        DicomValue::I64(v) => v.clone(),
+        DicomValue::U64(v) => v
+            .iter()
+            .map(|v| {
+                i64::try_from(*v)
+                    .map_err(|_| format!("{} value {} is out of range", data_element.tag, v))
+            })
+            .collect::<Result<_, _>>()?,
+        DicomValue::Float(v) => v
+            .iter()
+            .map(|v| f64_to_i64(*v as f64).ok_or_else(not_integral))
+            .collect::<Result<_, _>>()?,
+        DicomValue::Double(v) => v
+            .iter()
+            .map(|v| f64_to_i64(*v).ok_or_else(not_integral))
+            .collect::<Result<_, _>>()?,
+        DicomValue::String(v) => {
+            let (values, errors) = crate::util::parse_integer_strings(v, NumericParseMode::Lenient);
+
+            if let Some(value_error) = errors.first() {
+                return Err(format!(
+                    "{} value #{} {:?} is not an integer: {}",
+                    data_element.tag, value_error.index, value_error.raw, value_error.message
+                )
+                .into());
+            }
+
+            values
+        }
+        _ => {
+            return Err(format!(
+                "{} ({}) can not be converted to an integer",
+                data_element.tag, data_element.vr
+            )
+            .into())
+        }
+    };
+
+    Ok(result)
+}
+
+fn f64_to_i64(value: f64) -> Option<i64> {
+    if value.is_finite()
+        && value.fract() == 0.0
+        && value >= i64::MIN as f64
+        && value < i64::MAX as f64
+    {
+        Some(value as i64)
+    } else {
+        None
+    }
+}
+
+// 把数据元素中的值统一转换成f64
+pub fn element_to_f64s(data_element: &DataElement) -> CommonResult<Vec<f64>> {
+    check_value_errors(data_element)?;
+
+    let result = match &data_element.data {
+        DicomValue::U16(v) => v.iter().map(|v| *v as f64).collect(),
+        DicomValue::U32(v) => v.iter().map(|v| *v as f64).collect(),
+        DicomValue::I16(v) => v.iter().map(|v| *v as f64).collect(),
+        DicomValue::I32(v) => v.iter().map(|v| *v as f64).collect(),
+        DicomValue::I64(v) => v.iter().map(|v| *v as f64).collect(),
+        DicomValue::U64(v) => v.iter().map(|v| *v as f64).collect(),
+        DicomValue::Float(v) => v.iter().map(|v| *v as f64).collect(),
+        DicomValue::Double(v) => v.clone(),
+        DicomValue::String(v) => {
+            let (values, errors) = crate::util::parse_decimal_strings(v, NumericParseMode::Lenient);
+
+            if let Some(value_error) = errors.first() {
+                return Err(format!(
+                    "{} value #{} {:?} is not a number: {}",
+                    data_element.tag, value_error.index, value_error.raw, value_error.message
+                )
+                .into());
+            }
+
+            values
+        }
+        _ => {
+            return Err(format!(
+                "{} ({}) can not be converted to a number",
+                data_element.tag, data_element.vr
+            )
+            .into())
+        }
+    };
+
+    Ok(result)
+}
+
+// OW（内存中是小端）或者US保存的16位数据，比如LUT Data
+pub fn element_to_words(data_element: &DataElement) -> CommonResult<Vec<u16>> {
+    match &data_element.data {
+        DicomValue::Bytes(v) => Ok(v
+            .chunks_exact(2)
+            .map(|v| u16::from_le_bytes([v[0], v[1]]))
+            .collect()),
+        DicomValue::U16(v) => Ok(v.clone()),
+        DicomValue::I16(v) => Ok(v.iter().map(|v| *v as u16).collect()),
+        _ => Err(format!(
+            "{} ({}) is not 16 bit data",
+            data_element.tag, data_element.vr
+        )
+        .into()),
+    }
+}
+
+// 字符串类的值按照\拆分成多个值
+// LT/ST/UT/UR的VM固定为1，其中的\是值的一部分，不能拆分（首部的空格也是有意义的，只去掉尾部的空格）
+pub fn element_to_strs(data_element: &DataElement) -> CommonResult<Vec<String>> {
+    match &data_element.data {
+        DicomValue::String(v) if v.is_empty() => Ok(Vec::new()),
+        DicomValue::String(v) if ["LT", "ST", "UT", "UR"].contains(&data_element.vr.as_str()) => {
+            Ok(vec![v.trim_end().to_string()])
+        }
+        DicomValue::String(v) => Ok(v.split('\\').map(|v| v.trim().to_string()).collect()),
+        _ => Err(format!(
+            "{} ({}) is not a string value",
+            data_element.tag, data_element.vr
+        )
+        .into()),
+    }
+}
+
+// 取出唯一的一个值，VM不为1时返回错误
+fn single_value<T>(tag: &str, mut values: Vec<T>) -> CommonResult<T> {
+    if values.len() != 1 {
+        return Err(format!("{} has VM {}, expected 1", tag, values.len()).into());
+    }
+
+    Ok(values.remove(0))
+}
+
+fn convert_integers<T: TryFrom<i64>>(tag: &str, values: Vec<i64>) -> CommonResult<Vec<T>> {
+    values
+        .into_iter()
+        .map(|v| {
+            T::try_from(v).map_err(|_| {
+                format!(
+                    "{} value {} is out of range for {}",
+                    tag,
+                    v,
+                    std::any::type_name::<T>()
+                )
+                .into()
+            })
+        })
+        .collect()
+}
+
+// Sequence中的所有item
+pub fn get_items<'a>(
+    data_elements: &'a [DataElement],
+    tag: &str,
+) -> CommonResult<&'a [Vec<DataElement>]> {
+    match &find_data_element(data_elements, tag)?.data {
+        DicomValue::Sequence(items) => Ok(items),
+        _ => Err(format!("{} is not a sequence", tag).into()),
+    }
+}
+
+pub fn get_i64s(data_elements: &[DataElement], tag: &str) -> CommonResult<Vec<i64>> {
+    element_to_i64s(find_data_element(data_elements, tag)?)
+}
+
+pub fn get_i64(data_elements: &[DataElement], tag: &str) -> CommonResult<i64> {
+    single_value(tag, get_i64s(data_elements, tag)?)
+}
+
+pub fn get_i32s(data_elements: &[DataElement], tag: &str) -> CommonResult<Vec<i32>> {
+    convert_integers(tag, get_i64s(data_elements, tag)?)
+}
+
+pub fn get_i32(data_elements: &[DataElement], tag: &str) -> CommonResult<i32> {
+    single_value(tag, get_i32s(data_elements, tag)?)
+}
+
+pub fn get_u32s(data_elements: &[DataElement], tag: &str) -> CommonResult<Vec<u32>> {
+    convert_integers(tag, get_i64s(data_elements, tag)?)
+}
+
+pub fn get_u32(data_elements: &[DataElement], tag: &str) -> CommonResult<u32> {
+    single_value(tag, get_u32s(data_elements, tag)?)
+}
+
+pub fn get_u16s(data_elements: &[DataElement], tag: &str) -> CommonResult<Vec<u16>> {
+    convert_integers(tag, get_i64s(data_elements, tag)?)
+}
+
+pub fn get_u16(data_elements: &[DataElement], tag: &str) -> CommonResult<u16> {
+    single_value(tag, get_u16s(data_elements, tag)?)
+}
+
+pub fn get_f64s(data_elements: &[DataElement], tag: &str) -> CommonResult<Vec<f64>> {
+    element_to_f64s(find_data_element(data_elements, tag)?)
+}
+
+pub fn get_f64(data_elements: &[DataElement], tag: &str) -> CommonResult<f64> {
+    single_value(tag, get_f64s(data_elements, tag)?)
+}
+
+pub fn get_words(data_elements: &[DataElement], tag: &str) -> CommonResult<Vec<u16>> {
+    element_to_words(find_data_element(data_elements, tag)?)
+}
+
+pub fn get_strs(data_elements: &[DataElement], tag: &str) -> CommonResult<Vec<String>> {
+    element_to_strs(find_data_element(data_elements, tag)?)
+}
+
+pub fn get_str(data_elements: &[DataElement], tag: &str) -> CommonResult<String> {
+    single_value(tag, get_strs(data_elements, tag)?)
+}
+
+pub fn get_dates(data_elements: &[DataElement], tag: &str) -> CommonResult<Vec<DicomDate>> {
+    get_strs(data_elements, tag)?
+        .iter()
+        .map(|v| parse_date(v).ok_or_else(|| format!("{} value {:?} is not a date", tag, v).into()))
+        .collect()
+}
+
+pub fn get_date(data_elements: &[DataElement], tag: &str) -> CommonResult<DicomDate> {
+    single_value(tag, get_dates(data_elements, tag)?)
+}
+
+// DA的格式是YYYYMMDD，兼容旧标准（ACR-NEMA）中的YYYY.MM.DD
+pub fn parse_date(value: &str) -> Option<DicomDate> {
+    let value = value.trim();
+
+    // 按字节比较，避免非ASCII字符时切片落在字符中间
+    let bytes = value.as_bytes();
+    let digits = if bytes.len() == 10 && bytes[4] == b'.' && bytes[7] == b'.' {
+        format!("{}{}{}", &value[..4], &value[5..7], &value[8..])
+    } else {
+        value.to_string()
+    };
+
+    if digits.len() != 8 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let year = digits[..4].parse::<u16>().ok()?;
+    let month = digits[4..6].parse::<u8>().ok()?;
+    let day = digits[6..].parse::<u8>().ok()?;
+
+    let leap_year = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap_year => 29,
+        2 => 28,
+        _ => return None,
+    };
+
+    if day == 0 || day > days_in_month {
+        return None;
+    }
+
+    Some(DicomDate { year, month, day })
+}
+
+// TM的格式是HHMMSS.FFFFFF，后面的部分可以省略，兼容旧标准中的HH:MM:SS.FFFFFF
+// 返回从0点开始的秒数
+pub fn parse_time(value: &str) -> Option<f64> {
+    let value = value.trim().replace(':', "");
+    let (digits, fraction) = match value.split_once('.') {
+        Some((digits, fraction)) => (digits, fraction),
+        None => (value.as_str(), ""),
+    };
+
+    if !matches!(digits.len(), 2 | 4 | 6)
+        || !digits.chars().all(|c| c.is_ascii_digit())
+        || fraction.len() > 6
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+        || (!fraction.is_empty() && digits.len() != 6)
+    {
+        return None;
+    }
+
+    let mut parts = digits
+        .as_bytes()
+        .chunks(2)
+        .map(|v| (v[0] - b'0') as u32 * 10 + (v[1] - b'0') as u32);
+
+    let hours = parts.next()?;
+    let minutes = parts.next().unwrap_or(0);
+    // 60是闰秒
+    let seconds = parts.next().unwrap_or(0);
+
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    let fraction = if fraction.is_empty() {
+        0.0
+    } else {
+        format!("0.{}", fraction).parse::<f64>().ok()?
+    };
+
+    Some((hours * 3600 + minutes * 60 + seconds) as f64 + fraction)
+}
+
+// Private Creator的值，去掉首尾的空格和\0
+fn private_creator_value(data_element: &DataElement) -> Option<String> {
+    let value = match &data_element.data {
+        DicomValue::String(value) => value.clone(),
+        DicomValue::Bytes(value) => String::from_utf8_lossy(value).to_string(),
+        _ => return None,
+    };
+
+    Some(
+        value
+            .trim_matches(|c: char| c == ' ' || c == '\0')
+            .to_string(),
+    )
+}
+
+// 私有数据元素gggg,xxee的Private Creator，即gggg,00xx的值；不是私有数据元素或者没有Private Creator时返回None
+pub fn get_private_creator(data_elements: &[DataElement], tag: &str) -> Option<String> {
+    let (group, element) = crate::util::parse_tag(tag).ok()?;
+
+    if group % 2 == 0 || element < 0x1000 {
+        return None;
+    }
+
+    private_creator_value(
+        find_data_element(
+            data_elements,
+            &format!("{:04X},{:04X}", group, element >> 8),
+        )
+        .ok()?,
+    )
+}
+
+// 结合同一个数据集中的Private Creator在私有字典中查询私有数据元素的名字和VR
+pub fn get_private_tag(data_elements: &[DataElement], tag: &str) -> Option<PrivateTag> {
+    let (group, element) = crate::util::parse_tag(tag).ok()?;
+    let creator = get_private_creator(data_elements, tag)?;
+
+    crate::util::get_private_tag(group, &creator, (element & 0xff) as u8)
+}
+
+// 私有数据元素的tag，参考PS3.5 7.8.1
+// Private Creator (gggg,0010~gggg,00FF)的值为creator时，这个creator保留了gggg,xx00~gggg,xxFF（xx是Private Creator的element）
+// 返回gggg,xxee，ee为element_offset；隐式VR的文件中Private Creator可能没有被识别成LO，这里也接受原始字节
+pub fn find_private_tag(
+    data_elements: &[DataElement],
+    group: u16,
+    creator: &str,
+    element_offset: u8,
+) -> Option<String> {
+    let group = format!("{:04X}", group);
+
+    data_elements
+        .iter()
+        .filter(|v| v.tag_group == group)
+        .filter_map(|v| {
+            let block = u16::from_str_radix(&v.tag_element, 16).ok()?;
+
+            if !(0x10..=0xFF).contains(&block) {
+                return None;
+            }
+
+            (private_creator_value(v)? == creator).then_some(block)
+        })
+        .map(|block| format!("{},{:02X}{:02X}", group, block, element_offset))
+        .next()
+}
@@ -0,0 +1,333 @@
+use crate::{
+    model::{PixelDescription, TransferSyntax},
+    CommonResult,
+};
+
+// 把一帧压缩数据解码成非压缩格式（小端、按像素交错存储）
+pub fn decode_frame(
+    transfer_syntax: TransferSyntax,
+    frame: &[u8],
+    description: &PixelDescription,
+) -> CommonResult<Vec<u8>> {
+    match transfer_syntax {
+        TransferSyntax::RleLossless => rle_decode_frame(frame, description),
+        TransferSyntax::JpegBaseline | TransferSyntax::JpegExtended => {
+            jpeg_decode_frame(frame, description)
+        }
+        _ => Err(format!("no decoder for transfer syntax {:?}", transfer_syntax).into()),
+    }
+}
+
+// 把一帧非压缩数据编码成压缩格式
+pub fn encode_frame(
+    transfer_syntax: TransferSyntax,
+    frame: &[u8],
+    description: &PixelDescription,
+) -> CommonResult<Vec<u8>> {
+    match transfer_syntax {
+        TransferSyntax::RleLossless => rle_encode_frame(frame, description),
+        _ => Err(format!("no encoder for transfer syntax {:?}", transfer_syntax).into()),
+    }
+}
+
+// 解码后的像素数据的组织方式：统一按像素交错存储，JPEG解码得到的彩色图像是RGB
+pub fn decoded_description(
+    transfer_syntax: TransferSyntax,
+    description: &PixelDescription,
+) -> PixelDescription {
+    let mut result = description.clone();
+
+    if transfer_syntax.is_encapsulated() {
+        result.planar_configuration = 0;
+
+        if matches!(
+            transfer_syntax,
+            TransferSyntax::JpegBaseline | TransferSyntax::JpegExtended
+        ) && description.samples_per_pixel == 3
+        {
+            result.photometric_interpretation = "RGB".to_string();
+        }
+    }
+
+    result
+}
+
+// 是否可以解码该传输语法
+pub fn can_decode(transfer_syntax: TransferSyntax) -> bool {
+    !transfer_syntax.is_encapsulated()
+        || matches!(
+            transfer_syntax,
+            TransferSyntax::RleLossless
+                | TransferSyntax::JpegBaseline
+                | TransferSyntax::JpegExtended
+        )
+}
+
+// 是否可以编码成该传输语法
+pub fn can_encode(transfer_syntax: TransferSyntax) -> bool {
+    !transfer_syntax.is_encapsulated() || transfer_syntax == TransferSyntax::RleLossless
+}
+
+// RLE的每个segment保存所有像素中同一个样本的同一个字节，先保存高位字节
+// 返回非压缩数据中(像素, 样本, 字节)所在的位置，byte为0时是最高位字节
+fn native_byte_index(
+    description: &PixelDescription,
+    pixel: usize,
+    sample: usize,
+    byte: usize,
+) -> usize {
+    let bytes_per_sample = description.bits_allocated as usize / 8;
+    let samples_per_pixel = description.samples_per_pixel as usize;
+    let pixel_count = crate::pixel::pixel_count(description);
+
+    // 内存中是小端，最高位字节在最后
+    let byte = bytes_per_sample - 1 - byte;
+
+    if description.planar_configuration == 1 {
+        (sample * pixel_count + pixel) * bytes_per_sample + byte
+    } else {
+        (pixel * samples_per_pixel + sample) * bytes_per_sample + byte
+    }
+}
+
+fn rle_segment_count(description: &PixelDescription) -> CommonResult<usize> {
+    if !description.bits_allocated.is_multiple_of(8) {
+        return Err(format!(
+            "RLE does not support bits allocated {}",
+            description.bits_allocated
+        )
+        .into());
+    }
+
+    let count = description.samples_per_pixel as usize * description.bits_allocated as usize / 8;
+
+    // RLE Header中最多只能记录15个segment
+    if count > 15 {
+        return Err(format!("RLE does not support {} segments", count).into());
+    }
+
+    Ok(count)
+}
+
+// RLE的格式参考PS3.5 Annex G
+// https://dicom.nema.org/medical/dicom/current/output/chtml/part05/chapter_G.html
+pub fn rle_decode_frame(frame: &[u8], description: &PixelDescription) -> CommonResult<Vec<u8>> {
+    if frame.len() < 64 {
+        return Err("RLE header is too short".into());
+    }
+
+    // 64字节的RLE Header：segment的个数以及15个segment的偏移
+    let header = frame[..64]
+        .chunks_exact(4)
+        .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]) as usize)
+        .collect::<Vec<usize>>();
+
+    let segment_count = rle_segment_count(description)?;
+
+    if header[0] != segment_count {
+        return Err(format!(
+            "RLE frame has {} segments, expected {}",
+            header[0], segment_count
+        )
+        .into());
+    }
+
+    let pixel_count = crate::pixel::pixel_count(description);
+    let bytes_per_sample = description.bits_allocated as usize / 8;
+
+    // 解码后统一使用交错存储
+    let output_description = PixelDescription {
+        planar_configuration: 0,
+        ..description.clone()
+    };
+
+    let mut pixels = vec![0_u8; crate::pixel::frame_length(description)];
+
+    for segment_index in 0..segment_count {
+        let start = header[segment_index + 1];
+        let end = if segment_index + 1 < segment_count {
+            header[segment_index + 2]
+        } else {
+            frame.len()
+        };
+
+        if start > end || end > frame.len() {
+            return Err(format!("RLE segment #{} is out of range", segment_index).into());
+        }
+
+        let segment = packbits_decode(&frame[start..end], pixel_count)?;
+
+        let sample = segment_index / bytes_per_sample;
+        let byte = segment_index % bytes_per_sample;
+
+        for (pixel, value) in segment.iter().enumerate() {
+            pixels[native_byte_index(&output_description, pixel, sample, byte)] = *value;
+        }
+    }
+
+    Ok(pixels)
+}
+
+pub fn rle_encode_frame(frame: &[u8], description: &PixelDescription) -> CommonResult<Vec<u8>> {
+    let segment_count = rle_segment_count(description)?;
+    let pixel_count = crate::pixel::pixel_count(description);
+    let bytes_per_sample = description.bits_allocated as usize / 8;
+
+    if frame.len() < crate::pixel::frame_length(description) {
+        return Err(format!(
+            "frame has {} bytes, expected {}",
+            frame.len(),
+            crate::pixel::frame_length(description)
+        )
+        .into());
+    }
+
+    let mut header = vec![segment_count as u32];
+    let mut segments = Vec::new();
+
+    for segment_index in 0..segment_count {
+        let sample = segment_index / bytes_per_sample;
+        let byte = segment_index % bytes_per_sample;
+
+        let values = (0..pixel_count)
+            .map(|pixel| frame[native_byte_index(description, pixel, sample, byte)])
+            .collect::<Vec<u8>>();
+
+        let mut segment = packbits_encode(&values);
+
+        // 每个segment的长度都必须是偶数
+        if !segment.len().is_multiple_of(2) {
+            segment.push(0x00);
+        }
+
+        header.push((64 + segments.len()) as u32);
+        segments.extend(segment);
+    }
+
+    header.resize(16, 0);
+
+    let mut result = header
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<u8>>();
+    result.extend(segments);
+
+    Ok(result)
+}
+
+// PackBits解码：n为0~127时复制后面的n+1个字节，n为-1~-127时把下一个字节重复1-n次，-128不做处理
+fn packbits_decode(segment: &[u8], length: usize) -> CommonResult<Vec<u8>> {
+    let mut result = Vec::with_capacity(length);
+    let mut offset = 0;
+
+    while offset < segment.len() && result.len() < length {
+        let header = segment[offset] as i8;
+        offset += 1;
+
+        if header >= 0 {
+            let count = header as usize + 1;
+
+            if offset + count > segment.len() {
+                return Err("RLE literal run is out of range".into());
+            }
+
+            result.extend_from_slice(&segment[offset..offset + count]);
+            offset += count;
+        } else if header != -128 {
+            let count = 1 - header as isize;
+
+            let value = *segment
+                .get(offset)
+                .ok_or("RLE replicate run is out of range")?;
+
+            result.extend(std::iter::repeat_n(value, count as usize));
+            offset += 1;
+        }
+    }
+
+    if result.len() < length {
+        return Err(format!(
+            "RLE segment decoded to {} bytes, expected {}",
+            result.len(),
+            length
+        )
+        .into());
+    }
+
+    result.truncate(length);
+
+    Ok(result)
+}
+
+fn packbits_encode(values: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+
+    while offset < values.len() {
+        // 计算从当前位置开始相同字节的个数，最多128个
+        let mut run = 1;
+        while offset + run < values.len() && run < 128 && values[offset + run] == values[offset] {
+            run += 1;
+        }
+
+        if run >= 2 {
+            result.push((1 - run as isize) as i8 as u8);
+            result.push(values[offset]);
+            offset += run;
+            continue;
+        }
+
+        // 不重复的字节作为literal run，遇到两个以上的重复字节时结束
+        let start = offset;
+        while offset < values.len()
+            && offset - start < 128
+            && !(offset + 1 < values.len() && values[offset] == values[offset + 1])
+        {
+            offset += 1;
+        }
+
+        result.push((offset - start - 1) as u8);
+        result.extend_from_slice(&values[start..offset]);
+    }
+
+    result
+}
+
+// JPEG Baseline (Process 1)以及8位的Extended (Process 2 & 4)使用image进行解码
+// 彩色图像解码后是RGB，转换传输语法时需要同时修改Photometric Interpretation
+pub fn jpeg_decode_frame(frame: &[u8], description: &PixelDescription) -> CommonResult<Vec<u8>> {
+    if description.bits_allocated != 8 {
+        return Err(format!(
+            "JPEG decoder only supports 8 bit images, got {}",
+            description.bits_allocated
+        )
+        .into());
+    }
+
+    let image = image::load_from_memory_with_format(frame, image::ImageFormat::Jpeg)?;
+
+    if image.width() != description.columns as u32 || image.height() != description.rows as u32 {
+        return Err(format!(
+            "JPEG frame is {} x {}, expected {} x {}",
+            image.width(),
+            image.height(),
+            description.columns,
+            description.rows
+        )
+        .into());
+    }
+
+    let pixels = match description.samples_per_pixel {
+        1 => image.into_luma8().into_raw(),
+        3 => image.into_rgb8().into_raw(),
+        samples_per_pixel => {
+            return Err(format!(
+                "JPEG decoder does not support {} samples per pixel",
+                samples_per_pixel
+            )
+            .into())
+        }
+    };
+
+    Ok(pixels)
+}
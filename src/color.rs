@@ -0,0 +1,393 @@
+use crate::{
+    model::{DataElement, LookupTable, PixelDescription},
+    CommonResult,
+};
+
+// 彩色图像（Samples per Pixel为3）转换成按像素交错存储的8位RGB
+// 按平面存储(Planar Configuration为1)和YBR_FULL_422的降采样布局在这里展开
+// 参考PS3.3 C.7.6.3.1.2
+pub fn frame_to_rgb(frame: &[u8], description: &PixelDescription) -> CommonResult<Vec<u8>> {
+    if description.samples_per_pixel != 3 {
+        return Err(format!(
+            "{} samples per pixel is not a color image",
+            description.samples_per_pixel
+        )
+        .into());
+    }
+
+    let samples = read_color_samples(frame, description)?;
+
+    let convert: fn([u8; 3]) -> [u8; 3] = match description.photometric_interpretation.as_str() {
+        "RGB" => |v| v,
+        "YBR_FULL" | "YBR_FULL_422" | "YBR_ICT" => ybr_full_to_rgb,
+        "YBR_PARTIAL_422" => ybr_partial_to_rgb,
+        "YBR_RCT" => ybr_rct_to_rgb,
+        photometric_interpretation => {
+            return Err(format!(
+                "photometric interpretation {} is not supported for color images",
+                photometric_interpretation
+            )
+            .into())
+        }
+    };
+
+    Ok(samples.into_iter().flat_map(convert).collect())
+}
+
+// 读取每个像素的3个样本，超过8位的样本只保留高8位
+fn read_color_samples(frame: &[u8], description: &PixelDescription) -> CommonResult<Vec<[u8; 3]>> {
+    let pixel_count = crate::pixel::pixel_count(description);
+    let bytes_per_sample = match description.bits_allocated {
+        8 => 1,
+        16 => 2,
+        bits_allocated => {
+            return Err(format!(
+                "bits allocated {} is not supported for color images",
+                bits_allocated
+            )
+            .into())
+        }
+    };
+
+    // 422每行中相邻的两个像素共用Cb、Cr，标准要求Columns为偶数
+    if crate::pixel::is_subsampled_422(description) && !description.columns.is_multiple_of(2) {
+        return Err(format!(
+            "{} requires an even number of columns, got {}",
+            description.photometric_interpretation, description.columns
+        )
+        .into());
+    }
+
+    if frame.len() < crate::pixel::frame_length(description) {
+        return Err(format!(
+            "frame has {} bytes, expected {}",
+            frame.len(),
+            crate::pixel::frame_length(description)
+        )
+        .into());
+    }
+
+    let bits_stored = description.bits_stored.min(description.bits_allocated);
+    let mask = (1_u32 << bits_stored) - 1;
+    // 有效位的最高位在high_bit，先右对齐，超过8位时再只保留高8位
+    let high_bit_shift = (description.high_bit + 1).saturating_sub(bits_stored);
+    let shift = bits_stored.saturating_sub(8);
+
+    // 第index个样本（按照存储顺序）
+    let sample = |index: usize| -> u8 {
+        let offset = index * bytes_per_sample;
+
+        let value = if bytes_per_sample == 1 {
+            frame[offset] as u32
+        } else {
+            u16::from_le_bytes([frame[offset], frame[offset + 1]]) as u32
+        };
+
+        (((value >> high_bit_shift) & mask) >> shift) as u8
+    };
+
+    let mut result = Vec::with_capacity(pixel_count);
+
+    if crate::pixel::is_subsampled_422(description) {
+        // 每两个像素按照Y1 Y2 Cb Cr的顺序存储，两个像素共用Cb、Cr
+        for pixel in 0..pixel_count {
+            let group = pixel / 2 * 4;
+
+            result.push([
+                sample(group + pixel % 2),
+                sample(group + 2),
+                sample(group + 3),
+            ]);
+        }
+    } else if description.planar_configuration == 1 {
+        for pixel in 0..pixel_count {
+            result.push([
+                sample(pixel),
+                sample(pixel_count + pixel),
+                sample(2 * pixel_count + pixel),
+            ]);
+        }
+    } else {
+        for pixel in 0..pixel_count {
+            result.push([
+                sample(pixel * 3),
+                sample(pixel * 3 + 1),
+                sample(pixel * 3 + 2),
+            ]);
+        }
+    }
+
+    Ok(result)
+}
+
+fn clamp_to_u8(value: f64) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+// YBR_FULL使用JPEG File Interchange Format中的转换公式，Cb、Cr以128为中心
+// YBR_ICT是JPEG 2000中的不可逆颜色变换，系数与YBR_FULL相同
+fn ybr_full_to_rgb([y, cb, cr]: [u8; 3]) -> [u8; 3] {
+    let y = y as f64;
+    let cb = cb as f64 - 128.0;
+    let cr = cr as f64 - 128.0;
+
+    [
+        clamp_to_u8(y + 1.402 * cr),
+        clamp_to_u8(y - 0.344136 * cb - 0.714136 * cr),
+        clamp_to_u8(y + 1.772 * cb),
+    ]
+}
+
+// YBR_PARTIAL中Y的范围是16~235，Cb、Cr的范围是16~240
+fn ybr_partial_to_rgb([y, cb, cr]: [u8; 3]) -> [u8; 3] {
+    let y = 1.164 * (y as f64 - 16.0);
+    let cb = cb as f64 - 128.0;
+    let cr = cr as f64 - 128.0;
+
+    [
+        clamp_to_u8(y + 1.596 * cr),
+        clamp_to_u8(y - 0.392 * cb - 0.813 * cr),
+        clamp_to_u8(y + 2.017 * cb),
+    ]
+}
+
+// YBR_RCT是JPEG 2000中的可逆颜色变换，使用整数运算
+fn ybr_rct_to_rgb([y, cb, cr]: [u8; 3]) -> [u8; 3] {
+    let y = y as i32;
+    let cb = cb as i32 - 128;
+    let cr = cr as i32 - 128;
+
+    let g = y - (cb + cr).div_euclid(4);
+
+    [
+        (cr + g).clamp(0, 255) as u8,
+        g.clamp(0, 255) as u8,
+        (cb + g).clamp(0, 255) as u8,
+    ]
+}
+
+// 读取一个LUT描述符（US或SS，VM为3）
+// 第一个被映射的值在像素有符号时按照SS解释，表项个数为0时表示65536
+pub fn get_lut_descriptor(
+    data_elements: &[DataElement],
+    tag: &str,
+    pixel_representation: u16,
+) -> CommonResult<(usize, i64, u16)> {
+    let descriptor = crate::accessor::get_i64s(data_elements, tag)?;
+
+    if descriptor.len() != 3 {
+        return Err(format!("{} has VM {}, expected 3", tag, descriptor.len()).into());
+    }
+
+    let number_of_entries = match descriptor[0] as u16 {
+        0 => 65536,
+        v => v as usize,
+    };
+
+    let first_mapped_value = if pixel_representation == 1 {
+        descriptor[1] as u16 as i16 as i64
+    } else {
+        descriptor[1] as u16 as i64
+    };
+
+    let bits_per_entry = u16::try_from(descriptor[2])?;
+
+    Ok((number_of_entries, first_mapped_value, bits_per_entry))
+}
+
+// 读取Palette Color的红、绿、蓝三个LUT
+// 每个颜色可以使用普通的LUT Data (0028,1201-1203)或者Segmented LUT Data (0028,1221-1223)
+pub fn get_palette_color_luts(
+    data_elements: &[DataElement],
+    pixel_representation: u16,
+) -> CommonResult<[LookupTable; 3]> {
+    let read = |descriptor_tag: &str, data_tag: &str, segmented_data_tag: &str| {
+        let (number_of_entries, first_mapped_value, bits_per_entry) =
+            get_lut_descriptor(data_elements, descriptor_tag, pixel_representation)?;
+
+        let mut data = if crate::accessor::has_value(data_elements, data_tag) {
+            read_lut_data(data_elements, data_tag, number_of_entries, bits_per_entry)?
+        } else {
+            expand_segmented_lut(&crate::accessor::get_words(
+                data_elements,
+                segmented_data_tag,
+            )?)?
+        };
+
+        if data.is_empty() {
+            return Err(format!("{} is empty", data_tag).into());
+        }
+
+        data.truncate(number_of_entries);
+
+        CommonResult::Ok(LookupTable {
+            first_mapped_value,
+            bits_per_entry,
+            data,
+        })
+    };
+
+    Ok([
+        read("0028,1101", "0028,1201", "0028,1221")?,
+        read("0028,1102", "0028,1202", "0028,1222")?,
+        read("0028,1103", "0028,1203", "0028,1223")?,
+    ])
+}
+
+// 读取普通的LUT Data
+// 8位的表项有的文件每个字节一个表项，有的文件每个16位字一个表项，这里按照长度区分
+pub fn read_lut_data(
+    data_elements: &[DataElement],
+    tag: &str,
+    number_of_entries: usize,
+    bits_per_entry: u16,
+) -> CommonResult<Vec<u16>> {
+    let data_element = crate::accessor::find_data_element(data_elements, tag)?;
+
+    if bits_per_entry <= 8 {
+        if let crate::model::DicomValue::Bytes(v) = &data_element.data {
+            if v.len() == number_of_entries || v.len() == number_of_entries + 1 {
+                return Ok(v[..number_of_entries].iter().map(|v| *v as u16).collect());
+            }
+        }
+    }
+
+    crate::accessor::element_to_words(data_element)
+}
+
+// 展开Segmented LUT Data，参考PS3.3 C.7.9.2
+// 每个segment以opcode开头：0为离散段，1为线性段，2为间接段（引用之前的若干个segment）
+pub fn expand_segmented_lut(words: &[u16]) -> CommonResult<Vec<u16>> {
+    let mut result = Vec::new();
+
+    expand_segments(words, 0, usize::MAX, &mut result)?;
+
+    Ok(result)
+}
+
+fn expand_segments(
+    words: &[u16],
+    mut offset: usize,
+    max_segments: usize,
+    result: &mut Vec<u16>,
+) -> CommonResult<()> {
+    let word = |index: usize| -> CommonResult<u16> {
+        words
+            .get(index)
+            .copied()
+            .ok_or_else(|| "segmented LUT data is truncated".into())
+    };
+
+    let mut segments = 0;
+
+    while offset < words.len() && segments < max_segments {
+        let opcode = word(offset)?;
+        let length = word(offset + 1)? as usize;
+
+        match opcode {
+            0 => {
+                for index in 0..length {
+                    result.push(word(offset + 2 + index)?);
+                }
+
+                offset += 2 + length;
+            }
+            1 => {
+                // 线性段从上一个段的最后一个值开始插值
+                let y0 = *result
+                    .last()
+                    .ok_or("linear segment can not be the first segment")?
+                    as f64;
+                let y1 = word(offset + 2)? as f64;
+
+                for index in 1..=length {
+                    result.push((y0 + (y1 - y0) * index as f64 / length as f64).round() as u16);
+                }
+
+                offset += 3;
+            }
+            2 => {
+                if result.is_empty() {
+                    return Err("indirect segment can not be the first segment".into());
+                }
+
+                // 偏移是32位的字节偏移，先存低16位
+                let byte_offset = word(offset + 2)? as usize | (word(offset + 3)? as usize) << 16;
+
+                if byte_offset / 2 >= offset {
+                    return Err("indirect segment must refer to a previous segment".into());
+                }
+
+                expand_segments(words, byte_offset / 2, length, result)?;
+
+                offset += 4;
+            }
+            _ => return Err(format!("unknown segmented LUT opcode {}", opcode).into()),
+        }
+
+        segments += 1;
+    }
+
+    Ok(())
+}
+
+// 按照Palette Color LUT把像素值转换成8位RGB
+pub fn palette_to_rgb(values: &[i64], luts: &[LookupTable; 3]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| {
+            luts.iter().map(|lut| {
+                let entry = lut.lookup(*value);
+
+                // 16位的表项只保留高8位
+                if lut.bits_per_entry > 8 {
+                    (entry >> (lut.bits_per_entry - 8)) as u8
+                } else {
+                    entry as u8
+                }
+            })
+        })
+        .collect()
+}
+
+// DICOM中编码后的CIELab值（每个分量0~0xFFFF）转换成8位sRGB，参考PS3.3 C.10.7.1.1
+// L*为0~100，a*、b*为-128~127，这里使用D65白点
+pub fn cielab_to_rgb(value: [u16; 3]) -> [u8; 3] {
+    let l = value[0] as f64 * 100.0 / 65535.0;
+    let a = value[1] as f64 * 255.0 / 65535.0 - 128.0;
+    let b = value[2] as f64 * 255.0 / 65535.0 - 128.0;
+
+    let f_y = (l + 16.0) / 116.0;
+    let f_x = f_y + a / 500.0;
+    let f_z = f_y - b / 200.0;
+
+    let f_inverse = |t: f64| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            3.0 * (6.0_f64 / 29.0).powi(2) * (t - 4.0 / 29.0)
+        }
+    };
+
+    let x = 0.95047 * f_inverse(f_x);
+    let y = f_inverse(f_y);
+    let z = 1.08883 * f_inverse(f_z);
+
+    let linear = [
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    ];
+
+    linear.map(|v| {
+        let v = v.clamp(0.0, 1.0);
+
+        let v = if v <= 0.0031308 {
+            12.92 * v
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        };
+
+        clamp_to_u8(v * 255.0)
+    })
+}
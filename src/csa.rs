@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    accessor,
+    model::{CsaElement, CsaFormat, CsaHeader, DataElement, DicomValue},
+    mosaic::SIEMENS_CSA_HEADER,
+    CommonResult,
+};
+
+// Siemens CSA header的二进制格式，所有整数都是小端，参考nibabel的nicom/csareader.py
+// SV10："SV10" + 4字节 + 元素个数(u32) + 77(u32)，旧格式没有前8个字节
+// 每个元素：名字(64字节，以0结尾) + VM(i32) + VR(4字节) + SyngoDT(i32) + item个数(i32) + 77或205(i32)
+// 每个item：4个i32 + 以0结尾的字符串，按照4字节对齐
+// SV10中item的长度是第二个i32，旧格式中是第一个i32减去第一个元素的item个数
+
+// 元素个数和item个数的上限，超过时认为不是CSA header
+const MAX_ITEMS: i64 = 1000;
+
+fn read_i32(buffer: &[u8], offset: usize) -> CommonResult<i32> {
+    let bytes = buffer
+        .get(offset..offset + 4)
+        .ok_or_else(|| format!("CSA header is truncated at offset {}", offset))?;
+
+    Ok(i32::from_le_bytes(bytes.try_into()?))
+}
+
+// 以0结尾的字符串
+fn read_string(buffer: &[u8]) -> String {
+    let end = buffer.iter().position(|v| *v == 0).unwrap_or(buffer.len());
+
+    String::from_utf8_lossy(&buffer[..end]).trim().to_string()
+}
+
+pub fn parse_csa_header(buffer: &[u8]) -> CommonResult<CsaHeader> {
+    let (format, mut offset) = if buffer.starts_with(b"SV10") {
+        (CsaFormat::Sv10, 8)
+    } else {
+        (CsaFormat::Legacy, 0)
+    };
+
+    let count = read_i32(buffer, offset)? as u32 as i64;
+    offset += 8;
+
+    if !(1..=MAX_ITEMS).contains(&count) {
+        return Err(format!("CSA header has {} elements", count).into());
+    }
+
+    let mut elements = BTreeMap::new();
+    let mut first_item_count = None;
+
+    for _ in 0..count {
+        if buffer.len() < offset + 84 {
+            return Err(format!("CSA header is truncated at offset {}", offset).into());
+        }
+
+        let name = read_string(&buffer[offset..offset + 64]);
+        let vm = read_i32(buffer, offset + 64)?;
+        let vr = read_string(&buffer[offset + 68..offset + 72]);
+        let syngo_dt = read_i32(buffer, offset + 72)?;
+        let item_count = read_i32(buffer, offset + 76)? as i64;
+        offset += 84;
+
+        if !(0..=MAX_ITEMS).contains(&item_count) {
+            return Err(format!("CSA element {} has {} items", name, item_count).into());
+        }
+
+        let first_item_count = *first_item_count.get_or_insert(item_count);
+
+        // VM为0时所有的item都是值，否则只有前VM个item是值，后面的item长度为0
+        let value_count = if vm > 0 { vm as i64 } else { item_count };
+        let mut values = Vec::new();
+
+        for index in 0..item_count {
+            let item_length = match format {
+                CsaFormat::Sv10 => read_i32(buffer, offset + 4)? as i64,
+                CsaFormat::Legacy => read_i32(buffer, offset)? as i64 - first_item_count,
+            };
+            offset += 16;
+
+            if item_length < 0 || offset + item_length as usize > buffer.len() {
+                // 旧格式的长度不可靠，读不下去时停止读取这个元素
+                if format == CsaFormat::Legacy {
+                    break;
+                }
+
+                return Err(format!(
+                    "item {} of CSA element {} has an invalid length {}",
+                    index, name, item_length
+                )
+                .into());
+            }
+
+            let item_length = item_length as usize;
+
+            if index < value_count {
+                values.push(read_string(&buffer[offset..offset + item_length]));
+            }
+
+            offset += item_length.div_ceil(4) * 4;
+        }
+
+        // item的个数通常比实际的值多，去掉末尾的空值
+        while values.last().is_some_and(|v| v.is_empty()) {
+            values.pop();
+        }
+
+        elements.insert(
+            name,
+            CsaElement {
+                vr,
+                vm,
+                syngo_dt,
+                values,
+            },
+        );
+    }
+
+    Ok(CsaHeader { format, elements })
+}
+
+// 按照Private Creator "SIEMENS CSA HEADER"找到(0029,xx{element_offset})并解析，没有时返回None
+fn get_csa_header(
+    data_elements: &[DataElement],
+    element_offset: u8,
+) -> CommonResult<Option<CsaHeader>> {
+    let tag =
+        match accessor::find_private_tag(data_elements, 0x0029, SIEMENS_CSA_HEADER, element_offset)
+        {
+            Some(tag) if accessor::has_value(data_elements, &tag) => tag,
+            _ => return Ok(None),
+        };
+
+    match &accessor::find_data_element(data_elements, &tag)?.data {
+        DicomValue::Bytes(v) => Ok(Some(parse_csa_header(v)?)),
+        _ => Err(format!("{} is not binary data", tag).into()),
+    }
+}
+
+// CSA Image Header Info (0029,xx10)
+pub fn get_csa_image_header(data_elements: &[DataElement]) -> CommonResult<Option<CsaHeader>> {
+    get_csa_header(data_elements, 0x10)
+}
+
+// CSA Series Header Info (0029,xx20)
+pub fn get_csa_series_header(data_elements: &[DataElement]) -> CommonResult<Option<CsaHeader>> {
+    get_csa_header(data_elements, 0x20)
+}
+
+pub fn get_csa_strs(header: &CsaHeader, name: &str) -> CommonResult<Vec<String>> {
+    header
+        .elements
+        .get(name)
+        .map(|v| v.values.clone())
+        .ok_or_else(|| format!("CSA element {} is missing", name).into())
+}
+
+pub fn get_csa_f64s(header: &CsaHeader, name: &str) -> CommonResult<Vec<f64>> {
+    get_csa_strs(header, name)?
+        .iter()
+        .map(|v| {
+            v.parse::<f64>()
+                .map_err(|_| format!("CSA element {} value {:?} is not a number", name, v).into())
+        })
+        .collect()
+}
+
+// 只取第一个值，CSA中VM为1的元素后面也可能有多余的item
+pub fn get_csa_f64(header: &CsaHeader, name: &str) -> CommonResult<f64> {
+    get_csa_f64s(header, name)?
+        .first()
+        .copied()
+        .ok_or_else(|| format!("CSA element {} has no value", name).into())
+}
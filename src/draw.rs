@@ -0,0 +1,227 @@
+// 在图像上画注释用的简单图元，坐标是（x, y），即（列, 行），从0开始
+// 所有的函数都通过plot画单个像素，由调用方负责裁剪到图像范围内和选择颜色
+
+// Bresenham直线
+pub fn line(from: (f64, f64), to: (f64, f64), plot: &mut dyn FnMut(i64, i64)) {
+    let (mut x0, mut y0) = (from.0.floor() as i64, from.1.floor() as i64);
+    let (x1, y1) = (to.0.floor() as i64, to.1.floor() as i64);
+
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let step_x = if x0 < x1 { 1 } else { -1 };
+    let step_y = if y0 < y1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    loop {
+        plot(x0, y0);
+
+        if x0 == x1 && y0 == y1 {
+            break;
+        }
+
+        let doubled = 2 * error;
+
+        if doubled >= dy {
+            error += dy;
+            x0 += step_x;
+        }
+
+        if doubled <= dx {
+            error += dx;
+            y0 += step_y;
+        }
+    }
+}
+
+pub fn polyline(points: &[(f64, f64)], plot: &mut dyn FnMut(i64, i64)) {
+    if let [point] = points {
+        plot(point.0.floor() as i64, point.1.floor() as i64);
+    }
+
+    for pair in points.windows(2) {
+        line(pair[0], pair[1], plot);
+    }
+}
+
+// 扫描线填充多边形（奇偶规则），采样点是每个像素的中心
+pub fn fill_polygon(points: &[(f64, f64)], plot: &mut dyn FnMut(i64, i64)) {
+    if points.len() < 3 {
+        return polyline(points, plot);
+    }
+
+    let min_y = points.iter().map(|v| v.1).fold(f64::INFINITY, f64::min);
+    let max_y = points.iter().map(|v| v.1).fold(f64::NEG_INFINITY, f64::max);
+
+    for y in min_y.floor() as i64..=max_y.ceil() as i64 {
+        let center_y = y as f64 + 0.5;
+        let mut crossings = Vec::new();
+
+        for index in 0..points.len() {
+            let a = points[index];
+            let b = points[(index + 1) % points.len()];
+
+            if (a.1 > center_y) != (b.1 > center_y) {
+                crossings.push(a.0 + (center_y - a.1) * (b.0 - a.0) / (b.1 - a.1));
+            }
+        }
+
+        crossings.sort_by(f64::total_cmp);
+
+        for pair in crossings.chunks_exact(2) {
+            for x in (pair[0] - 0.5).ceil() as i64..=(pair[1] - 0.5).floor() as i64 {
+                plot(x, y);
+            }
+        }
+    }
+
+    // 边界也画上，避免很窄的多边形被漏掉
+    let mut closed = points.to_vec();
+    closed.push(points[0]);
+    polyline(&closed, plot);
+}
+
+// 把椭圆（或者圆）近似成多边形，axis_a、axis_b是从圆心出发的两个半轴向量
+pub fn ellipse_points(
+    center: (f64, f64),
+    axis_a: (f64, f64),
+    axis_b: (f64, f64),
+) -> Vec<(f64, f64)> {
+    let radius = (axis_a.0.hypot(axis_a.1)).max(axis_b.0.hypot(axis_b.1));
+    let segments = ((radius * 2.0 * std::f64::consts::PI / 2.0).ceil() as usize).clamp(16, 720);
+
+    (0..segments)
+        .map(|index| {
+            let angle = index as f64 * 2.0 * std::f64::consts::PI / segments as f64;
+            let (sin, cos) = angle.sin_cos();
+
+            (
+                center.0 + axis_a.0 * cos + axis_b.0 * sin,
+                center.1 + axis_a.1 * cos + axis_b.1 * sin,
+            )
+        })
+        .collect()
+}
+
+pub const GLYPH_WIDTH: i64 = 6;
+pub const GLYPH_HEIGHT: i64 = 8;
+
+// 5x7的ASCII点阵字体（0x20~0x5F），每个字符5列，每列的最低位是最上面的一行
+// 小写字母按照大写字母显示，其他字符显示为?
+const FONT: [[u8; 5]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x5F, 0x00, 0x00],
+    [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7F, 0x14, 0x7F, 0x14],
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12],
+    [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x55, 0x22, 0x50],
+    [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1C, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1C, 0x00],
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08],
+    [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00],
+    [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x60, 0x60, 0x00, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3E, 0x51, 0x49, 0x45, 0x3E],
+    [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46],
+    [0x21, 0x41, 0x45, 0x4B, 0x31],
+    [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3C, 0x4A, 0x49, 0x49, 0x30],
+    [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36],
+    [0x06, 0x49, 0x49, 0x29, 0x1E],
+    [0x00, 0x36, 0x36, 0x00, 0x00],
+    [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00],
+    [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08],
+    [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3E],
+    [0x7E, 0x11, 0x11, 0x11, 0x7E],
+    [0x7F, 0x49, 0x49, 0x49, 0x36],
+    [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x22, 0x1C],
+    [0x7F, 0x49, 0x49, 0x49, 0x41],
+    [0x7F, 0x09, 0x09, 0x01, 0x01],
+    [0x3E, 0x41, 0x41, 0x51, 0x32],
+    [0x7F, 0x08, 0x08, 0x08, 0x7F],
+    [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01],
+    [0x7F, 0x08, 0x14, 0x22, 0x41],
+    [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x04, 0x02, 0x7F],
+    [0x7F, 0x04, 0x08, 0x10, 0x7F],
+    [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06],
+    [0x3E, 0x41, 0x51, 0x21, 0x5E],
+    [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7F, 0x01, 0x01],
+    [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F],
+    [0x7F, 0x20, 0x18, 0x20, 0x7F],
+    [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03],
+    [0x61, 0x51, 0x49, 0x45, 0x43],
+    [0x00, 0x7F, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20],
+    [0x00, 0x41, 0x41, 0x7F, 0x00],
+    [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40],
+];
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    let c = c.to_ascii_uppercase();
+
+    match c as u32 {
+        code @ 0x20..=0x5F => &FONT[(code - 0x20) as usize],
+        _ => &FONT[('?' as u32 - 0x20) as usize],
+    }
+}
+
+// 从左上角(x, y)开始画文字，max_width不为None时按照宽度换行，\n和\r也会换行
+// 返回文字占用的宽度和高度
+pub fn text(
+    value: &str,
+    x: i64,
+    y: i64,
+    max_width: Option<i64>,
+    plot: &mut dyn FnMut(i64, i64),
+) -> (i64, i64) {
+    let characters_per_line = max_width
+        .map(|v| (v / GLYPH_WIDTH).max(1) as usize)
+        .unwrap_or(usize::MAX);
+
+    let mut lines = Vec::new();
+
+    for paragraph in value.split(['\n', '\r']).filter(|v| !v.is_empty()) {
+        let characters = paragraph.chars().collect::<Vec<char>>();
+
+        for chunk in characters.chunks(characters_per_line) {
+            lines.push(chunk.to_vec());
+        }
+    }
+
+    for (line_index, characters) in lines.iter().enumerate() {
+        for (index, c) in characters.iter().enumerate() {
+            let left = x + index as i64 * GLYPH_WIDTH;
+            let top = y + line_index as i64 * GLYPH_HEIGHT;
+
+            for (column, bits) in glyph(*c).iter().enumerate() {
+                for row in 0..7 {
+                    if bits >> row & 1 == 1 {
+                        plot(left + column as i64, top + row);
+                    }
+                }
+            }
+        }
+    }
+
+    let width = lines.iter().map(|v| v.len()).max().unwrap_or(0) as i64 * GLYPH_WIDTH;
+
+    (width, lines.len() as i64 * GLYPH_HEIGHT)
+}
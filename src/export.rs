@@ -0,0 +1,533 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::{ImageBuffer, Luma};
+
+use crate::{
+    accessor,
+    model::{
+        DataElement, ExportFormat, ModalityTransform, NpyArray, NpyData, PixelDescription,
+        SkippedFile,
+    },
+    CommonResult,
+};
+
+// 导出Modality LUT的输出值（CT值、SUV等），不经过VOI LUT，不压缩到8位
+// 每个导出的文件旁边会写一个同名的.json，记录Rescale和空间分辨率等信息
+
+// 第frame_index帧（从0开始）的Modality LUT输出值，只支持灰度图像
+pub fn modality_frame(
+    data_elements: &[DataElement],
+    frame_index: usize,
+) -> CommonResult<(PixelDescription, Vec<f64>)> {
+    let (description, _, values) = stored_and_modality_frame(data_elements, frame_index)?;
+
+    Ok((description, values))
+}
+
+// 同时返回存储值，用来识别填充像素
+fn stored_and_modality_frame(
+    data_elements: &[DataElement],
+    frame_index: usize,
+) -> CommonResult<(PixelDescription, Vec<i64>, Vec<f64>)> {
+    let frames = crate::pixel::frames(data_elements)?;
+    let description = frames.description().clone();
+
+    if description.samples_per_pixel != 1
+        || !description
+            .photometric_interpretation
+            .starts_with("MONOCHROME")
+    {
+        return Err(format!(
+            "photometric interpretation {} can not be exported as modality values",
+            description.photometric_interpretation
+        )
+        .into());
+    }
+
+    let values = crate::pixel::frame_to_values(&frames.frame(frame_index)?, &description)?;
+    let modality_transform =
+        crate::lut::get_modality_transform(data_elements, description.pixel_representation)?;
+
+    let modality_values = values
+        .iter()
+        .map(|v| crate::lut::apply_modality(*v, &modality_transform))
+        .collect();
+
+    Ok((description, values, modality_values))
+}
+
+// 导出第frame_index帧到file_path，并在同一目录下写出sidecar（扩展名为.json）
+pub fn export_frame<P: AsRef<Path>>(
+    data_elements: &[DataElement],
+    frame_index: usize,
+    file_path: P,
+    format: ExportFormat,
+) -> CommonResult<()> {
+    let file_path = file_path.as_ref();
+    let (description, stored_values, values) =
+        stored_and_modality_frame(data_elements, frame_index)?;
+    let padding =
+        crate::padding::get_pixel_padding(data_elements, description.pixel_representation)?;
+
+    let width = description.columns as u32;
+    let height = description.rows as u32;
+
+    // 16位无符号整数不能保存负值，最小值小于0时整体平移，原始值 = 保存的值 + value_offset
+    let value_offset = match format {
+        // 填充像素不参与计算，保存为0
+        ExportFormat::Png16 | ExportFormat::Tiff16 => values
+            .iter()
+            .zip(&stored_values)
+            .filter(|(_, stored)| {
+                padding.is_none_or(|padding| !crate::padding::is_padding(&padding, **stored))
+            })
+            .map(|(v, _)| *v)
+            .filter(|v| v.is_finite())
+            .fold(0.0, f64::min)
+            .floor(),
+        ExportFormat::TiffFloat | ExportFormat::Npy => 0.0,
+    };
+
+    match format {
+        ExportFormat::Png16 | ExportFormat::Tiff16 => {
+            // 只有整数值才能无损保存，有小数部分（比如PET的SUV）或者平移后超出16位时报错，
+            // 而不是四舍五入或截断，这种情况应该导出为浮点TIFF或.npy
+            let pixels = values
+                .iter()
+                .zip(&stored_values)
+                .map(|(v, stored)| {
+                    if padding.is_some_and(|padding| crate::padding::is_padding(&padding, *stored))
+                    {
+                        return Ok(0);
+                    }
+
+                    if !v.is_finite() || v.fract() != 0.0 {
+                        return Err(format!(
+                            "modality value {} is not an integer and can not be saved as 16-bit, export as float tiff or npy instead",
+                            v
+                        ));
+                    }
+
+                    u16::try_from((v - value_offset) as i64).map_err(|_| {
+                        format!(
+                            "modality value {} does not fit in 16 bits after subtracting the offset {}, export as float tiff or npy instead",
+                            v, value_offset
+                        )
+                    })
+                })
+                .collect::<Result<Vec<u16>, String>>()?;
+
+            let image = ImageBuffer::<Luma<u16>, _>::from_raw(width, height, pixels)
+                .ok_or("pixel data does not match the image size")?;
+
+            let image_format = if format == ExportFormat::Png16 {
+                image::ImageFormat::Png
+            } else {
+                image::ImageFormat::Tiff
+            };
+
+            image.save_with_format(file_path, image_format)?;
+        }
+        ExportFormat::TiffFloat => {
+            // image的TIFF编码器不支持单通道浮点数，这里直接使用tiff
+            let pixels = values.iter().map(|v| *v as f32).collect::<Vec<f32>>();
+
+            let mut encoder =
+                tiff::encoder::TiffEncoder::new(BufWriter::new(File::create(file_path)?))?;
+            encoder.write_image::<tiff::encoder::colortype::Gray32Float>(width, height, &pixels)?;
+        }
+        ExportFormat::Npy => {
+            let array = NpyArray {
+                shape: vec![height as usize, width as usize],
+                data: NpyData::F32(values.iter().map(|v| *v as f32).collect()),
+            };
+
+            write_npy(file_path, &array)?;
+        }
+    }
+
+    std::fs::write(
+        file_path.with_extension("json"),
+        sidecar_json(
+            data_elements,
+            &description,
+            frame_index,
+            format,
+            value_offset,
+        )?,
+    )?;
+
+    Ok(())
+}
+
+// .npy格式的文件头，参考numpy.lib.format（1.0版本）
+// magic、版本号、2字节的头长度之后是Python字面量形式的字典，整个文件头按照64字节对齐
+fn npy_header(descr: &str, shape: &[usize]) -> Vec<u8> {
+    let shape = match shape {
+        [length] => format!("({},)", length),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    };
+
+    let mut dictionary = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+
+    // magic(6) + 版本号(2) + 头长度(2) + 字典 + 换行
+    let total_length = (10 + dictionary.len() + 1).div_ceil(64) * 64;
+    dictionary.push_str(&" ".repeat(total_length - 10 - dictionary.len() - 1));
+    dictionary.push('\n');
+
+    let mut result = b"\x93NUMPY\x01\x00".to_vec();
+    result.extend((dictionary.len() as u16).to_le_bytes());
+    result.extend(dictionary.as_bytes());
+
+    result
+}
+
+// 所有帧的像素值，shape为(frames, rows, columns, samples)
+// modality为false时保存存储值：8位无符号为uint8，16位无符号为uint16，有符号（8位或16位）为int16，其他为float32
+// modality为true时保存Modality LUT的输出值，dtype为float32
+// 彩色图像转换成8位RGB保存，PALETTE COLOR保存索引值
+pub fn pixel_array(data_elements: &[DataElement], modality: bool) -> CommonResult<NpyArray> {
+    let frames = crate::pixel::frames(data_elements)?;
+    let description = frames.description().clone();
+
+    let samples = if description.samples_per_pixel == 3 {
+        3
+    } else {
+        1
+    };
+
+    let shape = vec![
+        description.number_of_frames as usize,
+        description.rows as usize,
+        description.columns as usize,
+        samples,
+    ];
+
+    if samples == 3 {
+        let mut data = Vec::new();
+
+        for frame in frames {
+            data.extend(crate::color::frame_to_rgb(&frame?, &description)?);
+        }
+
+        return Ok(NpyArray {
+            shape,
+            data: NpyData::U8(data),
+        });
+    }
+
+    let modality_transform = if modality {
+        Some(crate::lut::get_modality_transform(
+            data_elements,
+            description.pixel_representation,
+        )?)
+    } else {
+        None
+    };
+
+    let mut values = Vec::new();
+
+    for frame in frames {
+        values.extend(crate::pixel::frame_to_values(&frame?, &description)?);
+    }
+
+    let data = match (
+        &modality_transform,
+        description.pixel_representation,
+        description.bits_allocated,
+    ) {
+        (Some(transform), _, _) => NpyData::F32(
+            values
+                .iter()
+                .map(|v| crate::lut::apply_modality(*v, transform) as f32)
+                .collect(),
+        ),
+        (None, 0, 1 | 8) => NpyData::U8(values.iter().map(|v| *v as u8).collect()),
+        (None, 0, 16) => NpyData::U16(values.iter().map(|v| *v as u16).collect()),
+        (None, 1, 8 | 16) => NpyData::I16(values.iter().map(|v| *v as i16).collect()),
+        _ => NpyData::F32(values.iter().map(|v| *v as f32).collect()),
+    };
+
+    Ok(NpyArray { shape, data })
+}
+
+// 按照.npy格式编码，数据都是小端
+pub fn encode_npy(array: &NpyArray) -> CommonResult<Vec<u8>> {
+    let (descr, length) = match &array.data {
+        NpyData::U8(v) => ("|u1", v.len()),
+        NpyData::U16(v) => ("<u2", v.len()),
+        NpyData::I16(v) => ("<i2", v.len()),
+        NpyData::F32(v) => ("<f4", v.len()),
+    };
+
+    if array.shape.iter().product::<usize>() != length {
+        return Err(format!("shape {:?} does not match {} values", array.shape, length).into());
+    }
+
+    let mut result = npy_header(descr, &array.shape);
+
+    match &array.data {
+        NpyData::U8(v) => result.extend(v),
+        NpyData::U16(v) => result.extend(v.iter().flat_map(|v| v.to_le_bytes())),
+        NpyData::I16(v) => result.extend(v.iter().flat_map(|v| v.to_le_bytes())),
+        NpyData::F32(v) => result.extend(v.iter().flat_map(|v| v.to_le_bytes())),
+    }
+
+    Ok(result)
+}
+
+pub fn write_npy<P: AsRef<Path>>(file_path: P, array: &NpyArray) -> CommonResult<()> {
+    std::fs::write(file_path, encode_npy(array)?)?;
+
+    Ok(())
+}
+
+// .npz是不压缩的zip文件，每个数组保存为一个name.npy，与numpy.savez相同
+// 参考PKWARE APPNOTE.TXT中的local file header、central directory和end of central directory
+pub fn write_npz<P: AsRef<Path>>(file_path: P, arrays: &[(String, NpyArray)]) -> CommonResult<()> {
+    let mut content = Vec::new();
+    let mut central_directory = Vec::new();
+
+    for (name, array) in arrays {
+        let data = encode_npy(array)?;
+        let file_name = format!("{}.npy", name);
+        let offset = content.len();
+
+        if data.len() > u32::MAX as usize || offset > u32::MAX as usize {
+            return Err("npz larger than 4GB is not supported".into());
+        }
+
+        let crc = crc32fast::hash(&data);
+
+        // 版本、标志、压缩方式（0为不压缩）、修改时间、修改日期
+        let mut common = Vec::new();
+        common.extend(20_u16.to_le_bytes());
+        common.extend(0_u16.to_le_bytes());
+        common.extend(0_u16.to_le_bytes());
+        common.extend(0_u16.to_le_bytes());
+        common.extend(0x21_u16.to_le_bytes());
+        common.extend(crc.to_le_bytes());
+        common.extend((data.len() as u32).to_le_bytes());
+        common.extend((data.len() as u32).to_le_bytes());
+        common.extend((file_name.len() as u16).to_le_bytes());
+        common.extend(0_u16.to_le_bytes());
+
+        content.extend(0x04034b50_u32.to_le_bytes());
+        content.extend(&common);
+        content.extend(file_name.as_bytes());
+        content.extend(&data);
+
+        central_directory.extend(0x02014b50_u32.to_le_bytes());
+        central_directory.extend(20_u16.to_le_bytes());
+        central_directory.extend(&common);
+        // 注释长度、磁盘号、内部属性、外部属性、local file header的偏移
+        central_directory.extend(0_u16.to_le_bytes());
+        central_directory.extend(0_u16.to_le_bytes());
+        central_directory.extend(0_u16.to_le_bytes());
+        central_directory.extend(0_u32.to_le_bytes());
+        central_directory.extend((offset as u32).to_le_bytes());
+        central_directory.extend(file_name.as_bytes());
+    }
+
+    let central_directory_offset = content.len();
+
+    if central_directory_offset > u32::MAX as usize || arrays.len() > u16::MAX as usize {
+        return Err("npz larger than 4GB is not supported".into());
+    }
+
+    content.extend(&central_directory);
+
+    content.extend(0x06054b50_u32.to_le_bytes());
+    content.extend(0_u16.to_le_bytes());
+    content.extend(0_u16.to_le_bytes());
+    content.extend((arrays.len() as u16).to_le_bytes());
+    content.extend((arrays.len() as u16).to_le_bytes());
+    content.extend((central_directory.len() as u32).to_le_bytes());
+    content.extend((central_directory_offset as u32).to_le_bytes());
+    content.extend(0_u16.to_le_bytes());
+
+    std::fs::write(file_path, content)?;
+
+    Ok(())
+}
+
+// 把目录中的一个序列保存到一个.npz，目录中有多个Series Instance UID (0020,000E)时返回错误
+// 按照Instance Number (0020,0013)排序，没有时按照文件名，数组依次命名为instance_0001、instance_0002...
+// 返回写入的数组个数和被跳过的文件（无法解析、没有像素数据）
+pub fn export_series_npz<P: AsRef<Path>, Q: AsRef<Path>>(
+    directory: P,
+    file_path: Q,
+    modality: bool,
+) -> CommonResult<(usize, Vec<SkippedFile>)> {
+    let (series, skipped) = crate::volume::read_series(directory)?;
+
+    if series.len() > 1 {
+        return Err(format!(
+            "the directory contains {} series ({}), export each series separately",
+            series.len(),
+            series
+                .keys()
+                .map(|v| format!("{:?}", v))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+        .into());
+    }
+
+    let mut instances = series
+        .into_values()
+        .flatten()
+        .map(|(path, data_elements)| {
+            let instance_number = accessor::get_i64(&data_elements, "0020,0013").ok();
+
+            (instance_number, path, data_elements)
+        })
+        .collect::<Vec<_>>();
+
+    if instances.is_empty() {
+        return Err("no dicom image found in the directory".into());
+    }
+
+    instances.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+    let number_width = instances.len().to_string().len().max(4);
+
+    let arrays = instances
+        .iter()
+        .enumerate()
+        .map(|(index, (_, _, data_elements))| {
+            Ok((
+                format!("instance_{:0width$}", index + 1, width = number_width),
+                pixel_array(data_elements, modality)?,
+            ))
+        })
+        .collect::<CommonResult<Vec<(String, NpyArray)>>>()?;
+
+    write_npz(file_path, &arrays)?;
+
+    Ok((arrays.len(), skipped))
+}
+
+pub fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+// 数值属性不存在时写null
+fn json_numbers(data_elements: &[DataElement], tag: &str, array: bool) -> CommonResult<String> {
+    if !accessor::has_value(data_elements, tag) {
+        return Ok("null".to_string());
+    }
+
+    let values = accessor::get_f64s(data_elements, tag)?;
+
+    if array {
+        Ok(format!(
+            "[{}]",
+            values
+                .iter()
+                .map(|v| json_number(*v))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ))
+    } else {
+        Ok(values
+            .first()
+            .map(|v| json_number(*v))
+            .unwrap_or_else(|| "null".to_string()))
+    }
+}
+
+// 导出文件的sidecar，记录把保存的值还原成物理值以及放回空间中所需的信息
+fn sidecar_json(
+    data_elements: &[DataElement],
+    description: &PixelDescription,
+    frame_index: usize,
+    format: ExportFormat,
+    value_offset: f64,
+) -> CommonResult<String> {
+    let (format_name, dtype) = match format {
+        ExportFormat::Png16 => ("png16", "uint16"),
+        ExportFormat::Tiff16 => ("tiff16", "uint16"),
+        ExportFormat::TiffFloat => ("tiff_float", "float32"),
+        ExportFormat::Npy => ("npy", "float32"),
+    };
+
+    let (modality_transform, slope, intercept) = match crate::lut::get_modality_transform(
+        data_elements,
+        description.pixel_representation,
+    )? {
+        ModalityTransform::Rescale { slope, intercept } => ("rescale", slope, intercept),
+        // 使用Modality LUT时没有线性的slope和intercept
+        ModalityTransform::Lut(_) => ("lut", f64::NAN, f64::NAN),
+    };
+
+    let rescale_type = if accessor::has_value(data_elements, "0028,1054") {
+        json_string(&accessor::get_str(data_elements, "0028,1054")?)
+    } else {
+        "null".to_string()
+    };
+
+    let fields = [
+        ("format", json_string(format_name)),
+        ("dtype", json_string(dtype)),
+        ("frame_index", frame_index.to_string()),
+        ("rows", description.rows.to_string()),
+        ("columns", description.columns.to_string()),
+        ("modality_transform", json_string(modality_transform)),
+        ("rescale_slope", json_number(slope)),
+        ("rescale_intercept", json_number(intercept)),
+        ("rescale_type", rescale_type),
+        ("value_offset", json_number(value_offset)),
+        (
+            "pixel_spacing",
+            json_numbers(data_elements, "0028,0030", true)?,
+        ),
+        (
+            "slice_thickness",
+            json_numbers(data_elements, "0018,0050", false)?,
+        ),
+        (
+            "spacing_between_slices",
+            json_numbers(data_elements, "0018,0088", false)?,
+        ),
+    ];
+
+    Ok(format!(
+        "{{\n{}\n}}\n",
+        fields
+            .iter()
+            .map(|(key, value)| format!("  {}: {}", json_string(key), value))
+            .collect::<Vec<String>>()
+            .join(",\n")
+    ))
+}
@@ -0,0 +1,227 @@
+use crate::{
+    accessor,
+    model::{DataElement, ImageGeometry, OrientationLabels},
+    CommonResult,
+};
+
+// 图像的空间位置，参考PS3.3 C.7.6.2
+// 病人坐标系是LPS：x指向病人的左边，y指向病人的后面，z指向病人的头
+
+pub fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn normalize(value: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(value, value).sqrt();
+
+    if length < 1e-6 || !length.is_finite() {
+        return None;
+    }
+
+    Some(value.map(|v| v / length))
+}
+
+fn triplet(values: &[f64], tag: &str) -> CommonResult<[f64; 3]> {
+    match values {
+        [x, y, z] => Ok([*x, *y, *z]),
+        _ => Err(format!("{} has VM {}, expected 3", tag, values.len()).into()),
+    }
+}
+
+// 在data_elements中按顺序查找tags中第一个有值的tag
+fn first_with_value<'a>(
+    data_elements: &'a [DataElement],
+    tags: &[&str],
+) -> Option<(&'a [DataElement], String)> {
+    tags.iter()
+        .find(|tag| accessor::has_value(data_elements, tag))
+        .map(|tag| (data_elements, tag.to_string()))
+}
+
+// 增强型多帧图像把位置放在Functional Group里：
+// Per-frame Functional Groups Sequence (5200,9230)第frame_index个item优先，
+// 其次是Shared Functional Groups Sequence (5200,9229)，最后是顶层的tag
+fn functional_group_items<'a>(
+    data_elements: &'a [DataElement],
+    frame_index: usize,
+    sequence_tag: &str,
+) -> CommonResult<Vec<&'a [DataElement]>> {
+    let mut result = Vec::new();
+
+    if accessor::has_value(data_elements, "5200,9230") {
+        if let Some(frame) = accessor::get_items(data_elements, "5200,9230")?.get(frame_index) {
+            if accessor::has_value(frame, sequence_tag) {
+                result.extend(
+                    accessor::get_items(frame, sequence_tag)?
+                        .iter()
+                        .map(|v| v.as_slice()),
+                );
+            }
+        }
+    }
+
+    if accessor::has_value(data_elements, "5200,9229") {
+        for shared in accessor::get_items(data_elements, "5200,9229")? {
+            if accessor::has_value(shared, sequence_tag) {
+                result.extend(
+                    accessor::get_items(shared, sequence_tag)?
+                        .iter()
+                        .map(|v| v.as_slice()),
+                );
+            }
+        }
+    }
+
+    result.push(data_elements);
+
+    Ok(result)
+}
+
+fn find_in_groups<'a>(
+    data_elements: &'a [DataElement],
+    frame_index: usize,
+    sequence_tag: &str,
+    tags: &[&str],
+) -> CommonResult<Option<(&'a [DataElement], String)>> {
+    Ok(
+        functional_group_items(data_elements, frame_index, sequence_tag)?
+            .into_iter()
+            .find_map(|items| first_with_value(items, tags)),
+    )
+}
+
+// 单帧图像的空间位置
+pub fn get_image_geometry(data_elements: &[DataElement]) -> CommonResult<ImageGeometry> {
+    get_frame_geometry(data_elements, 0)
+}
+
+// 第frame_index帧（从0开始）的空间位置
+// Pixel Spacing没有时使用Imager Pixel Spacing (0018,1164)，投影图像的距离是在探测器平面上测量的
+pub fn get_frame_geometry(
+    data_elements: &[DataElement],
+    frame_index: usize,
+) -> CommonResult<ImageGeometry> {
+    let (items, tag) = find_in_groups(data_elements, frame_index, "0020,9113", &["0020,0032"])?
+        .ok_or("Image Position (Patient) (0020,0032) is missing")?;
+    let position = triplet(&accessor::get_f64s(items, &tag)?, &tag)?;
+
+    let (items, tag) = find_in_groups(data_elements, frame_index, "0020,9116", &["0020,0037"])?
+        .ok_or("Image Orientation (Patient) (0020,0037) is missing")?;
+    let orientation = accessor::get_f64s(items, &tag)?;
+
+    if orientation.len() != 6 {
+        return Err(format!("{} has VM {}, expected 6", tag, orientation.len()).into());
+    }
+
+    let row_direction = normalize(triplet(&orientation[0..3], &tag)?)
+        .ok_or("the row direction of Image Orientation (Patient) is a zero vector")?;
+    let column_direction = normalize(triplet(&orientation[3..6], &tag)?)
+        .ok_or("the column direction of Image Orientation (Patient) is a zero vector")?;
+
+    let (items, tag) = find_in_groups(
+        data_elements,
+        frame_index,
+        "0028,9110",
+        &["0028,0030", "0018,1164"],
+    )?
+    .ok_or("Pixel Spacing (0028,0030) is missing")?;
+    let pixel_spacing = match accessor::get_f64s(items, &tag)?[..] {
+        [row_spacing, column_spacing] => (row_spacing, column_spacing),
+        ref values => {
+            return Err(format!("{} has VM {}, expected 2", tag, values.len()).into());
+        }
+    };
+
+    if pixel_spacing.0 <= 0.0 || pixel_spacing.1 <= 0.0 {
+        return Err(format!("{} must be positive, got {:?}", tag, pixel_spacing).into());
+    }
+
+    let slice_thickness =
+        match find_in_groups(data_elements, frame_index, "0028,9110", &["0018,0050"])? {
+            Some((items, tag)) => Some(accessor::get_f64(items, &tag)?),
+            None => None,
+        };
+
+    let spacing_between_slices =
+        match find_in_groups(data_elements, frame_index, "0028,9110", &["0018,0088"])? {
+            Some((items, tag)) => Some(accessor::get_f64(items, &tag)?),
+            None => None,
+        };
+
+    Ok(ImageGeometry {
+        rows: accessor::get_u16(data_elements, "0028,0010")?,
+        columns: accessor::get_u16(data_elements, "0028,0011")?,
+        position,
+        row_direction,
+        column_direction,
+        pixel_spacing,
+        slice_thickness,
+        spacing_between_slices,
+    })
+}
+
+// 图像平面的法线方向（row_direction × column_direction）
+pub fn normal(geometry: &ImageGeometry) -> [f64; 3] {
+    cross(geometry.row_direction, geometry.column_direction)
+}
+
+// 像素(row, column)（从0开始，可以是小数）的中心在病人坐标系中的位置（mm）
+pub fn pixel_to_patient(geometry: &ImageGeometry, row: f64, column: f64) -> [f64; 3] {
+    let (row_spacing, column_spacing) = geometry.pixel_spacing;
+
+    [0, 1, 2].map(|axis| {
+        geometry.position[axis]
+            + geometry.row_direction[axis] * column * column_spacing
+            + geometry.column_direction[axis] * row * row_spacing
+    })
+}
+
+// 病人坐标系中的点投影到图像平面上，返回(row, column)
+// 第三个值是点到图像平面的有符号距离（沿着法线方向，mm）
+pub fn patient_to_pixel(geometry: &ImageGeometry, point: [f64; 3]) -> (f64, f64, f64) {
+    let (row_spacing, column_spacing) = geometry.pixel_spacing;
+    let offset = [0, 1, 2].map(|axis| point[axis] - geometry.position[axis]);
+
+    (
+        dot(offset, geometry.column_direction) / row_spacing,
+        dot(offset, geometry.row_direction) / column_spacing,
+        dot(offset, normal(geometry)),
+    )
+}
+
+// 方向向量对应的病人方向，按照分量的大小排序，忽略很小的分量
+pub fn orientation_label(direction: [f64; 3]) -> String {
+    let mut components = [
+        (direction[0], 'L', 'R'),
+        (direction[1], 'P', 'A'),
+        (direction[2], 'H', 'F'),
+    ];
+
+    components.sort_by(|a, b| b.0.abs().total_cmp(&a.0.abs()));
+
+    components
+        .iter()
+        .filter(|(value, _, _)| value.abs() > 1e-4)
+        .map(|(value, positive, negative)| if *value > 0.0 { *positive } else { *negative })
+        .collect()
+}
+
+// 图像四条边的方向，左边和上边分别是row_direction和column_direction的反方向
+pub fn orientation_labels(geometry: &ImageGeometry) -> OrientationLabels {
+    let opposite = |value: [f64; 3]| value.map(|v| -v);
+
+    OrientationLabels {
+        top: orientation_label(opposite(geometry.column_direction)),
+        bottom: orientation_label(geometry.column_direction),
+        left: orientation_label(opposite(geometry.row_direction)),
+        right: orientation_label(geometry.row_direction),
+    }
+}
@@ -0,0 +1,41 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use lazy_static::lazy_static;
+
+pub type CommonError = Box<dyn std::error::Error>;
+pub type CommonResult<T> = std::result::Result<T, CommonError>;
+
+pub mod accessor;
+pub mod codec;
+pub mod color;
+pub mod csa;
+pub mod draw;
+pub mod export;
+pub mod geometry;
+pub mod lut;
+pub mod model;
+pub mod mosaic;
+pub mod mpr;
+pub mod nifti;
+pub mod overlay;
+pub mod padding;
+pub mod pixel;
+pub mod presentation;
+pub mod render;
+pub mod service;
+pub mod shutter;
+pub mod transcode;
+pub mod util;
+pub mod volume;
+pub mod writer;
+
+lazy_static! {
+    static ref FULL_MATCH_MAPPING: HashMap<String, String> =
+        util::load_and_convert_tag_mapping().unwrap().0;
+    static ref PARTIAL_MATCH_MAPPING: HashMap<String, String> =
+        util::load_and_convert_tag_mapping().unwrap().1;
+    static ref VR_MAPPING: HashMap<String, String> = util::load_tag_vr_mapping().unwrap();
+    // 默认加载private_dictionary.txt，可以通过util::add_private_dictionary追加其他厂商的字典
+    static ref PRIVATE_DICTIONARY: RwLock<model::PrivateDictionary> =
+        RwLock::new(util::load_private_dictionary("./private_dictionary.txt").unwrap());
+}
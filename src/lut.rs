@@ -0,0 +1,297 @@
+use crate::{
+    accessor,
+    model::{
+        AutoWindowStrategy, DataElement, LookupTable, ModalityTransform, VoiLutFunction,
+        VoiTransform, Window,
+    },
+    CommonResult,
+};
+
+// 灰度图像的显示流程参考PS3.4 N.2：
+// 存储值 -> Modality LUT -> VOI LUT -> Presentation LUT（MONOCHROME1时反转）
+
+// 读取Modality LUT Sequence或VOI LUT Sequence中的一个item
+fn read_lookup_table(item: &[DataElement], pixel_representation: u16) -> CommonResult<LookupTable> {
+    let (number_of_entries, first_mapped_value, bits_per_entry) =
+        crate::color::get_lut_descriptor(item, "0028,3002", pixel_representation)?;
+
+    let mut data =
+        crate::color::read_lut_data(item, "0028,3006", number_of_entries, bits_per_entry)?;
+
+    if data.is_empty() {
+        return Err("0028,3006 is empty".into());
+    }
+
+    data.truncate(number_of_entries);
+
+    Ok(LookupTable {
+        first_mapped_value,
+        bits_per_entry,
+        data,
+    })
+}
+
+// Modality LUT Sequence和Rescale Slope/Intercept不会同时存在，存在LUT时优先使用LUT
+// MR、CR和二次采集等图像通常没有Rescale Slope/Intercept，缺少时使用默认值1和0
+pub fn get_modality_transform(
+    data_elements: &[DataElement],
+    pixel_representation: u16,
+) -> CommonResult<ModalityTransform> {
+    if accessor::has_value(data_elements, "0028,3000") {
+        let item = accessor::get_items(data_elements, "0028,3000")?
+            .first()
+            .ok_or("0028,3000 has no item")?;
+
+        return Ok(ModalityTransform::Lut(read_lookup_table(
+            item,
+            pixel_representation,
+        )?));
+    }
+
+    let slope = if accessor::has_value(data_elements, "0028,1053") {
+        accessor::get_f64(data_elements, "0028,1053")?
+    } else {
+        1.0
+    };
+
+    let intercept = if accessor::has_value(data_elements, "0028,1052") {
+        accessor::get_f64(data_elements, "0028,1052")?
+    } else {
+        0.0
+    };
+
+    Ok(ModalityTransform::Rescale { slope, intercept })
+}
+
+fn parse_voi_lut_function(value: &str) -> CommonResult<VoiLutFunction> {
+    match value {
+        "" | "LINEAR" => Ok(VoiLutFunction::Linear),
+        "LINEAR_EXACT" => Ok(VoiLutFunction::LinearExact),
+        "SIGMOID" => Ok(VoiLutFunction::Sigmoid),
+        _ => Err(format!("VOI LUT function {} is not supported", value).into()),
+    }
+}
+
+// 所有的窗宽窗位预设，Window Center和Window Width的VM必须相同
+// 只有其中一个时无法组成窗宽窗位，和两个都没有一样处理（渲染时使用自动窗宽窗位）
+pub fn get_windows(data_elements: &[DataElement]) -> CommonResult<Vec<Window>> {
+    if !accessor::has_value(data_elements, "0028,1050")
+        || !accessor::has_value(data_elements, "0028,1051")
+    {
+        return Ok(Vec::new());
+    }
+
+    let centers = accessor::get_f64s(data_elements, "0028,1050")?;
+    let widths = accessor::get_f64s(data_elements, "0028,1051")?;
+
+    if centers.len() != widths.len() {
+        return Err(format!(
+            "window center has {} values but window width has {}",
+            centers.len(),
+            widths.len()
+        )
+        .into());
+    }
+
+    let function = if accessor::has_value(data_elements, "0028,1056") {
+        parse_voi_lut_function(&accessor::get_str(data_elements, "0028,1056")?)?
+    } else {
+        VoiLutFunction::Linear
+    };
+
+    let explanations = if accessor::has_value(data_elements, "0028,1055") {
+        accessor::get_strs(data_elements, "0028,1055")?
+    } else {
+        Vec::new()
+    };
+
+    Ok(centers
+        .iter()
+        .zip(widths.iter())
+        .enumerate()
+        .map(|(index, (center, width))| Window {
+            center: *center,
+            width: *width,
+            function,
+            explanation: explanations.get(index).cloned(),
+        })
+        .collect())
+}
+
+// VOI LUT Sequence中的所有LUT
+pub fn get_voi_luts(
+    data_elements: &[DataElement],
+    pixel_representation: u16,
+) -> CommonResult<Vec<LookupTable>> {
+    if !accessor::has_value(data_elements, "0028,3010") {
+        return Ok(Vec::new());
+    }
+
+    accessor::get_items(data_elements, "0028,3010")?
+        .iter()
+        .map(|item| read_lookup_table(item, pixel_representation))
+        .collect()
+}
+
+// 文件中所有可用的VOI变换，窗宽窗位预设在前，VOI LUT在后
+pub fn get_voi_transforms(
+    data_elements: &[DataElement],
+    pixel_representation: u16,
+) -> CommonResult<Vec<VoiTransform>> {
+    let mut result = get_windows(data_elements)?
+        .into_iter()
+        .map(VoiTransform::Window)
+        .collect::<Vec<VoiTransform>>();
+
+    result.extend(
+        get_voi_luts(data_elements, pixel_representation)?
+            .into_iter()
+            .map(VoiTransform::Lut),
+    );
+
+    Ok(result)
+}
+
+// 根据Modality LUT输出值的分布计算窗宽窗位，没有有效值时返回None
+pub fn compute_auto_window(values: &[f64], strategy: AutoWindowStrategy) -> Option<Window> {
+    let mut values = values
+        .iter()
+        .copied()
+        .filter(|v| v.is_finite())
+        .collect::<Vec<f64>>();
+
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(f64::total_cmp);
+
+    let (lower, upper, explanation) = match strategy {
+        AutoWindowStrategy::MinMax => (values[0], values[values.len() - 1], "AUTO MINMAX"),
+        AutoWindowStrategy::Percentile { lower, upper } => {
+            let lower = lower.clamp(0.0, 100.0);
+            let upper = upper.clamp(lower, 100.0);
+
+            // 最近秩法取百分位
+            let percentile = |p: f64| {
+                let index = (p / 100.0 * (values.len() - 1) as f64).round() as usize;
+
+                values[index.min(values.len() - 1)]
+            };
+
+            (percentile(lower), percentile(upper), "AUTO PERCENTILE")
+        }
+    };
+
+    // 所有像素值都相同时窗宽为0，这里至少保留1，保证图像仍然可以显示
+    Some(Window {
+        center: (lower + upper) / 2.0,
+        width: (upper - lower).max(1.0),
+        function: VoiLutFunction::LinearExact,
+        explanation: Some(explanation.to_string()),
+    })
+}
+
+// 文件中的第一个VOI变换，没有时按照strategy自动计算窗宽窗位
+pub fn get_voi_transform_or_auto(
+    data_elements: &[DataElement],
+    pixel_representation: u16,
+    values: &[i64],
+    modality: &ModalityTransform,
+    strategy: AutoWindowStrategy,
+) -> CommonResult<VoiTransform> {
+    if let Some(transform) = get_voi_transforms(data_elements, pixel_representation)?
+        .into_iter()
+        .next()
+    {
+        return Ok(transform);
+    }
+
+    // 填充像素不参与计算，否则扫描野之外的值会把窗口拉得很宽
+    let padding = crate::padding::get_pixel_padding(data_elements, pixel_representation)?;
+
+    let modality_values = crate::padding::without_padding(padding.as_ref(), values)
+        .iter()
+        .map(|v| apply_modality(*v, modality))
+        .collect::<Vec<f64>>();
+
+    compute_auto_window(&modality_values, strategy)
+        .map(VoiTransform::Window)
+        .ok_or_else(|| "no pixel values to compute a window from".into())
+}
+
+pub fn apply_modality(value: i64, transform: &ModalityTransform) -> f64 {
+    match transform {
+        ModalityTransform::Rescale { slope, intercept } => value as f64 * slope + intercept,
+        ModalityTransform::Lut(lut) => lut.lookup(value) as f64,
+    }
+}
+
+// VOI变换，输出范围是0.0~1.0
+// 公式参考PS3.3 C.11.2.1.2
+pub fn apply_voi(value: f64, transform: &VoiTransform) -> f64 {
+    match transform {
+        VoiTransform::Window(window) => apply_window(value, window),
+        VoiTransform::Lut(lut) => {
+            let max_entry = ((1_u32 << lut.bits_per_entry.min(16)) - 1) as f64;
+
+            (lut.lookup(value.round() as i64) as f64 / max_entry).clamp(0.0, 1.0)
+        }
+    }
+}
+
+fn apply_window(value: f64, window: &Window) -> f64 {
+    let center = window.center;
+
+    match window.function {
+        VoiLutFunction::Linear => {
+            // LINEAR要求窗宽不小于1
+            let width = window.width.max(1.0);
+
+            if value <= center - 0.5 - (width - 1.0) / 2.0 {
+                0.0
+            } else if value > center - 0.5 + (width - 1.0) / 2.0 {
+                1.0
+            } else {
+                (value - (center - 0.5)) / (width - 1.0) + 0.5
+            }
+        }
+        VoiLutFunction::LinearExact => {
+            let width = window.width.max(f64::MIN_POSITIVE);
+
+            if value <= center - width / 2.0 {
+                0.0
+            } else if value > center + width / 2.0 {
+                1.0
+            } else {
+                (value - center) / width + 0.5
+            }
+        }
+        VoiLutFunction::Sigmoid => {
+            let width = window.width.max(f64::MIN_POSITIVE);
+
+            1.0 / (1.0 + (-4.0 * (value - center) / width).exp())
+        }
+    }
+}
+
+// 完整的灰度显示流程，输出范围是0.0~1.0，invert为true时（MONOCHROME1）最小值显示为白色
+pub fn apply_grayscale_pipeline(
+    values: &[i64],
+    modality: &ModalityTransform,
+    voi: &VoiTransform,
+    invert: bool,
+) -> Vec<f64> {
+    values
+        .iter()
+        .map(|value| {
+            let output = apply_voi(apply_modality(*value, modality), voi);
+
+            if invert {
+                1.0 - output
+            } else {
+                output
+            }
+        })
+        .collect()
+}
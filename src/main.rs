@@ -1,87 +1,249 @@
-use std::{collections::HashMap, io::Read};
+use std::io::Read;
+
+use dicom_parser::{
+    accessor, export, model, mpr, nifti, pixel, service, util, volume, CommonResult,
+};
+
+const EXPORT_NPY_USAGE: &str = "usage:
+    cargo run -- export-npy [--modality] <input.dcm> <output.npy>
+    cargo run -- export-npy [--modality] --npz <series_directory> <output.npz>";
+
+// cargo run -- export-npy ...
+// 默认保存存储值，--modality保存Modality LUT的输出值（float32），--npz把目录中的一个序列保存到一个.npz
+fn export_npy(args: &[String]) -> CommonResult<()> {
+    let modality = args.iter().any(|v| v == "--modality");
+    let npz = args.iter().any(|v| v == "--npz");
+    let paths = args
+        .iter()
+        .filter(|v| !v.starts_with("--"))
+        .collect::<Vec<&String>>();
+
+    if paths.len() != 2
+        || args
+            .iter()
+            .any(|v| v.starts_with("--") && v != "--modality" && v != "--npz")
+    {
+        return Err(EXPORT_NPY_USAGE.into());
+    }
+
+    if npz {
+        let (count, skipped) = export::export_series_npz(paths[0], paths[1], modality)?;
+
+        for file in &skipped {
+            eprintln!("skipped {}: {}", file.file_path.display(), file.reason);
+        }
+
+        println!("{} instances written to {}", count, paths[1]);
+    } else {
+        let data_elements = service::read_file(paths[0])?;
+        let array = export::pixel_array(&data_elements, modality)?;
 
-pub type CommonError = Box<dyn std::error::Error>;
-pub type CommonResult<T> = std::result::Result<T, CommonError>;
-use lazy_static::lazy_static;
+        export::write_npy(paths[1], &array)?;
 
-mod model;
-mod service;
-mod util;
+        println!("{:?} written to {}", array.shape, paths[1]);
+    }
 
-lazy_static! {
-    static ref FULL_MATCH_MAPPING: HashMap<String, String> =
-        util::load_and_convert_tag_mapping().unwrap().0;
-    static ref PARTIAL_MATCH_MAPPING: HashMap<String, String> =
-        util::load_and_convert_tag_mapping().unwrap().1;
+    Ok(())
 }
 
-fn main() -> CommonResult<()> {
-    let file_path = "./datas/1-003.dcm";
-    // let file_path = "./datas/93117444";
+const EXPORT_NIFTI_USAGE: &str = "usage:
+    cargo run -- export-nifti [--nifti2] <series_directory> <output_directory>";
+
+// cargo run -- export-nifti ...
+// 目录中的每个序列保存为一个series_<Series Number>.nii（没有Series Number时使用序号）和同名的.json
+fn export_nifti(args: &[String]) -> CommonResult<()> {
+    let version = if args.iter().any(|v| v == "--nifti2") {
+        model::NiftiVersion::Nifti2
+    } else {
+        model::NiftiVersion::Nifti1
+    };
+    let paths = args
+        .iter()
+        .filter(|v| !v.starts_with("--"))
+        .collect::<Vec<&String>>();
+
+    if paths.len() != 2 || args.iter().any(|v| v.starts_with("--") && v != "--nifti2") {
+        return Err(EXPORT_NIFTI_USAGE.into());
+    }
 
-    let mut offset = 0;
+    std::fs::create_dir_all(paths[1])?;
 
-    // 获取文件句柄
-    let mut file = crate::util::get_file(&file_path)?;
+    let (volumes, failures) = volume::read_volumes(paths[0])?;
 
-    let file_length = (&file).metadata()?.len();
+    for (series_instance_uid, reason) in &failures {
+        eprintln!(
+            "series {:?} can not be assembled: {}",
+            series_instance_uid, reason
+        );
+    }
 
-    let mut file_content = Vec::new();
-    file.read_to_end(&mut file_content)?;
+    for (index, volume) in volumes.iter().enumerate() {
+        let series_number = accessor::get_i64(&volume.attributes, "0020,0011")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|_| (index + 1).to_string());
+        let file_path =
+            std::path::Path::new(paths[1]).join(format!("series_{}.nii", series_number));
 
-    // 读取导言
-    let result = crate::service::get_preamble(&file_content[offset..].to_vec())?;
+        for issue in &volume.issues {
+            eprintln!("series {}: {:?}", series_number, issue);
+        }
 
-    let preamble = result.0;
-    let consumed_bytes = result.1;
-    offset += consumed_bytes;
+        nifti::write_nifti(volume, &file_path, version)?;
 
-    println!("preamble: {}", preamble);
+        println!("{:?} written to {}", volume.shape, file_path.display());
+    }
 
-    // 读取前缀
-    let result = crate::service::get_prefix(&file_content[offset..].to_vec())?;
+    Ok(())
+}
 
-    let prefix = result.0;
-    let consumed_bytes = result.1;
-    offset += consumed_bytes;
+const EXPORT_MPR_USAGE: &str = "usage:
+    cargo run -- export-mpr [--mip|--minip|--avgip] [--slab=<mm>] <series_directory> <output_directory>";
+
+// cargo run -- export-mpr ...
+// 目录中的每个序列重建出经过中心的横断面、冠状面和矢状面，保存为series_<Series Number>_axial.png等
+// --mip/--minip/--avgip在厚度为--slab（默认10mm）的层块内投影
+fn export_mpr(args: &[String]) -> CommonResult<()> {
+    let mut reslice_options = model::ResliceOptions::default();
+    let mut projection = false;
+    let mut slab_thickness = 10.0;
+    let mut paths = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--mip" | "--minip" | "--avgip" => {
+                projection = true;
+                reslice_options.projection = match arg.as_str() {
+                    "--mip" => model::Projection::Maximum,
+                    "--minip" => model::Projection::Minimum,
+                    _ => model::Projection::Average,
+                };
+            }
+            _ if arg.starts_with("--slab=") => {
+                slab_thickness = arg["--slab=".len()..]
+                    .parse::<f64>()
+                    .map_err(|_| EXPORT_MPR_USAGE)?;
+            }
+            _ if arg.starts_with("--") => return Err(EXPORT_MPR_USAGE.into()),
+            _ => paths.push(arg),
+        }
+    }
 
-    println!("prefix: {}", prefix);
+    if paths.len() != 2 {
+        return Err(EXPORT_MPR_USAGE.into());
+    }
 
-    // 将所有的数据按照element全部都分割好了
-    let mut data_elements = Vec::new();
+    if projection {
+        reslice_options.slab_thickness = slab_thickness;
+    }
 
-    loop {
-        let result = crate::service::get_data_element(&file_content[offset..].to_vec())?;
-        let consumed_bytes = result.1;
-        offset += consumed_bytes;
+    std::fs::create_dir_all(paths[1])?;
 
-        data_elements.push(result.0);
+    let planes = [
+        ("axial", model::ReslicePlane::Axial),
+        ("coronal", model::ReslicePlane::Coronal),
+        ("sagittal", model::ReslicePlane::Sagittal),
+    ];
 
-        // println!("offset: {}", offset);
+    let (volumes, failures) = volume::read_volumes(paths[0])?;
 
-        if offset as u64 >= file_length - 1 {
-            break;
+    for (series_instance_uid, reason) in &failures {
+        eprintln!(
+            "series {:?} can not be assembled: {}",
+            series_instance_uid, reason
+        );
+    }
+
+    for (index, volume) in volumes.iter().enumerate() {
+        let series_number = accessor::get_i64(&volume.attributes, "0020,0011")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|_| (index + 1).to_string());
+
+        for (name, plane) in planes {
+            let file_path = std::path::Path::new(paths[1])
+                .join(format!("series_{}_{}.png", series_number, name));
+            let options = model::ResliceOptions {
+                plane,
+                ..reslice_options.clone()
+            };
+
+            mpr::reslice_to_file(
+                volume,
+                &file_path,
+                &options,
+                &model::RenderOptions::default(),
+            )?;
+
+            println!("{} written to {}", name, file_path.display());
         }
     }
 
-    // 检查是否为小端方式进行存储的
-    let transfer_syntax_result = util::get_data_element_via_tag(&data_elements, "0002,0010".to_string()).unwrap().data;
+    Ok(())
+}
+
+fn main() -> CommonResult<()> {
+    let args = std::env::args().collect::<Vec<String>>();
+
+    if args.get(1).map(String::as_str) == Some("export-npy") {
+        return export_npy(&args[2..]);
+    }
+
+    if args.get(1).map(String::as_str) == Some("export-nifti") {
+        return export_nifti(&args[2..]);
+    }
+
+    if args.get(1).map(String::as_str) == Some("export-mpr") {
+        return export_mpr(&args[2..]);
+    }
+
+    let file_path = "./datas/1-003.dcm";
+    // let file_path = "./datas/93117444";
+
+    // 获取文件句柄
+    let mut file = util::get_file(file_path)?;
+
+    let mut file_content = Vec::new();
+    file.read_to_end(&mut file_content)?;
+
+    // 读取前缀，导言的内容是任意的，不做解析
+    let result = service::get_prefix(&file_content[128..])?;
 
-    if let Some(model::DicomValue::String(v)) = Some(transfer_syntax_result) {
-        if &v != "1.2.840.10008.1.2.1" {
-            panic!("目前只支持显式小端的格式");
+    println!("prefix: {}", result.0);
+
+    // 将所有的数据按照element全部都分割好了
+    // 数据集会按照0002,0010中的传输语法（隐式小端、显式小端、显式大端）进行解析
+    let data_elements =
+        service::parse_file_content(&file_content, &model::ParseOptions::default())?;
+
+    let transfer_syntax = accessor::get_str(&data_elements, "0002,0010")?;
+
+    println!("transfer syntax: {}", transfer_syntax);
+
+    // 宽松模式下解析失败的数值不会中断解析，这里把记录下来的错误打印出来
+    for data_element in &data_elements {
+        for value_error in &data_element.value_errors {
+            eprintln!(
+                "{} {} value #{} {:?}: {}",
+                data_element.tag,
+                data_element.tag_for_human,
+                value_error.index,
+                value_error.raw,
+                value_error.message
+            );
         }
     }
 
     println!("{:#?}", data_elements[..4].to_vec());
 
-    // 生成图像数据
-    let _ = service::generate_image(&data_elements);
+    // 生成图像数据，多帧的图像把每一帧都输出到./images/frames下
+    let number_of_frames = pixel::get_pixel_description(&data_elements)
+        .map(|v| v.number_of_frames)
+        .unwrap_or(1);
 
-    // 尝试读取一个数据元素
-    // let result = crate::service::get_data_element(&file_content[offset..].to_vec())?;
-    // let consumed_bytes = result.1;
-    // offset += consumed_bytes;
+    if number_of_frames > 1 {
+        let _ = service::generate_frame_images(&data_elements, "./images/frames");
+    } else {
+        let _ = service::generate_image(&data_elements);
+    }
 
     Ok(())
 }
@@ -1,4 +1,6 @@
-#[derive(Debug, Clone)]
+use std::{collections::BTreeMap, path::PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum DicomValue {
     String(String),
     U16Pair((String, String)),
@@ -8,12 +10,16 @@ pub enum DicomValue {
     I64(Vec<i64>),
     I16(Vec<i16>),
     U32(Vec<u32>),
+    U64(Vec<u64>),
     U16(Vec<u16>),
     Bytes(Vec<u8>),
-    Sequence(Vec<DataElement>),
+    // 每个item是一组DataElement
+    Sequence(Vec<Vec<DataElement>>),
+    // 封装格式（压缩）的像素数据，第一个item是Basic Offset Table，其余的是各个fragment
+    Fragments(Vec<Vec<u8>>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DataElement {
     pub tag_group: String,
     pub tag_element: String,
@@ -21,4 +27,638 @@ pub struct DataElement {
     pub tag_for_human: String,
     pub vr: String,
     pub data: DicomValue,
+    // 宽松模式下无法解析的值不会出现在data中，而是记录在这里
+    pub value_errors: Vec<ValueError>,
+    // 从文件中读取时记录的原始编码信息，新建的元素为None
+    pub original_encoding: Option<OriginalEncoding>,
+}
+
+// 读取时记录的原始编码信息，写回时用于保证未修改的元素能够按字节还原
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OriginalEncoding {
+    // 原始的值字节，只有当按照解析出的值重新编码得到的字节与原始字节不同时才会保存
+    // 比如奇数长度、非标准的填充、字符串首尾的空格、DS的书写格式等
+    pub raw: Option<Vec<u8>>,
+    // raw所使用的字节序
+    pub little_endian: bool,
+    // SQ是否使用未定义长度
+    pub undefined_length: bool,
+    // SQ中每个item是否使用未定义长度
+    pub item_undefined_lengths: Vec<bool>,
+}
+
+// 多值（以\分隔）中某一个值的解析错误
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueError {
+    // 该值在原始多值字符串中的位置（从0开始）
+    pub index: usize,
+    pub raw: String,
+    pub message: String,
+}
+
+// DS/IS等数值字符串的解析模式
+// Lenient: 容忍空值、多余的空白和NUL填充，无法解析的值记录为ValueError后跳过
+// Strict: 严格按照PS3.5 6.2的字符集和长度限制，任何一个值不合法都会返回错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumericParseMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    pub numeric_mode: NumericParseMode,
+    // 解析数据集时使用的传输语法，读取整个文件时会根据0002,0010自动设置
+    pub transfer_syntax: TransferSyntax,
+}
+
+// DA(Date)类型的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DicomDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+// Image Pixel模块中描述像素数据组织方式的属性
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelDescription {
+    pub rows: u16,
+    pub columns: u16,
+    pub samples_per_pixel: u16,
+    pub bits_allocated: u16,
+    pub bits_stored: u16,
+    pub high_bit: u16,
+    // 0为无符号，1为有符号（补码）
+    pub pixel_representation: u16,
+    // 0为RGBRGB...交错存储，1为RRR...GGG...BBB...按平面存储
+    pub planar_configuration: u16,
+    pub number_of_frames: u32,
+    pub photometric_interpretation: String,
+}
+
+// Palette Color/Modality/VOI LUT共用的查找表
+// 描述符的3个值分别是表项个数（0表示65536）、第一个被映射的像素值、每个表项的位数
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupTable {
+    pub first_mapped_value: i64,
+    pub bits_per_entry: u16,
+    pub data: Vec<u16>,
+}
+
+impl LookupTable {
+    // 小于第一个被映射值的像素使用第一个表项，超出表长度的使用最后一个表项
+    pub fn lookup(&self, value: i64) -> u16 {
+        let index = (value - self.first_mapped_value).clamp(0, self.data.len() as i64 - 1);
+
+        self.data[index as usize]
+    }
+}
+
+// Modality LUT模块：把存储值转换成与设备无关的值（比如CT值）
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModalityTransform {
+    // 线性变换 output = value * slope + intercept
+    Rescale { slope: f64, intercept: f64 },
+    // Modality LUT Sequence (0028,3000)
+    Lut(LookupTable),
+}
+
+// VOI LUT Function (0028,1056)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoiLutFunction {
+    #[default]
+    Linear,
+    LinearExact,
+    Sigmoid,
+}
+
+// 一组窗宽窗位预设，Window Center/Width的VM大于1时每一组是一个预设
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub center: f64,
+    pub width: f64,
+    pub function: VoiLutFunction,
+    // Window Center & Width Explanation (0028,1055)
+    pub explanation: Option<String>,
+}
+
+// VOI LUT模块：把Modality LUT的输出转换成用于显示的值
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoiTransform {
+    Window(Window),
+    // VOI LUT Sequence (0028,3010)中的一个item
+    Lut(LookupTable),
+}
+
+// 文件中没有窗宽窗位和VOI LUT时，根据Modality LUT输出值的分布自动计算窗宽窗位
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AutoWindowStrategy {
+    // 使用最小值和最大值
+    #[default]
+    MinMax,
+    // 使用直方图中lower和upper两个百分位（0~100）之间的范围，可以去掉少量极端值的影响
+    Percentile {
+        lower: f64,
+        upper: f64,
+    },
+}
+
+// Overlay Plane模块（60xx组），参考PS3.3 C.9.2
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlay {
+    // 所在的组号，0x6000~0x601E中的偶数
+    pub group: u16,
+    pub rows: u16,
+    pub columns: u16,
+    // G为图形，R为感兴趣区域
+    pub overlay_type: String,
+    // 第一个点在图像中的位置（从1开始），顺序为行、列，可以是负数
+    pub origin: (i32, i32),
+    pub number_of_frames: u32,
+    // 第一帧overlay对应的图像帧号（从1开始）
+    pub image_frame_origin: u32,
+    pub description: Option<String>,
+    pub label: Option<String>,
+    // 每个点是否显示，所有帧按顺序存储
+    pub data: Vec<bool>,
+}
+
+// Pixel Padding Value (0028,0120)和Pixel Padding Range Limit (0028,0121)
+// 存储值在[lower, upper]之内的像素是填充像素（比如CT扫描野之外的区域），不属于图像内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelPadding {
+    pub lower: i64,
+    pub upper: i64,
+}
+
+// 渲染时填充像素的显示方式，Unchanged表示和其他像素一样经过显示流程
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PaddingDisplay {
+    #[default]
+    Unchanged,
+    Black,
+    Transparent,
+}
+
+// Display Shutter模块，参考PS3.3 C.7.6.11，坐标都是从1开始的（行, 列）
+// 多个shutter同时存在时，只有位于所有shutter开口之内的像素才显示
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shutter {
+    Rectangular {
+        left: i64,
+        right: i64,
+        upper: i64,
+        lower: i64,
+    },
+    Circular {
+        center: (i64, i64),
+        radius: i64,
+    },
+    Polygonal(Vec<(i64, i64)>),
+    // Shutter Overlay Group (0018,1623)指向的overlay，值为1的点被遮住
+    Bitmap(Overlay),
+}
+
+// 所有的shutter以及被遮住区域的显示方式
+// presentation_value是Shutter Presentation Value (0018,1622)，0~0xFFFF，默认为0（黑色）
+// 有Shutter Presentation Color CIELab Value (0018,1624)时按照彩色显示
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayShutter {
+    pub shutters: Vec<Shutter>,
+    pub presentation_value: u16,
+    pub presentation_color: Option<[u8; 3]>,
+}
+
+// Graphic Annotation Units，PIXEL是相对于图像的坐标，DISPLAY是相对于显示区域的0.0~1.0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationUnits {
+    Pixel,
+    Display,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicType {
+    Point,
+    Polyline,
+    Interpolated,
+    // 第一个点是圆心，第二个点在圆上
+    Circle,
+    // 前两个点是长轴的端点，后两个点是短轴的端点
+    Ellipse,
+}
+
+// Graphic Object Sequence (0070,0009)中的一个item，点的坐标是（列, 行）
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphicObject {
+    pub units: AnnotationUnits,
+    pub graphic_type: GraphicType,
+    pub points: Vec<(f64, f64)>,
+    pub filled: bool,
+}
+
+// Text Object Sequence (0070,0008)中的一个item，bounding box和anchor point至少有一个
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextObject {
+    pub text: String,
+    // 左上角和右下角，单位是bounding_box_units
+    pub bounding_box: Option<((f64, f64), (f64, f64))>,
+    pub bounding_box_units: AnnotationUnits,
+    pub anchor_point: Option<(f64, f64)>,
+    pub anchor_point_units: AnnotationUnits,
+    pub anchor_point_visible: bool,
+}
+
+// Graphic Annotation Sequence (0070,0001)中的一个item
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphicAnnotation {
+    pub layer: String,
+    pub graphics: Vec<GraphicObject>,
+    pub texts: Vec<TextObject>,
+}
+
+// Displayed Area Selection Sequence (0070,005A)中适用于当前图像的item
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayedArea {
+    // 从1开始的（列, 行），可以超出图像的范围
+    pub top_left: (i64, i64),
+    pub bottom_right: (i64, i64),
+    // SCALE TO FIT、TRUE SIZE或MAGNIFY
+    pub size_mode: String,
+    pub magnification: Option<f64>,
+}
+
+// Grayscale Softcopy Presentation State中适用于一帧图像的显示参数
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresentationState {
+    // 为None时使用图像本身的Modality LUT
+    pub modality: Option<ModalityTransform>,
+    // 为None时使用图像本身的VOI LUT
+    pub voi: Option<VoiTransform>,
+    // Presentation LUT Shape (2050,0020)为INVERSE时反转
+    pub invert: Option<bool>,
+    pub display_shutter: DisplayShutter,
+    pub displayed_area: Option<DisplayedArea>,
+    // 顺时针旋转的角度，0、90、180或270
+    pub rotation: u16,
+    // 先水平翻转再旋转
+    pub horizontal_flip: bool,
+    pub annotations: Vec<GraphicAnnotation>,
+    // 每个graphic layer推荐的显示颜色
+    pub layer_colors: Vec<(String, [u8; 3])>,
+}
+
+// 输出图像每个通道的位数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputBitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+// 输出图像的尺寸
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RenderSize {
+    // 与Rows/Columns相同
+    #[default]
+    Original,
+    // 按比例缩放
+    Scale(f64),
+    // 保持宽高比缩放到不超过width x height（生成缩略图）
+    Fit {
+        width: u32,
+        height: u32,
+    },
+}
+
+// 渲染一帧图像时的选项
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    // 帧号，从0开始
+    pub frame_index: usize,
+    // 指定窗宽窗位，优先于文件中的预设
+    pub window: Option<Window>,
+    // 使用文件中的第几个VOI预设（窗宽窗位在前，VOI LUT在后）
+    pub voi_index: usize,
+    // 文件中没有任何VOI预设时自动计算窗宽窗位的方式
+    pub auto_window: AutoWindowStrategy,
+    pub bit_depth: OutputBitDepth,
+    // 在Photometric Interpretation的基础上再反转一次
+    pub invert: bool,
+    pub size: RenderSize,
+    // 不为None时把所有的overlay按照这个颜色（RGB）画到图像上
+    pub overlay_color: Option<[u8; 3]>,
+    // 填充像素（Pixel Padding Value）的显示方式
+    pub padding: PaddingDisplay,
+    // 写文件或者写到writer时使用的格式
+    pub format: image::ImageFormat,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            frame_index: 0,
+            window: None,
+            voi_index: 0,
+            auto_window: AutoWindowStrategy::default(),
+            bit_depth: OutputBitDepth::default(),
+            invert: false,
+            size: RenderSize::default(),
+            overlay_color: None,
+            padding: PaddingDisplay::default(),
+            format: image::ImageFormat::Png,
+        }
+    }
+}
+
+// 导出Modality LUT输出值（比如CT值）时使用的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    // 16位无符号整数，负值按照sidecar中的value_offset平移
+    Png16,
+    Tiff16,
+    // 32位浮点数，保留原始的值
+    TiffFloat,
+    // NumPy的.npy文件，dtype为float32
+    Npy,
+}
+
+// 导出到.npy时的数组数据，对应NumPy的uint8/uint16/int16/float32
+#[derive(Debug, Clone, PartialEq)]
+pub enum NpyData {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    I16(Vec<i16>),
+    F32(Vec<f32>),
+}
+
+// 按照C order（行优先）存储的多维数组
+#[derive(Debug, Clone, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: NpyData,
+}
+
+// 一幅图像（或者多帧图像中的一帧）在病人坐标系（LPS，单位mm）中的位置
+// row_direction是列号增加的方向（沿着一行），column_direction是行号增加的方向（沿着一列）
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageGeometry {
+    pub rows: u16,
+    pub columns: u16,
+    // Image Position (Patient)，第一个像素中心的坐标
+    pub position: [f64; 3],
+    // Image Orientation (Patient)的前三个值和后三个值
+    pub row_direction: [f64; 3],
+    pub column_direction: [f64; 3],
+    // Pixel Spacing，(相邻两行的距离, 相邻两列的距离)
+    pub pixel_spacing: (f64, f64),
+    pub slice_thickness: Option<f64>,
+    pub spacing_between_slices: Option<f64>,
+}
+
+// 图像四条边所朝的病人方向，比如左边是"R"、上边是"A"，斜的方向由多个字母组成（比如"LP"）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrientationLabels {
+    pub top: String,
+    pub bottom: String,
+    pub left: String,
+    pub right: String,
+}
+
+// 读取目录时跳过的文件（无法解析、没有像素数据等）和原因
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedFile {
+    pub file_path: PathBuf,
+    pub reason: String,
+}
+
+// 组成体数据时发现的问题，层的序号是排序之后的序号（从0开始）
+#[derive(Debug, Clone, PartialEq)]
+pub enum VolumeIssue {
+    // 方向、大小和其他层不同，或者不是单帧灰度图像，没有放进体数据
+    Excluded {
+        file_path: PathBuf,
+        reason: String,
+    },
+    // 和kept位置相同，没有放进体数据
+    Duplicate {
+        file_path: PathBuf,
+        kept: PathBuf,
+    },
+    // 两层之间的距离明显大于层间距（中间缺了层）
+    Gap {
+        between: (usize, usize),
+        distance: f64,
+        expected: f64,
+    },
+    // 两层之间的距离和层间距不一致
+    InconsistentSpacing {
+        between: (usize, usize),
+        distance: f64,
+        expected: f64,
+    },
+    // 层的排列方向和图像平面的法线不平行，angle为两者的夹角（度）
+    GantryTilt {
+        angle: f64,
+    },
+}
+
+// 同一个序列的单帧图像按照空间位置排列成的体数据
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume {
+    pub series_instance_uid: String,
+    // (slices, rows, columns)，data按照time、slice、row、column的顺序存储
+    pub shape: [usize; 3],
+    // 时间序列（比如fMRI）中的时间点个数，不是时间序列时为1
+    pub time_points: usize,
+    // 所有层的Rescale都相同时保存存储值（数据类型和export::pixel_array相同），
+    // 否则保存Modality LUT的输出值（float32），这时slope为1、intercept为0
+    pub data: NpyData,
+    pub rescale_slope: f64,
+    pub rescale_intercept: f64,
+    // (column, row, slice)方向的体素大小（mm）
+    pub spacing: [f64; 3],
+    // 把体素坐标(column, row, slice, 1)映射到病人坐标系（LPS，mm），有机架倾斜时slice方向不垂直于图像平面
+    pub affine: [[f64; 4]; 4],
+    // 排序之后每一层对应的文件，顺序和data相同
+    pub file_paths: Vec<PathBuf>,
+    // 第一个时间点中每一层的Acquisition Time (0008,0032)，从0点开始的秒数
+    pub acquisition_times: Vec<Option<f64>>,
+    // 第一层除了像素数据之外的属性
+    pub attributes: Vec<DataElement>,
+    pub issues: Vec<VolumeIssue>,
+}
+
+// Siemens CSA header的格式，SV10以"SV10"开头，旧格式（CSA1）没有这个标记
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsaFormat {
+    Sv10,
+    Legacy,
+}
+
+// CSA header中的一个元素，所有的值都以字符串保存，数值按照vr解析
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsaElement {
+    pub vr: String,
+    pub vm: i32,
+    pub syngo_dt: i32,
+    pub values: Vec<String>,
+}
+
+// (0029,xx10) CSA Image Header Info或者(0029,xx20) CSA Series Header Info，按照元素的名字索引
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsaHeader {
+    pub format: CsaFormat,
+    pub elements: BTreeMap<String, CsaElement>,
+}
+
+// 私有字典中的一项，私有tag由(group, Private Creator, element的低8位)确定，参考PS3.5 7.8.1
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivateTag {
+    pub group: u16,
+    pub creator: String,
+    pub element_offset: u8,
+    pub vr: String,
+    pub name: String,
+}
+
+pub type PrivateDictionary = BTreeMap<(u16, String, u8), PrivateTag>;
+
+// 多平面重建（MPR）的切面方向，横断面、冠状面、矢状面按照放射科的习惯显示
+// 横断面和冠状面的左边是病人的右边，矢状面的左边是病人的前面
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReslicePlane {
+    #[default]
+    Axial,
+    Coronal,
+    Sagittal,
+    // 斜切面：图像中沿着一行和沿着一列的方向（病人坐标系），两个方向需要互相垂直
+    Oblique {
+        row_direction: [f64; 3],
+        column_direction: [f64; 3],
+    },
+}
+
+// 层块内的投影方式：最大密度投影（MIP）、最小密度投影（MinIP）、平均密度投影（AvgIP）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Projection {
+    #[default]
+    Maximum,
+    Minimum,
+    Average,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ResliceOptions {
+    pub plane: ReslicePlane,
+    // 切面经过的点（病人坐标系），None时使用体数据的中心
+    pub center: Option<[f64; 3]>,
+    // 输出图像的像素间距（mm），None时使用体素大小中最小的一个
+    pub pixel_spacing: Option<f64>,
+    // 层块的厚度（mm），为0时只重建一层，否则按照projection把层块内的所有层投影成一幅图像
+    pub slab_thickness: f64,
+    pub projection: Projection,
+    // 4维体数据中的时间点，从0开始
+    pub time_point: usize,
+}
+
+// 重建出的图像，values是Modality LUT的输出值（比如CT值），体数据范围之外的像素为NaN
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reslice {
+    pub geometry: ImageGeometry,
+    pub values: Vec<f64>,
+}
+
+// NIfTI文件头的版本，NIfTI-2使用64位的维度和双精度浮点数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NiftiVersion {
+    #[default]
+    Nifti1,
+    Nifti2,
+}
+
+// 目前支持读写的传输语法
+// 封装格式的传输语法都是显式小端，像素数据按照fragment保存，能否解码取决于codec中是否有对应的解码器
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransferSyntax {
+    ImplicitVrLittleEndian,
+    #[default]
+    ExplicitVrLittleEndian,
+    ExplicitVrBigEndian,
+    RleLossless,
+    JpegBaseline,
+    JpegExtended,
+    JpegLossless,
+    JpegLosslessSv1,
+    JpegLsLossless,
+    JpegLsNearLossless,
+    Jpeg2000Lossless,
+    Jpeg2000,
+}
+
+impl TransferSyntax {
+    pub fn uid(&self) -> &'static str {
+        match self {
+            TransferSyntax::ImplicitVrLittleEndian => "1.2.840.10008.1.2",
+            TransferSyntax::ExplicitVrLittleEndian => "1.2.840.10008.1.2.1",
+            TransferSyntax::ExplicitVrBigEndian => "1.2.840.10008.1.2.2",
+            TransferSyntax::RleLossless => "1.2.840.10008.1.2.5",
+            TransferSyntax::JpegBaseline => "1.2.840.10008.1.2.4.50",
+            TransferSyntax::JpegExtended => "1.2.840.10008.1.2.4.51",
+            TransferSyntax::JpegLossless => "1.2.840.10008.1.2.4.57",
+            TransferSyntax::JpegLosslessSv1 => "1.2.840.10008.1.2.4.70",
+            TransferSyntax::JpegLsLossless => "1.2.840.10008.1.2.4.80",
+            TransferSyntax::JpegLsNearLossless => "1.2.840.10008.1.2.4.81",
+            TransferSyntax::Jpeg2000Lossless => "1.2.840.10008.1.2.4.90",
+            TransferSyntax::Jpeg2000 => "1.2.840.10008.1.2.4.91",
+        }
+    }
+
+    pub fn from_uid(uid: &str) -> Option<TransferSyntax> {
+        match uid.trim_end_matches(['\0', ' ']) {
+            "1.2.840.10008.1.2" => Some(TransferSyntax::ImplicitVrLittleEndian),
+            "1.2.840.10008.1.2.1" => Some(TransferSyntax::ExplicitVrLittleEndian),
+            "1.2.840.10008.1.2.2" => Some(TransferSyntax::ExplicitVrBigEndian),
+            "1.2.840.10008.1.2.5" => Some(TransferSyntax::RleLossless),
+            "1.2.840.10008.1.2.4.50" => Some(TransferSyntax::JpegBaseline),
+            "1.2.840.10008.1.2.4.51" => Some(TransferSyntax::JpegExtended),
+            "1.2.840.10008.1.2.4.57" => Some(TransferSyntax::JpegLossless),
+            "1.2.840.10008.1.2.4.70" => Some(TransferSyntax::JpegLosslessSv1),
+            "1.2.840.10008.1.2.4.80" => Some(TransferSyntax::JpegLsLossless),
+            "1.2.840.10008.1.2.4.81" => Some(TransferSyntax::JpegLsNearLossless),
+            "1.2.840.10008.1.2.4.90" => Some(TransferSyntax::Jpeg2000Lossless),
+            "1.2.840.10008.1.2.4.91" => Some(TransferSyntax::Jpeg2000),
+            _ => None,
+        }
+    }
+
+    pub fn is_explicit_vr(&self) -> bool {
+        *self != TransferSyntax::ImplicitVrLittleEndian
+    }
+
+    pub fn is_little_endian(&self) -> bool {
+        *self != TransferSyntax::ExplicitVrBigEndian
+    }
+
+    // 像素数据是否以fragment的形式封装
+    pub fn is_encapsulated(&self) -> bool {
+        !matches!(
+            self,
+            TransferSyntax::ImplicitVrLittleEndian
+                | TransferSyntax::ExplicitVrLittleEndian
+                | TransferSyntax::ExplicitVrBigEndian
+        )
+    }
+
+    // 是否是有损压缩，转换成其他传输语法后需要设置Lossy Image Compression
+    pub fn is_lossy(&self) -> bool {
+        matches!(
+            self,
+            TransferSyntax::JpegBaseline
+                | TransferSyntax::JpegExtended
+                | TransferSyntax::JpegLsNearLossless
+                | TransferSyntax::Jpeg2000
+        )
+    }
 }
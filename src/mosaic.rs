@@ -0,0 +1,253 @@
+use crate::{
+    accessor, geometry,
+    model::{DataElement, DicomValue, TransferSyntax},
+    CommonResult,
+};
+
+// Siemens的mosaic图像把一次采集的所有层按照网格排列在同一帧中（fMRI、DTI的EPI序列）
+// 层数在Number of Images in Mosaic (0019,xx0A)中，网格的边长是ceil(sqrt(层数))，各层按照行优先的顺序排列
+// Image Position (Patient)是把整个mosaic当作一幅图像时左上角像素的位置，需要换算成每一层的位置，参考dcm2niix
+
+// 0019组的Private Creator是SIEMENS MR HEADER，0029组（CSA header）的是SIEMENS CSA HEADER
+pub const SIEMENS_MR_HEADER: &str = "SIEMENS MR HEADER";
+pub const SIEMENS_CSA_HEADER: &str = "SIEMENS CSA HEADER";
+
+// 不是mosaic时返回None，Image Type中有MOSAIC但是没有层数时返回错误
+pub fn get_images_in_mosaic(data_elements: &[DataElement]) -> CommonResult<Option<usize>> {
+    let mosaic_image_type = accessor::has_value(data_elements, "0008,0008")
+        && accessor::get_strs(data_elements, "0008,0008")?
+            .iter()
+            .any(|v| v == "MOSAIC");
+
+    let tag = accessor::find_private_tag(data_elements, 0x0019, SIEMENS_MR_HEADER, 0x0A)
+        .filter(|tag| accessor::has_value(data_elements, tag));
+
+    let count = match tag {
+        Some(tag) => match &accessor::find_data_element(data_elements, &tag)?.data {
+            // 隐式VR的文件中私有元素没有VR，按照US（小端）读取
+            DicomValue::Bytes(v) if v.len() == 2 => u16::from_le_bytes([v[0], v[1]]),
+            _ => accessor::get_u16(data_elements, &tag)?,
+        },
+        // 有些匿名化工具会删掉0019组，这时使用CSA Image Header中的NumberOfImagesInMosaic
+        None if mosaic_image_type => {
+            match crate::csa::get_csa_image_header(data_elements)?
+                .and_then(|v| crate::csa::get_csa_f64(&v, "NumberOfImagesInMosaic").ok())
+            {
+                Some(count) if count >= 0.0 && count <= u16::MAX as f64 => count as u16,
+                _ => {
+                    return Err(
+                        "MOSAIC image without Number of Images in Mosaic (0019,xx0A)".into(),
+                    );
+                }
+            }
+        }
+        None => return Ok(None),
+    };
+
+    if count == 0 {
+        return Err("Number of Images in Mosaic is 0".into());
+    }
+
+    Ok(Some(count as usize))
+}
+
+// 网格的边长
+fn mosaic_size(count: usize) -> usize {
+    (1..=count).find(|v| v * v >= count).unwrap_or(1)
+}
+
+// 从0点开始的秒数转换成TM（HHMMSS.FFFFFF）
+fn format_time(seconds: f64) -> String {
+    let microseconds = (seconds * 1e6).round().max(0.0) as u64 % (86400 * 1_000_000);
+    let seconds = microseconds / 1_000_000;
+
+    format!(
+        "{:02}{:02}{:02}.{:06}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        microseconds % 1_000_000
+    )
+}
+
+// 把mosaic拆成单独的层，每一层是一个单帧图像的数据集
+// 像素数据解码成原生格式（显式小端），Image Position (Patient)换算到每一层，Image Type中去掉MOSAIC
+// 第index层的位置是第一层沿着法线方向移动index个层间距（Spacing Between Slices，没有时使用Slice Thickness）
+// 有CSA Image Header时按照SliceNormalVector确定法线的方向，按照MosaicRefAcqTimes设置每一层的Acquisition Time
+pub fn unpack_mosaic(data_elements: &[DataElement]) -> CommonResult<Vec<Vec<DataElement>>> {
+    let count = get_images_in_mosaic(data_elements)?.ok_or("not a Siemens mosaic image")?;
+
+    let frames = crate::pixel::frames(data_elements)?;
+    let description = frames.description().clone();
+
+    if description.samples_per_pixel != 1
+        || description.number_of_frames > 1
+        || !description.bits_allocated.is_multiple_of(8)
+    {
+        return Err(format!(
+            "mosaic with {} samples, {} frames and {} bits allocated is not supported",
+            description.samples_per_pixel, description.number_of_frames, description.bits_allocated
+        )
+        .into());
+    }
+
+    let size = mosaic_size(count);
+    let rows = description.rows as usize;
+    let columns = description.columns as usize;
+
+    if !rows.is_multiple_of(size) || !columns.is_multiple_of(size) {
+        return Err(format!(
+            "mosaic of {}x{} can not be divided into {}x{} images",
+            rows, columns, size, size
+        )
+        .into());
+    }
+
+    let (slice_rows, slice_columns) = (rows / size, columns / size);
+    let bytes_per_pixel = description.bits_allocated as usize / 8;
+    let frame = frames.frame(0)?;
+
+    let mosaic_geometry = geometry::get_image_geometry(data_elements)?;
+    let (row_spacing, column_spacing) = mosaic_geometry.pixel_spacing;
+    let csa_header = crate::csa::get_csa_image_header(data_elements)?;
+    let mut normal = geometry::normal(&mosaic_geometry);
+
+    // 各层在mosaic中的顺序沿着CSA中的SliceNormalVector，和row_direction × column_direction可能相反
+    if let Some(slice_normal) = csa_header
+        .as_ref()
+        .and_then(|v| crate::csa::get_csa_f64s(v, "SliceNormalVector").ok())
+    {
+        if let [x, y, z] = slice_normal[..] {
+            if geometry::dot([x, y, z], normal) < 0.0 {
+                normal = normal.map(|v| -v);
+            }
+        }
+    }
+
+    // MosaicRefAcqTimes是每一层相对于Acquisition Time的采集时间（ms），换算成每一层的Acquisition Time
+    let acquisition_time = if accessor::has_value(data_elements, "0008,0032") {
+        accessor::parse_time(&accessor::get_str(data_elements, "0008,0032")?)
+    } else {
+        None
+    };
+    let slice_times = csa_header
+        .as_ref()
+        .and_then(|v| crate::csa::get_csa_f64s(v, "MosaicRefAcqTimes").ok())
+        .filter(|v| v.len() >= count);
+    let slice_spacing = mosaic_geometry
+        .spacing_between_slices
+        .or(mosaic_geometry.slice_thickness)
+        .ok_or("mosaic without Spacing Between Slices or Slice Thickness")?;
+
+    // 从mosaic左上角移动到第一层左上角的位置
+    let row_offset = (rows - slice_rows) as f64 / 2.0;
+    let column_offset = (columns - slice_columns) as f64 / 2.0;
+
+    let image_type = if accessor::has_value(data_elements, "0008,0008") {
+        Some(
+            accessor::get_strs(data_elements, "0008,0008")?
+                .into_iter()
+                .filter(|v| v != "MOSAIC")
+                .collect::<Vec<String>>()
+                .join("\\"),
+        )
+    } else {
+        None
+    };
+
+    let count_tag = accessor::find_private_tag(data_elements, 0x0019, SIEMENS_MR_HEADER, 0x0A);
+
+    let mut common = data_elements
+        .iter()
+        .filter(|v| Some(&v.tag) != count_tag.as_ref() && v.tag != "0028,0008")
+        .cloned()
+        .collect::<Vec<DataElement>>();
+
+    if accessor::has_value(data_elements, "0002,0010") {
+        crate::util::set_data_element(
+            &mut common,
+            crate::util::new_data_element(
+                "0002,0010",
+                "UI",
+                DicomValue::String(TransferSyntax::ExplicitVrLittleEndian.uid().to_string()),
+            )?,
+        );
+    }
+
+    if let Some(image_type) = image_type {
+        crate::util::set_data_element(
+            &mut common,
+            crate::util::new_data_element("0008,0008", "CS", DicomValue::String(image_type))?,
+        );
+    }
+
+    crate::util::set_data_element(
+        &mut common,
+        crate::util::new_data_element("0028,0010", "US", DicomValue::U16(vec![slice_rows as u16]))?,
+    );
+    crate::util::set_data_element(
+        &mut common,
+        crate::util::new_data_element(
+            "0028,0011",
+            "US",
+            DicomValue::U16(vec![slice_columns as u16]),
+        )?,
+    );
+
+    let mut result = Vec::new();
+
+    for index in 0..count {
+        let (tile_row, tile_column) = (index / size, index % size);
+        let mut pixels = Vec::with_capacity(slice_rows * slice_columns * bytes_per_pixel);
+
+        for row in 0..slice_rows {
+            let start = ((tile_row * slice_rows + row) * columns + tile_column * slice_columns)
+                * bytes_per_pixel;
+
+            pixels.extend(&frame[start..start + slice_columns * bytes_per_pixel]);
+        }
+
+        let position = [0, 1, 2].map(|axis| {
+            mosaic_geometry.position[axis]
+                + mosaic_geometry.row_direction[axis] * column_offset * column_spacing
+                + mosaic_geometry.column_direction[axis] * row_offset * row_spacing
+                + normal[axis] * slice_spacing * index as f64
+        });
+
+        let mut slice = common.clone();
+
+        if let Some(slice_times) = &slice_times {
+            crate::util::set_data_element(
+                &mut slice,
+                crate::util::new_data_element(
+                    "0008,0032",
+                    "TM",
+                    DicomValue::String(format_time(
+                        acquisition_time.unwrap_or(0.0) + slice_times[index] / 1000.0,
+                    )),
+                )?,
+            );
+        }
+
+        crate::util::set_data_element(
+            &mut slice,
+            crate::util::new_data_element(
+                "0020,0032",
+                "DS",
+                DicomValue::Double(position.to_vec()),
+            )?,
+        );
+        crate::util::set_data_element(
+            &mut slice,
+            crate::util::new_data_element(
+                "7FE0,0010",
+                if bytes_per_pixel == 1 { "OB" } else { "OW" },
+                DicomValue::Bytes(pixels),
+            )?,
+        );
+
+        result.push(slice);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,390 @@
+use image::DynamicImage;
+
+use crate::{
+    accessor, geometry,
+    model::{
+        ImageGeometry, NpyData, PixelPadding, Projection, RenderOptions, Reslice, ResliceOptions,
+        ReslicePlane, VoiTransform, Volume,
+    },
+    CommonResult,
+};
+
+// 多平面重建（MPR）和层块投影（MIP/MinIP/AvgIP）
+// 输出图像的每个像素是病人坐标系中的一个点，通过affine的逆矩阵换算成体素坐标后做三线性插值
+// 层块投影在切面的法线方向上按照像素间距取若干层，再按照Projection合并
+
+// 切面内沿着一行和沿着一列的方向（LPS）
+fn plane_directions(plane: ReslicePlane) -> CommonResult<([f64; 3], [f64; 3])> {
+    let (row_direction, column_direction) = match plane {
+        ReslicePlane::Axial => ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ReslicePlane::Coronal => ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ReslicePlane::Sagittal => ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+        ReslicePlane::Oblique {
+            row_direction,
+            column_direction,
+        } => (row_direction, column_direction),
+    };
+
+    let row_direction = geometry::normalize(row_direction).ok_or("row direction is zero")?;
+    let column_direction =
+        geometry::normalize(column_direction).ok_or("column direction is zero")?;
+
+    if geometry::dot(row_direction, column_direction).abs() > 1e-3 {
+        return Err("row and column directions of the reslice are not orthogonal".into());
+    }
+
+    Ok((row_direction, column_direction))
+}
+
+// affine中3x3部分的逆矩阵，病人坐标减去原点后乘以它得到(column, row, slice)
+fn inverse(affine: &[[f64; 4]; 4]) -> CommonResult<[[f64; 3]; 3]> {
+    let m = |row: usize, column: usize| affine[row][column];
+
+    let cofactors = [
+        [
+            m(1, 1) * m(2, 2) - m(1, 2) * m(2, 1),
+            m(0, 2) * m(2, 1) - m(0, 1) * m(2, 2),
+            m(0, 1) * m(1, 2) - m(0, 2) * m(1, 1),
+        ],
+        [
+            m(1, 2) * m(2, 0) - m(1, 0) * m(2, 2),
+            m(0, 0) * m(2, 2) - m(0, 2) * m(2, 0),
+            m(0, 2) * m(1, 0) - m(0, 0) * m(1, 2),
+        ],
+        [
+            m(1, 0) * m(2, 1) - m(1, 1) * m(2, 0),
+            m(0, 1) * m(2, 0) - m(0, 0) * m(2, 1),
+            m(0, 0) * m(1, 1) - m(0, 1) * m(1, 0),
+        ],
+    ];
+    let determinant =
+        m(0, 0) * cofactors[0][0] + m(0, 1) * cofactors[1][0] + m(0, 2) * cofactors[2][0];
+
+    if determinant.abs() < 1e-12 || !determinant.is_finite() {
+        return Err("volume affine is singular".into());
+    }
+
+    Ok(cofactors.map(|row| row.map(|v| v / determinant)))
+}
+
+fn to_patient(affine: &[[f64; 4]; 4], index: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|axis| {
+        affine[axis][0] * index[0]
+            + affine[axis][1] * index[1]
+            + affine[axis][2] * index[2]
+            + affine[axis][3]
+    })
+}
+
+fn pixel_representation(volume: &Volume) -> CommonResult<u16> {
+    if accessor::has_value(&volume.attributes, "0028,0103") {
+        accessor::get_u16(&volume.attributes, "0028,0103")
+    } else {
+        Ok(0)
+    }
+}
+
+// 第index个体素的Modality LUT输出值，填充像素返回None
+fn voxel(volume: &Volume, padding: Option<&PixelPadding>, index: usize) -> Option<f64> {
+    let stored = match &volume.data {
+        NpyData::U8(data) => data[index] as f64,
+        NpyData::U16(data) => data[index] as f64,
+        NpyData::I16(data) => data[index] as f64,
+        NpyData::F32(data) => data[index] as f64,
+    };
+
+    if padding.is_some_and(|v| crate::padding::is_padding(v, stored as i64)) {
+        return None;
+    }
+
+    Some(stored * volume.rescale_slope + volume.rescale_intercept)
+}
+
+// 三线性插值，体数据边界之外半个体素以内的点按照边界上的值计算
+// 相邻的8个体素中有填充像素时只使用其余的体素，全部是填充像素或者在体数据之外时返回None
+fn sample(
+    volume: &Volume,
+    padding: Option<&PixelPadding>,
+    offset: usize,
+    index: [f64; 3],
+) -> Option<f64> {
+    let [columns, rows, slices] = [volume.shape[2], volume.shape[1], volume.shape[0]];
+    let mut lower = [0; 3];
+    let mut fraction = [0.0; 3];
+
+    for (axis, size) in [columns, rows, slices].into_iter().enumerate() {
+        let value = index[axis];
+
+        if !(-0.5..=size as f64 - 0.5).contains(&value) {
+            return None;
+        }
+
+        let value = value.clamp(0.0, (size - 1) as f64);
+
+        lower[axis] = (value.floor() as usize).min(size.saturating_sub(2));
+        fraction[axis] = value - lower[axis] as f64;
+    }
+
+    let mut sum = 0.0;
+    let mut weight_sum = 0.0;
+
+    for corner in 0..8 {
+        let step = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+        let weight = (0..3)
+            .map(|axis| {
+                if step[axis] == 1 {
+                    fraction[axis]
+                } else {
+                    1.0 - fraction[axis]
+                }
+            })
+            .product::<f64>();
+
+        if weight == 0.0 {
+            continue;
+        }
+
+        let [column, row, slice] = [0, 1, 2].map(|axis| lower[axis] + step[axis]);
+
+        if let Some(value) = voxel(
+            volume,
+            padding,
+            offset + (slice * rows + row) * columns + column,
+        ) {
+            sum += value * weight;
+            weight_sum += weight;
+        }
+    }
+
+    (weight_sum > 0.0).then(|| sum / weight_sum)
+}
+
+// 按照options从体数据中重建一幅图像
+// 图像覆盖体数据在切面上的整个投影范围，第一个像素在左上角，slab_thickness大于0时在层块内按照projection投影
+pub fn reslice(volume: &Volume, options: &ResliceOptions) -> CommonResult<Reslice> {
+    let [slices, rows, columns] = volume.shape;
+
+    if slices == 0 || rows == 0 || columns == 0 {
+        return Err("volume is empty".into());
+    }
+
+    if options.time_point >= volume.time_points.max(1) {
+        return Err(format!(
+            "time point {} is out of range, the volume has {} time points",
+            options.time_point, volume.time_points
+        )
+        .into());
+    }
+
+    let (row_direction, column_direction) = plane_directions(options.plane)?;
+    let normal = geometry::cross(row_direction, column_direction);
+    let inverse = inverse(&volume.affine)?;
+    let origin = [0, 1, 2].map(|axis| volume.affine[axis][3]);
+
+    let pixel_spacing = options.pixel_spacing.unwrap_or_else(|| {
+        volume
+            .spacing
+            .iter()
+            .copied()
+            .filter(|v| *v > 0.0)
+            .fold(f64::INFINITY, f64::min)
+    });
+
+    if !(pixel_spacing > 0.0 && pixel_spacing.is_finite()) {
+        return Err(format!("invalid reslice pixel spacing {}", pixel_spacing).into());
+    }
+
+    let last = [columns, rows, slices].map(|v| (v - 1) as f64);
+    let center = options
+        .center
+        .unwrap_or_else(|| to_patient(&volume.affine, last.map(|v| v / 2.0)));
+
+    // 体数据的8个角在切面上的投影范围
+    let corners = (0..8)
+        .map(|corner| {
+            let index = [0, 1, 2].map(|axis| {
+                if (corner >> axis) & 1 == 1 {
+                    last[axis]
+                } else {
+                    0.0
+                }
+            });
+            let point = to_patient(&volume.affine, index);
+            let relative = [0, 1, 2].map(|axis| point[axis] - center[axis]);
+
+            (
+                geometry::dot(relative, row_direction),
+                geometry::dot(relative, column_direction),
+            )
+        })
+        .collect::<Vec<(f64, f64)>>();
+
+    let left = corners.iter().map(|v| v.0).fold(f64::INFINITY, f64::min);
+    let right = corners
+        .iter()
+        .map(|v| v.0)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let top = corners.iter().map(|v| v.1).fold(f64::INFINITY, f64::min);
+    let bottom = corners
+        .iter()
+        .map(|v| v.1)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    let output_columns = ((right - left) / pixel_spacing + 1e-6).floor() as usize + 1;
+    let output_rows = ((bottom - top) / pixel_spacing + 1e-6).floor() as usize + 1;
+
+    if output_columns > u16::MAX as usize || output_rows > u16::MAX as usize {
+        return Err(format!(
+            "reslice of {}x{} pixels is too large, use a larger pixel spacing",
+            output_rows, output_columns
+        )
+        .into());
+    }
+
+    // 层块内的各层相对于切面的距离，层间距与像素间距相同
+    let slab_thickness = options.slab_thickness.max(0.0);
+    let layers = (slab_thickness / pixel_spacing + 1e-6).floor() as usize + 1;
+    let layer_offsets = (0..layers)
+        .map(|v| (v as f64 - (layers - 1) as f64 / 2.0) * pixel_spacing)
+        .collect::<Vec<f64>>();
+
+    let pixel_representation = pixel_representation(volume)?;
+
+    // 只有保存存储值（所有层的Rescale相同）时才能按照Pixel Padding Value判断填充像素
+    let padding = match volume.data {
+        NpyData::F32(_) => None,
+        _ => crate::padding::get_pixel_padding(&volume.attributes, pixel_representation)?,
+    };
+
+    let offset = options.time_point * slices * rows * columns;
+    let position = [0, 1, 2]
+        .map(|axis| center[axis] + row_direction[axis] * left + column_direction[axis] * top);
+    let mut values = Vec::with_capacity(output_rows * output_columns);
+
+    for row in 0..output_rows {
+        for column in 0..output_columns {
+            let samples = layer_offsets
+                .iter()
+                .filter_map(|layer_offset| {
+                    let point = [0, 1, 2].map(|axis| {
+                        position[axis]
+                            + row_direction[axis] * column as f64 * pixel_spacing
+                            + column_direction[axis] * row as f64 * pixel_spacing
+                            + normal[axis] * layer_offset
+                            - origin[axis]
+                    });
+                    let index = inverse.map(|v| geometry::dot(v, point));
+
+                    sample(volume, padding.as_ref(), offset, index)
+                })
+                .collect::<Vec<f64>>();
+
+            let value = if samples.is_empty() {
+                f64::NAN
+            } else {
+                match options.projection {
+                    Projection::Maximum => samples.iter().copied().fold(f64::MIN, f64::max),
+                    Projection::Minimum => samples.iter().copied().fold(f64::MAX, f64::min),
+                    Projection::Average => samples.iter().sum::<f64>() / samples.len() as f64,
+                }
+            };
+
+            values.push(value);
+        }
+    }
+
+    Ok(Reslice {
+        geometry: ImageGeometry {
+            rows: output_rows as u16,
+            columns: output_columns as u16,
+            position,
+            row_direction,
+            column_direction,
+            pixel_spacing: (pixel_spacing, pixel_spacing),
+            slice_thickness: (slab_thickness > 0.0).then_some(slab_thickness),
+            spacing_between_slices: None,
+        },
+        values,
+    })
+}
+
+// 按照和单幅图像相同的显示流程渲染重建出的图像：指定的窗宽窗位 > 文件中第voi_index个预设 > 自动计算
+// 体数据范围之外和全部是填充像素的位置显示为黑色，overlay、shutter和padding的选项不起作用
+pub fn render_reslice(
+    volume: &Volume,
+    reslice: &Reslice,
+    options: &RenderOptions,
+) -> CommonResult<DynamicImage> {
+    let pixel_representation = pixel_representation(volume)?;
+
+    let voi_transform = match &options.window {
+        Some(window) => VoiTransform::Window(window.clone()),
+        None => {
+            let transforms =
+                crate::lut::get_voi_transforms(&volume.attributes, pixel_representation)?;
+            let count = transforms.len();
+
+            match transforms.into_iter().nth(options.voi_index) {
+                Some(transform) => transform,
+                None if options.voi_index == 0 => {
+                    crate::lut::compute_auto_window(&reslice.values, options.auto_window)
+                        .map(VoiTransform::Window)
+                        .ok_or("no pixel values to compute a window from")?
+                }
+                None => {
+                    return Err(format!(
+                        "VOI preset {} is out of range, the file has {} presets",
+                        options.voi_index, count
+                    )
+                    .into());
+                }
+            }
+        }
+    };
+
+    let monochrome1 = accessor::has_value(&volume.attributes, "0028,0004")
+        && accessor::get_str(&volume.attributes, "0028,0004")? == "MONOCHROME1";
+    let invert = monochrome1 != options.invert;
+
+    let values = reslice
+        .values
+        .iter()
+        .map(|value| {
+            if value.is_nan() {
+                return 0.0;
+            }
+
+            let output = crate::lut::apply_voi(*value, &voi_transform);
+
+            if invert {
+                1.0 - output
+            } else {
+                output
+            }
+        })
+        .collect::<Vec<f64>>();
+
+    let image = crate::render::grayscale_to_image(
+        reslice.geometry.columns as u32,
+        reslice.geometry.rows as u32,
+        &values,
+        options.bit_depth,
+    )?;
+
+    Ok(crate::render::resize(image, options.size))
+}
+
+// 重建并保存到file_path，格式由render_options.format决定
+pub fn reslice_to_file<P: AsRef<std::path::Path>>(
+    volume: &Volume,
+    file_path: P,
+    reslice_options: &ResliceOptions,
+    render_options: &RenderOptions,
+) -> CommonResult<()> {
+    let reslice = reslice(volume, reslice_options)?;
+
+    render_reslice(volume, &reslice, render_options)?
+        .save_with_format(file_path, render_options.format)?;
+
+    Ok(())
+}
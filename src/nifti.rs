@@ -0,0 +1,376 @@
+use std::path::Path;
+
+use crate::{
+    accessor,
+    export::{json_number, json_string},
+    geometry,
+    model::{NiftiVersion, NpyData, Volume},
+    CommonResult,
+};
+
+// 把体数据保存为NIfTI（.nii，不压缩），参考https://nifti.nimh.nih.gov/nifti-1和nifti-2
+// NIfTI使用RAS坐标系（x指向病人的右边，y指向病人的前面），DICOM的LPS坐标需要把x和y取反
+// 数据按照column最快、time最慢的顺序保存，和Volume::data的顺序相同，不需要重新排列
+
+// 体素坐标到RAS坐标的变换
+pub fn ras_affine(volume: &Volume) -> [[f64; 4]; 4] {
+    let mut result = volume.affine;
+
+    for row in result.iter_mut().take(2) {
+        *row = row.map(|v| -v);
+    }
+
+    result
+}
+
+// qform只能表示旋转、平移和体素大小，机架倾斜时slice方向使用法线，倾斜的信息只保存在sform中
+// 返回(quatern_b, quatern_c, quatern_d, qfac)，参考nifti1_io.c中的nifti_mat44_to_quatern
+fn quaternion(affine: &[[f64; 4]; 4]) -> CommonResult<(f64, f64, f64, f64)> {
+    let column = |index: usize| [affine[0][index], affine[1][index], affine[2][index]];
+
+    let x = geometry::normalize(column(0)).ok_or("the column direction of the affine is zero")?;
+    let y = geometry::normalize(column(1)).ok_or("the row direction of the affine is zero")?;
+    let mut z = geometry::cross(x, y);
+
+    // 层的方向和法线相反时是左手坐标系，qfac为-1
+    let qfac = if geometry::dot(z, column(2)) < 0.0 {
+        z = z.map(|v| -v);
+        -1.0
+    } else {
+        1.0
+    };
+
+    // 旋转矩阵，r[i][j]是第i行第j列，第三列按照qfac取反之后是右手坐标系
+    let r = [0, 1, 2].map(|i| [x[i], y[i], z[i] * qfac]);
+
+    let trace = r[0][0] + r[1][1] + r[2][2];
+
+    let (a, b, c, d) = if trace > 0.0 {
+        let s = 0.5 / (trace + 1.0).sqrt();
+        (
+            0.25 / s,
+            (r[2][1] - r[1][2]) * s,
+            (r[0][2] - r[2][0]) * s,
+            (r[1][0] - r[0][1]) * s,
+        )
+    } else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+        let s = 2.0 * (1.0 + r[0][0] - r[1][1] - r[2][2]).sqrt();
+        (
+            (r[2][1] - r[1][2]) / s,
+            0.25 * s,
+            (r[0][1] + r[1][0]) / s,
+            (r[0][2] + r[2][0]) / s,
+        )
+    } else if r[1][1] > r[2][2] {
+        let s = 2.0 * (1.0 + r[1][1] - r[0][0] - r[2][2]).sqrt();
+        (
+            (r[0][2] - r[2][0]) / s,
+            (r[0][1] + r[1][0]) / s,
+            0.25 * s,
+            (r[1][2] + r[2][1]) / s,
+        )
+    } else {
+        let s = 2.0 * (1.0 + r[2][2] - r[0][0] - r[1][1]).sqrt();
+        (
+            (r[1][0] - r[0][1]) / s,
+            (r[0][2] + r[2][0]) / s,
+            (r[1][2] + r[2][1]) / s,
+            0.25 * s,
+        )
+    };
+
+    // NIfTI中a = sqrt(1 - b² - c² - d²)，要求a不小于0
+    Ok(if a < 0.0 {
+        (-b, -c, -d, qfac)
+    } else {
+        (b, c, d, qfac)
+    })
+}
+
+// NIfTI的datatype和bitpix
+fn data_type(data: &NpyData) -> (i16, i16) {
+    match data {
+        NpyData::U8(_) => (2, 8),
+        NpyData::I16(_) => (4, 16),
+        NpyData::F32(_) => (16, 32),
+        NpyData::U16(_) => (512, 16),
+    }
+}
+
+fn data_bytes(data: &NpyData) -> Vec<u8> {
+    match data {
+        NpyData::U8(v) => v.clone(),
+        NpyData::U16(v) => v.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        NpyData::I16(v) => v.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        NpyData::F32(v) => v.iter().flat_map(|v| v.to_le_bytes()).collect(),
+    }
+}
+
+fn put(header: &mut [u8], offset: usize, bytes: &[u8]) {
+    header[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+// Repetition Time (0018,0080)，单位是秒
+fn repetition_time(volume: &Volume) -> Option<f64> {
+    accessor::get_f64(&volume.attributes, "0018,0080")
+        .ok()
+        .map(|v| v / 1000.0)
+}
+
+// 编码成单个文件（.nii）的内容：文件头 + 4字节的extension标记（全为0，没有extension）+ 数据，都是小端
+pub fn encode_nifti(volume: &Volume, version: NiftiVersion) -> CommonResult<Vec<u8>> {
+    let length = match &volume.data {
+        NpyData::U8(v) => v.len(),
+        NpyData::U16(v) => v.len(),
+        NpyData::I16(v) => v.len(),
+        NpyData::F32(v) => v.len(),
+    };
+
+    if volume.shape.iter().product::<usize>() * volume.time_points != length {
+        return Err(format!(
+            "shape {:?} with {} time points does not match {} values",
+            volume.shape, volume.time_points, length
+        )
+        .into());
+    }
+
+    let affine = ras_affine(volume);
+    let (quatern_b, quatern_c, quatern_d, qfac) = quaternion(&affine)?;
+    let (datatype, bitpix) = data_type(&volume.data);
+
+    // NIfTI的维度顺序是(x, y, z, t) = (column, row, slice, time)
+    let dim = [
+        if volume.time_points > 1 { 4 } else { 3 },
+        volume.shape[2] as i64,
+        volume.shape[1] as i64,
+        volume.shape[0] as i64,
+        volume.time_points as i64,
+        1,
+        1,
+        1,
+    ];
+    let pixdim = [
+        qfac,
+        volume.spacing[0],
+        volume.spacing[1],
+        volume.spacing[2],
+        repetition_time(volume).unwrap_or(0.0),
+        0.0,
+        0.0,
+        0.0,
+    ];
+    // 空间单位是mm（2），时间单位是秒（8）
+    let xyzt_units = 2 | 8;
+    // qform_code和sform_code都是1（NIFTI_XFORM_SCANNER_ANAT）
+    let xform_code: i16 = 1;
+    let description = b"dicom_parser";
+
+    let mut result = match version {
+        NiftiVersion::Nifti1 => {
+            let mut header = vec![0; 352];
+
+            put(&mut header, 0, &348_i32.to_le_bytes());
+            put(&mut header, 38, b"r");
+            for (index, value) in dim.iter().enumerate() {
+                if *value > i16::MAX as i64 {
+                    return Err(format!(
+                        "dimension {} is too large for NIfTI-1, use NIfTI-2",
+                        value
+                    )
+                    .into());
+                }
+
+                put(&mut header, 40 + index * 2, &(*value as i16).to_le_bytes());
+            }
+            put(&mut header, 70, &datatype.to_le_bytes());
+            put(&mut header, 72, &bitpix.to_le_bytes());
+            for (index, value) in pixdim.iter().enumerate() {
+                put(&mut header, 76 + index * 4, &(*value as f32).to_le_bytes());
+            }
+            put(&mut header, 108, &352_f32.to_le_bytes());
+            put(
+                &mut header,
+                112,
+                &(volume.rescale_slope as f32).to_le_bytes(),
+            );
+            put(
+                &mut header,
+                116,
+                &(volume.rescale_intercept as f32).to_le_bytes(),
+            );
+            header[123] = xyzt_units;
+            put(&mut header, 148, description);
+            put(&mut header, 252, &xform_code.to_le_bytes());
+            put(&mut header, 254, &xform_code.to_le_bytes());
+            for (index, value) in [
+                quatern_b,
+                quatern_c,
+                quatern_d,
+                affine[0][3],
+                affine[1][3],
+                affine[2][3],
+            ]
+            .iter()
+            .enumerate()
+            {
+                put(&mut header, 256 + index * 4, &(*value as f32).to_le_bytes());
+            }
+            for (row, values) in affine.iter().take(3).enumerate() {
+                for (index, value) in values.iter().enumerate() {
+                    put(
+                        &mut header,
+                        280 + row * 16 + index * 4,
+                        &(*value as f32).to_le_bytes(),
+                    );
+                }
+            }
+            put(&mut header, 344, b"n+1\0");
+
+            header
+        }
+        NiftiVersion::Nifti2 => {
+            let mut header = vec![0; 544];
+
+            put(&mut header, 0, &540_i32.to_le_bytes());
+            put(&mut header, 4, b"n+2\0\r\n\x1a\n");
+            put(&mut header, 12, &datatype.to_le_bytes());
+            put(&mut header, 14, &bitpix.to_le_bytes());
+            for (index, value) in dim.iter().enumerate() {
+                put(&mut header, 16 + index * 8, &value.to_le_bytes());
+            }
+            for (index, value) in pixdim.iter().enumerate() {
+                put(&mut header, 104 + index * 8, &value.to_le_bytes());
+            }
+            put(&mut header, 168, &544_i64.to_le_bytes());
+            put(&mut header, 176, &volume.rescale_slope.to_le_bytes());
+            put(&mut header, 184, &volume.rescale_intercept.to_le_bytes());
+            put(&mut header, 240, description);
+            put(&mut header, 344, &(xform_code as i32).to_le_bytes());
+            put(&mut header, 348, &(xform_code as i32).to_le_bytes());
+            for (index, value) in [
+                quatern_b,
+                quatern_c,
+                quatern_d,
+                affine[0][3],
+                affine[1][3],
+                affine[2][3],
+            ]
+            .iter()
+            .enumerate()
+            {
+                put(&mut header, 352 + index * 8, &value.to_le_bytes());
+            }
+            for (row, values) in affine.iter().take(3).enumerate() {
+                for (index, value) in values.iter().enumerate() {
+                    put(
+                        &mut header,
+                        400 + row * 32 + index * 8,
+                        &value.to_le_bytes(),
+                    );
+                }
+            }
+            put(&mut header, 500, &(xyzt_units as i32).to_le_bytes());
+
+            header
+        }
+    };
+
+    result.extend(data_bytes(&volume.data));
+
+    Ok(result)
+}
+
+// BIDS格式的sidecar，时间的单位都是秒，文件中没有的属性不写
+// SliceTiming是每一层相对于最早的一层的采集时间，只有所有层都有Acquisition Time并且不完全相同时才写
+pub fn bids_sidecar(volume: &Volume) -> CommonResult<String> {
+    let attributes = &volume.attributes;
+    let mut fields = Vec::new();
+
+    for (key, tag) in [
+        ("Modality", "0008,0060"),
+        ("Manufacturer", "0008,0070"),
+        ("ManufacturersModelName", "0008,1090"),
+        ("SeriesDescription", "0008,103E"),
+        ("ProtocolName", "0018,1030"),
+    ] {
+        if accessor::has_value(attributes, tag) {
+            fields.push((key, json_string(&accessor::get_str(attributes, tag)?)));
+        }
+    }
+
+    fields.push((
+        "SeriesInstanceUID",
+        json_string(&volume.series_instance_uid),
+    ));
+
+    if accessor::has_value(attributes, "0020,0011") {
+        fields.push((
+            "SeriesNumber",
+            accessor::get_i64(attributes, "0020,0011")?.to_string(),
+        ));
+    }
+
+    // (key, tag, 换算到BIDS单位时的除数)
+    for (key, tag, divisor) in [
+        ("MagneticFieldStrength", "0018,0087", 1.0),
+        ("SliceThickness", "0018,0050", 1.0),
+        ("RepetitionTime", "0018,0080", 1000.0),
+        ("EchoTime", "0018,0081", 1000.0),
+        ("InversionTime", "0018,0082", 1000.0),
+        ("FlipAngle", "0018,1314", 1.0),
+    ] {
+        if accessor::has_value(attributes, tag) {
+            fields.push((
+                key,
+                json_number(accessor::get_f64(attributes, tag)? / divisor),
+            ));
+        }
+    }
+
+    let times = volume
+        .acquisition_times
+        .iter()
+        .copied()
+        .collect::<Option<Vec<f64>>>()
+        .unwrap_or_default();
+
+    if let Some(first) = times.iter().copied().reduce(f64::min) {
+        if times.iter().any(|v| *v != first) {
+            fields.push((
+                "SliceTiming",
+                format!(
+                    "[{}]",
+                    times
+                        .iter()
+                        // 去掉浮点数减法带来的误差，Acquisition Time最多精确到微秒
+                        .map(|v| json_number(((v - first) * 1e6).round() / 1e6))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            ));
+        }
+    }
+
+    Ok(format!(
+        "{{\n{}\n}}\n",
+        fields
+            .iter()
+            .map(|(key, value)| format!("  {}: {}", json_string(key), value))
+            .collect::<Vec<String>>()
+            .join(",\n")
+    ))
+}
+
+// 保存到file_path（.nii），并在同一目录下写出BIDS sidecar（扩展名为.json）
+pub fn write_nifti<P: AsRef<Path>>(
+    volume: &Volume,
+    file_path: P,
+    version: NiftiVersion,
+) -> CommonResult<()> {
+    let file_path = file_path.as_ref();
+
+    std::fs::write(file_path, encode_nifti(volume, version)?)?;
+    std::fs::write(file_path.with_extension("json"), bids_sidecar(volume)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use crate::{
+    accessor,
+    model::{DataElement, DicomValue, Overlay},
+    CommonResult,
+};
+
+// 60xx组的tag，group为0x6000~0x601E
+fn overlay_tag(group: u16, element: u16) -> String {
+    format!("{:04X},{:04X}", group, element)
+}
+
+fn optional_str(data_elements: &[DataElement], tag: &str) -> CommonResult<Option<String>> {
+    if accessor::has_value(data_elements, tag) {
+        Ok(Some(accessor::get_str(data_elements, tag)?))
+    } else {
+        Ok(None)
+    }
+}
+
+// 读取文件中所有的overlay（最多16个），按照组号排序
+pub fn get_overlays(data_elements: &[DataElement]) -> CommonResult<Vec<Overlay>> {
+    let mut result = Vec::new();
+
+    for group in (0x6000..=0x601E).step_by(2) {
+        if accessor::has_value(data_elements, &overlay_tag(group, 0x0010)) {
+            result.push(get_overlay(data_elements, group)?);
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn get_overlay(data_elements: &[DataElement], group: u16) -> CommonResult<Overlay> {
+    if !(0x6000..=0x601E).contains(&group) || !group.is_multiple_of(2) {
+        return Err(format!("{:04X} is not an overlay group", group).into());
+    }
+
+    let tag = |element: u16| overlay_tag(group, element);
+
+    let rows = accessor::get_u16(data_elements, &tag(0x0010))?;
+    let columns = accessor::get_u16(data_elements, &tag(0x0011))?;
+
+    let overlay_type = optional_str(data_elements, &tag(0x0040))?.unwrap_or("G".to_string());
+
+    let origin = if accessor::has_value(data_elements, &tag(0x0050)) {
+        let origin = accessor::get_i32s(data_elements, &tag(0x0050))?;
+
+        if origin.len() != 2 {
+            return Err(format!("{} has VM {}, expected 2", tag(0x0050), origin.len()).into());
+        }
+
+        (origin[0], origin[1])
+    } else {
+        (1, 1)
+    };
+
+    let number_of_frames = if accessor::has_value(data_elements, &tag(0x0015)) {
+        accessor::get_u32(data_elements, &tag(0x0015))?.max(1)
+    } else {
+        1
+    };
+
+    let image_frame_origin = if accessor::has_value(data_elements, &tag(0x0051)) {
+        accessor::get_u32(data_elements, &tag(0x0051))?.max(1)
+    } else {
+        1
+    };
+
+    let point_count = rows as usize * columns as usize;
+
+    let data = if accessor::has_value(data_elements, &tag(0x3000)) {
+        read_overlay_data(
+            data_elements,
+            &tag(0x3000),
+            point_count * number_of_frames as usize,
+        )?
+    } else {
+        // 老的文件会把overlay放在像素数据中没有使用的高位里
+        let bit_position = accessor::get_u16(data_elements, &tag(0x0102))?;
+
+        read_embedded_overlay(data_elements, rows, columns, bit_position)?
+    };
+
+    // 嵌入在像素数据中的overlay每个图像帧都有一帧
+    let number_of_frames = (data.len() / point_count.max(1)) as u32;
+
+    Ok(Overlay {
+        group,
+        rows,
+        columns,
+        overlay_type,
+        origin,
+        number_of_frames,
+        image_frame_origin,
+        description: optional_str(data_elements, &tag(0x0022))?,
+        label: optional_str(data_elements, &tag(0x1500))?,
+        data,
+    })
+}
+
+// Overlay Data中每个点占1位，从每个字节（OW时是每个16位字）的最低位开始，帧与帧之间不对齐
+fn read_overlay_data(
+    data_elements: &[DataElement],
+    tag: &str,
+    bit_count: usize,
+) -> CommonResult<Vec<bool>> {
+    let bytes = match &accessor::find_data_element(data_elements, tag)?.data {
+        DicomValue::Bytes(v) => v.clone(),
+        _ => accessor::get_words(data_elements, tag)?
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect(),
+    };
+
+    if bytes.len() * 8 < bit_count {
+        return Err(format!(
+            "{} has {} bits, expected {}",
+            tag,
+            bytes.len() * 8,
+            bit_count
+        )
+        .into());
+    }
+
+    Ok((0..bit_count)
+        .map(|index| bytes[index / 8] >> (index % 8) & 1 == 1)
+        .collect())
+}
+
+// 从像素数据的第bit_position位取出overlay，所有图像帧依次排列
+fn read_embedded_overlay(
+    data_elements: &[DataElement],
+    rows: u16,
+    columns: u16,
+    bit_position: u16,
+) -> CommonResult<Vec<bool>> {
+    let frames = crate::pixel::frames(data_elements)?;
+    let description = frames.description().clone();
+
+    if description.rows != rows || description.columns != columns {
+        return Err(format!(
+            "embedded overlay is {}x{} but the image is {}x{}",
+            columns, rows, description.columns, description.rows
+        )
+        .into());
+    }
+
+    if description.samples_per_pixel != 1 || bit_position >= description.bits_allocated {
+        return Err(format!(
+            "overlay bit position {} is not in the pixel data",
+            bit_position
+        )
+        .into());
+    }
+
+    let point_count = rows as usize * columns as usize;
+    let mut result = Vec::new();
+
+    for frame in frames {
+        let frame = frame?;
+
+        for index in 0..point_count {
+            let sample = match description.bits_allocated {
+                8 => frame[index] as u32,
+                16 => u16::from_le_bytes([frame[index * 2], frame[index * 2 + 1]]) as u32,
+                bits_allocated => {
+                    return Err(format!(
+                        "embedded overlay in {} bits allocated is not supported",
+                        bits_allocated
+                    )
+                    .into())
+                }
+            };
+
+            result.push(sample >> bit_position & 1 == 1);
+        }
+    }
+
+    Ok(result)
+}
+
+// overlay在第frame_index帧图像（从0开始）上的显示范围，按照Overlay Origin对齐到rows x columns的图像上
+// 这一帧图像没有对应的overlay帧时返回None
+pub fn overlay_mask(
+    overlay: &Overlay,
+    frame_index: usize,
+    rows: u16,
+    columns: u16,
+) -> Option<Vec<bool>> {
+    let overlay_frame = (frame_index + 1).checked_sub(overlay.image_frame_origin as usize)?;
+
+    if overlay_frame >= overlay.number_of_frames as usize {
+        return None;
+    }
+
+    let point_count = overlay.rows as usize * overlay.columns as usize;
+    let data = overlay
+        .data
+        .get(overlay_frame * point_count..(overlay_frame + 1) * point_count)?;
+
+    let mut result = vec![false; rows as usize * columns as usize];
+
+    for row in 0..overlay.rows as i64 {
+        let image_row = row + overlay.origin.0 as i64 - 1;
+
+        if image_row < 0 || image_row >= rows as i64 {
+            continue;
+        }
+
+        for column in 0..overlay.columns as i64 {
+            let image_column = column + overlay.origin.1 as i64 - 1;
+
+            if image_column < 0 || image_column >= columns as i64 {
+                continue;
+            }
+
+            if data[(row * overlay.columns as i64 + column) as usize] {
+                result[(image_row * columns as i64 + image_column) as usize] = true;
+            }
+        }
+    }
+
+    Some(result)
+}
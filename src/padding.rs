@@ -0,0 +1,60 @@
+use crate::{
+    accessor,
+    model::{DataElement, PixelPadding},
+    CommonResult,
+};
+
+// 读取Pixel Padding Value和Pixel Padding Range Limit，没有Pixel Padding Value时返回None
+// 这两个tag的VR是US或SS，和Pixel Representation一致，这里按照Pixel Representation重新解释
+pub fn get_pixel_padding(
+    data_elements: &[DataElement],
+    pixel_representation: u16,
+) -> CommonResult<Option<PixelPadding>> {
+    if !accessor::has_value(data_elements, "0028,0120") {
+        return Ok(None);
+    }
+
+    let read = |tag: &str| -> CommonResult<i64> {
+        let value = accessor::get_i64(data_elements, tag)?;
+
+        Ok(if pixel_representation == 1 {
+            value as u16 as i16 as i64
+        } else {
+            value as u16 as i64
+        })
+    };
+
+    let value = read("0028,0120")?;
+
+    let limit = if accessor::has_value(data_elements, "0028,0121") {
+        read("0028,0121")?
+    } else {
+        value
+    };
+
+    Ok(Some(PixelPadding {
+        lower: value.min(limit),
+        upper: value.max(limit),
+    }))
+}
+
+pub fn is_padding(padding: &PixelPadding, value: i64) -> bool {
+    value >= padding.lower && value <= padding.upper
+}
+
+// 每个像素是否是填充像素，values是存储值（Modality LUT之前）
+pub fn padding_mask(padding: &PixelPadding, values: &[i64]) -> Vec<bool> {
+    values.iter().map(|v| is_padding(padding, *v)).collect()
+}
+
+// 去掉填充像素之后的存储值，用来计算最大最小值和自动窗宽窗位
+pub fn without_padding(padding: Option<&PixelPadding>, values: &[i64]) -> Vec<i64> {
+    match padding {
+        Some(padding) => values
+            .iter()
+            .copied()
+            .filter(|v| !is_padding(padding, *v))
+            .collect(),
+        None => values.to_vec(),
+    }
+}
@@ -0,0 +1,459 @@
+use crate::{
+    accessor,
+    model::{DataElement, DicomValue, PixelDescription, TransferSyntax},
+    CommonResult,
+};
+
+// 读取Image Pixel模块中的属性，可选的属性缺失时使用标准中的默认值
+pub fn get_pixel_description(data_elements: &[DataElement]) -> CommonResult<PixelDescription> {
+    let rows = accessor::get_u16(data_elements, "0028,0010")?;
+    let columns = accessor::get_u16(data_elements, "0028,0011")?;
+    let bits_allocated = accessor::get_u16(data_elements, "0028,0100")?;
+    let photometric_interpretation = accessor::get_str(data_elements, "0028,0004")?;
+
+    let samples_per_pixel = optional_u16(data_elements, "0028,0002")?.unwrap_or(1);
+    let bits_stored = optional_u16(data_elements, "0028,0101")?.unwrap_or(bits_allocated);
+    let high_bit =
+        optional_u16(data_elements, "0028,0102")?.unwrap_or(bits_stored.saturating_sub(1));
+    let pixel_representation = optional_u16(data_elements, "0028,0103")?.unwrap_or(0);
+    let planar_configuration = optional_u16(data_elements, "0028,0006")?.unwrap_or(0);
+
+    // Number of Frames是IS，单帧图像中通常不存在
+    let number_of_frames = if accessor::has_value(data_elements, "0028,0008") {
+        accessor::get_u32(data_elements, "0028,0008")?
+    } else {
+        1
+    };
+
+    if rows == 0 || columns == 0 || samples_per_pixel == 0 || number_of_frames == 0 {
+        return Err(format!(
+            "invalid image size: {} x {} x {} samples, {} frames",
+            rows, columns, samples_per_pixel, number_of_frames
+        )
+        .into());
+    }
+
+    if bits_allocated == 0 || bits_stored == 0 || bits_stored > bits_allocated {
+        return Err(format!(
+            "invalid bits allocated {} / bits stored {}",
+            bits_allocated, bits_stored
+        )
+        .into());
+    }
+
+    Ok(PixelDescription {
+        rows,
+        columns,
+        samples_per_pixel,
+        bits_allocated,
+        bits_stored,
+        high_bit,
+        pixel_representation,
+        planar_configuration,
+        number_of_frames,
+        photometric_interpretation,
+    })
+}
+
+fn optional_u16(data_elements: &[DataElement], tag: &str) -> CommonResult<Option<u16>> {
+    if accessor::has_value(data_elements, tag) {
+        Ok(Some(accessor::get_u16(data_elements, tag)?))
+    } else {
+        Ok(None)
+    }
+}
+
+// 一帧中的像素个数
+pub fn pixel_count(description: &PixelDescription) -> usize {
+    description.rows as usize * description.columns as usize
+}
+
+// YBR_FULL_422等水平降采样的格式在非压缩时每两个像素共用一组Cb、Cr
+pub fn is_subsampled_422(description: &PixelDescription) -> bool {
+    description.samples_per_pixel == 3
+        && matches!(
+            description.photometric_interpretation.as_str(),
+            "YBR_FULL_422" | "YBR_PARTIAL_422"
+        )
+}
+
+// 非压缩格式下一帧所占的位数
+pub fn frame_bits(description: &PixelDescription) -> usize {
+    // 降采样的格式中每个像素平均只有2个样本（Y Y Cb Cr）
+    let samples_per_pixel = if is_subsampled_422(description) {
+        2
+    } else {
+        description.samples_per_pixel as usize
+    };
+
+    pixel_count(description) * samples_per_pixel * description.bits_allocated as usize
+}
+
+// 非压缩格式下一帧所占的字节数
+pub fn frame_length(description: &PixelDescription) -> usize {
+    frame_bits(description).div_ceil(8)
+}
+
+// 读取单样本图像一帧中每个像素的值
+// 每个样本占bits_allocated位，其中有效的bits_stored位的最高位在high_bit
+// pixel_representation为1时有效位是补码，需要在bits_stored处做符号扩展
+// 参考PS3.5 8.1.1以及Annex D
+pub fn frame_to_values(frame: &[u8], description: &PixelDescription) -> CommonResult<Vec<i64>> {
+    let pixel_count = pixel_count(description);
+
+    if description.samples_per_pixel != 1 {
+        return Err(format!(
+            "{} samples per pixel can not be read as single values",
+            description.samples_per_pixel
+        )
+        .into());
+    }
+
+    if frame.len() < frame_length(description) {
+        return Err(format!(
+            "frame has {} bytes, expected {}",
+            frame.len(),
+            frame_length(description)
+        )
+        .into());
+    }
+
+    let bits_allocated = description.bits_allocated;
+    let bits_stored = description.bits_stored;
+    let high_bit = description.high_bit;
+
+    if high_bit >= bits_allocated || high_bit + 1 < bits_stored {
+        return Err(format!(
+            "high bit {} does not fit bits allocated {} / bits stored {}",
+            high_bit, bits_allocated, bits_stored
+        )
+        .into());
+    }
+
+    // 有效位右对齐之后再取低bits_stored位
+    let shift = high_bit + 1 - bits_stored;
+    let mask = (1_i64 << bits_stored) - 1;
+    let sign_bit = 1_i64 << (bits_stored - 1);
+    let signed = description.pixel_representation == 1 && bits_stored > 1;
+
+    let sample = |index: usize| -> i64 {
+        match bits_allocated {
+            // 1位的数据按位紧密排列，每个字节中的第一个像素在最低位
+            1 => (frame[index / 8] >> (index % 8)) as i64 & 1,
+            8 => frame[index] as i64,
+            16 => u16::from_le_bytes([frame[index * 2], frame[index * 2 + 1]]) as i64,
+            _ => u32::from_le_bytes([
+                frame[index * 4],
+                frame[index * 4 + 1],
+                frame[index * 4 + 2],
+                frame[index * 4 + 3],
+            ]) as i64,
+        }
+    };
+
+    if !matches!(bits_allocated, 1 | 8 | 16 | 32) {
+        return Err(format!("bits allocated {} is not supported", bits_allocated).into());
+    }
+
+    Ok((0..pixel_count)
+        .map(|index| {
+            let value = (sample(index) >> shift) & mask;
+
+            if signed && value & sign_bit != 0 {
+                value - (1_i64 << bits_stored)
+            } else {
+                value
+            }
+        })
+        .collect())
+}
+
+// 把非压缩的像素数据按帧拆分
+pub fn native_frames(
+    pixel_datas: &[u8],
+    description: &PixelDescription,
+) -> CommonResult<Vec<Vec<u8>>> {
+    check_native_length(pixel_datas, description)?;
+
+    (0..description.number_of_frames as usize)
+        .map(|index| native_frame(pixel_datas, description, index))
+        .collect()
+}
+
+fn check_native_length(pixel_datas: &[u8], description: &PixelDescription) -> CommonResult<()> {
+    let bits = frame_bits(description) * description.number_of_frames as usize;
+
+    // 像素数据可能带有1字节的填充，只有不够时才报错
+    if pixel_datas.len() < bits.div_ceil(8) {
+        return Err(format!(
+            "pixel data has {} bytes, expected {} frames of {} bits",
+            pixel_datas.len(),
+            description.number_of_frames,
+            frame_bits(description)
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+// 取出非压缩像素数据中的第index帧
+// 1位的数据中帧与帧之间是紧密排列的，一帧不一定从字节的边界开始，需要按位重新对齐
+fn native_frame(
+    pixel_datas: &[u8],
+    description: &PixelDescription,
+    index: usize,
+) -> CommonResult<Vec<u8>> {
+    let frame_length = frame_length(description);
+
+    if description.bits_allocated != 1 {
+        let start = index * frame_length;
+
+        return Ok(pixel_datas[start..start + frame_length].to_vec());
+    }
+
+    let frame_bits = frame_bits(description);
+    let start_bit = index * frame_bits;
+
+    let mut frame = vec![0_u8; frame_length];
+
+    for bit in 0..frame_bits {
+        let source_bit = start_bit + bit;
+
+        if pixel_datas[source_bit / 8] >> (source_bit % 8) & 1 == 1 {
+            frame[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    Ok(frame)
+}
+
+// 把封装格式的fragment组合成帧
+// 参考PS3.5 A.4，一帧可以由多个fragment组成，但一个fragment不能跨帧
+pub fn encapsulated_frames(
+    fragments: &[Vec<u8>],
+    number_of_frames: u32,
+) -> CommonResult<Vec<Vec<u8>>> {
+    let (basic_offset_table, fragments) = fragments
+        .split_first()
+        .ok_or("encapsulated pixel data has no Basic Offset Table")?;
+
+    let number_of_frames = number_of_frames as usize;
+
+    if number_of_frames == 1 {
+        return Ok(vec![fragments.concat()]);
+    }
+
+    // Basic Offset Table中记录了每一帧的第一个fragment相对于第一个fragment的item tag的偏移
+    if !basic_offset_table.is_empty() {
+        let offsets = basic_offset_table
+            .chunks_exact(4)
+            .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]) as usize)
+            .collect::<Vec<usize>>();
+
+        if offsets.len() != number_of_frames {
+            return Err(format!(
+                "Basic Offset Table has {} entries, expected {}",
+                offsets.len(),
+                number_of_frames
+            )
+            .into());
+        }
+
+        let mut frames = vec![Vec::new(); number_of_frames];
+        let mut position = 0;
+
+        for fragment in fragments {
+            let index = offsets
+                .iter()
+                .rposition(|v| *v <= position)
+                .ok_or("Basic Offset Table does not start at the first fragment")?;
+
+            frames[index].extend_from_slice(fragment);
+
+            // 每个fragment前面还有8字节的item tag和长度
+            position += 8 + fragment.len();
+        }
+
+        return Ok(frames);
+    }
+
+    if fragments.len() == number_of_frames {
+        return Ok(fragments.to_vec());
+    }
+
+    // 没有Basic Offset Table并且一帧由多个fragment组成时，JPEG系列可以根据SOI标记(FFD8)判断帧的开始
+    let mut frames: Vec<Vec<u8>> = Vec::new();
+
+    for fragment in fragments {
+        if fragment.starts_with(&[0xff, 0xd8]) || frames.is_empty() {
+            frames.push(Vec::new());
+        }
+
+        if let Some(frame) = frames.last_mut() {
+            frame.extend_from_slice(fragment);
+        }
+    }
+
+    if frames.len() != number_of_frames {
+        return Err(format!(
+            "can not split {} fragments into {} frames",
+            fragments.len(),
+            number_of_frames
+        )
+        .into());
+    }
+
+    Ok(frames)
+}
+
+// 每一帧作为一个fragment封装，并生成Basic Offset Table
+pub fn encapsulate_frames(frames: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut basic_offset_table = Vec::new();
+    let mut fragments = vec![];
+    let mut position = 0;
+
+    for frame in frames {
+        basic_offset_table.extend_from_slice(&(position as u32).to_le_bytes());
+
+        let mut fragment = frame.clone();
+
+        // fragment的长度必须是偶数
+        if !fragment.len().is_multiple_of(2) {
+            fragment.push(0x00);
+        }
+
+        position += 8 + fragment.len();
+        fragments.push(fragment);
+    }
+
+    // 单帧时Basic Offset Table可以为空
+    if frames.len() == 1 {
+        basic_offset_table.clear();
+    }
+
+    let mut result = vec![basic_offset_table];
+    result.extend(fragments);
+
+    result
+}
+
+// 数据集中像素数据所使用的传输语法，没有文件元信息时按照非压缩处理
+fn get_transfer_syntax(data_elements: &[DataElement]) -> CommonResult<TransferSyntax> {
+    if !accessor::has_value(data_elements, "0002,0010") {
+        return Ok(TransferSyntax::ExplicitVrLittleEndian);
+    }
+
+    let uid = accessor::get_str(data_elements, "0002,0010")?;
+
+    TransferSyntax::from_uid(&uid)
+        .ok_or_else(|| format!("transfer syntax {} is not supported", uid).into())
+}
+
+enum FrameSource<'a> {
+    // 整个非压缩的像素数据，取某一帧时再按照偏移截取
+    Native(&'a [u8]),
+    // 每一帧压缩后的数据
+    Encapsulated(Vec<Vec<u8>>),
+}
+
+// 逐帧返回解码后的非压缩像素数据（小端，封装格式解码后按像素交错存储）
+// 压缩的帧只有在迭代到时才会解码
+pub struct FrameIter<'a> {
+    description: PixelDescription,
+    transfer_syntax: TransferSyntax,
+    source: FrameSource<'a>,
+    index: usize,
+}
+
+impl FrameIter<'_> {
+    pub fn description(&self) -> &PixelDescription {
+        &self.description
+    }
+
+    pub fn len(&self) -> usize {
+        self.description.number_of_frames as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // 解码第index帧（从0开始），不影响迭代的位置
+    pub fn frame(&self, index: usize) -> CommonResult<Vec<u8>> {
+        if index >= self.len() {
+            return Err(format!("frame {} is out of range (0..{})", index, self.len()).into());
+        }
+
+        match &self.source {
+            FrameSource::Native(pixel_datas) => native_frame(pixel_datas, &self.description, index),
+            FrameSource::Encapsulated(frames) => {
+                crate::codec::decode_frame(self.transfer_syntax, &frames[index], &self.description)
+            }
+        }
+    }
+}
+
+impl Iterator for FrameIter<'_> {
+    type Item = CommonResult<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // 这里不能用self.len()，&mut Self上会解析成ExactSizeIterator::len，得到的是剩余的帧数
+        if self.index >= self.description.number_of_frames as usize {
+            return None;
+        }
+
+        let result = self.frame(self.index);
+        self.index += 1;
+
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.description.number_of_frames as usize - self.index;
+
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for FrameIter<'_> {}
+
+// 按帧遍历(7FE0,0010)中的像素数据，Number of Frames (0028,0008)缺失时视为单帧
+pub fn frames(data_elements: &[DataElement]) -> CommonResult<FrameIter<'_>> {
+    let transfer_syntax = get_transfer_syntax(data_elements)?;
+    let description = get_pixel_description(data_elements)?;
+
+    let source = match &accessor::find_data_element(data_elements, "7FE0,0010")?.data {
+        DicomValue::Bytes(pixel_datas) if !transfer_syntax.is_encapsulated() => {
+            check_native_length(pixel_datas, &description)?;
+
+            FrameSource::Native(pixel_datas)
+        }
+        DicomValue::Fragments(fragments) if transfer_syntax.is_encapsulated() => {
+            FrameSource::Encapsulated(encapsulated_frames(
+                fragments,
+                description.number_of_frames,
+            )?)
+        }
+        _ => {
+            return Err(format!(
+                "pixel data does not match transfer syntax {:?}",
+                transfer_syntax
+            )
+            .into())
+        }
+    };
+
+    Ok(FrameIter {
+        // 迭代得到的是解码后的帧，描述也要与之一致
+        description: crate::codec::decoded_description(transfer_syntax, &description),
+        transfer_syntax,
+        source,
+        index: 0,
+    })
+}
+
+// 取出第index帧（从0开始）解码后的像素数据
+pub fn get_frame(data_elements: &[DataElement], index: usize) -> CommonResult<Vec<u8>> {
+    frames(data_elements)?.frame(index)
+}
@@ -0,0 +1,624 @@
+use image::{DynamicImage, Rgb, Rgba};
+
+use crate::{
+    accessor,
+    model::{
+        AnnotationUnits, DataElement, DisplayedArea, GraphicAnnotation, GraphicObject, GraphicType,
+        OutputBitDepth, PresentationState, RenderOptions, TextObject, VoiTransform,
+    },
+    CommonResult,
+};
+
+// Grayscale Softcopy Presentation State，参考PS3.3 A.33.1和PS3.4 N.2
+// 显示流程：Modality LUT -> VOI LUT -> Presentation LUT -> Shutter -> Displayed Area -> Spatial Transformation -> Annotation
+
+// 注释的默认颜色
+const DEFAULT_ANNOTATION_COLOR: [u8; 3] = [255, 255, 255];
+
+fn optional_str(data_elements: &[DataElement], tag: &str) -> CommonResult<Option<String>> {
+    if accessor::has_value(data_elements, tag) {
+        Ok(Some(accessor::get_str(data_elements, tag)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn optional_items<'a>(
+    data_elements: &'a [DataElement],
+    tag: &str,
+) -> CommonResult<&'a [Vec<DataElement>]> {
+    if accessor::has_value(data_elements, tag) {
+        accessor::get_items(data_elements, tag)
+    } else {
+        Ok(&[])
+    }
+}
+
+// item中的Referenced Image Sequence (0008,1140)是否包含这幅图像的这一帧（从1开始）
+// 没有Referenced Image Sequence时适用于所有的图像
+fn applies_to(
+    item: &[DataElement],
+    sop_instance_uid: Option<&str>,
+    frame_number: i64,
+) -> CommonResult<bool> {
+    if !accessor::has_value(item, "0008,1140") {
+        return Ok(true);
+    }
+
+    let sop_instance_uid = match sop_instance_uid {
+        Some(v) => v,
+        None => return Ok(true),
+    };
+
+    for reference in accessor::get_items(item, "0008,1140")? {
+        if accessor::get_str(reference, "0008,1155")? != sop_instance_uid {
+            continue;
+        }
+
+        if !accessor::has_value(reference, "0008,1160")
+            || accessor::get_i64s(reference, "0008,1160")?.contains(&frame_number)
+        {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn parse_units(value: &str) -> CommonResult<AnnotationUnits> {
+    match value {
+        "PIXEL" => Ok(AnnotationUnits::Pixel),
+        "DISPLAY" => Ok(AnnotationUnits::Display),
+        _ => Err(format!("annotation units {} is not supported", value).into()),
+    }
+}
+
+fn point(data_elements: &[DataElement], tag: &str) -> CommonResult<Option<(f64, f64)>> {
+    if !accessor::has_value(data_elements, tag) {
+        return Ok(None);
+    }
+
+    match accessor::get_f64s(data_elements, tag)?[..] {
+        [x, y] => Ok(Some((x, y))),
+        ref values => Err(format!("{} has VM {}, expected 2", tag, values.len()).into()),
+    }
+}
+
+fn parse_graphic_object(item: &[DataElement]) -> CommonResult<GraphicObject> {
+    let graphic_type = match accessor::get_str(item, "0070,0023")?.as_str() {
+        "POINT" => GraphicType::Point,
+        "POLYLINE" => GraphicType::Polyline,
+        "INTERPOLATED" => GraphicType::Interpolated,
+        "CIRCLE" => GraphicType::Circle,
+        "ELLIPSE" => GraphicType::Ellipse,
+        value => return Err(format!("graphic type {} is not supported", value).into()),
+    };
+
+    let data = accessor::get_f64s(item, "0070,0022")?;
+
+    if !data.len().is_multiple_of(2) {
+        return Err(format!("0070,0022 has VM {}, expected pairs", data.len()).into());
+    }
+
+    Ok(GraphicObject {
+        units: parse_units(&accessor::get_str(item, "0070,0005")?)?,
+        graphic_type,
+        points: data.chunks(2).map(|v| (v[0], v[1])).collect(),
+        filled: optional_str(item, "0070,0024")?.as_deref() == Some("Y"),
+    })
+}
+
+fn parse_text_object(item: &[DataElement]) -> CommonResult<TextObject> {
+    let top_left = point(item, "0070,0010")?;
+    let bottom_right = point(item, "0070,0011")?;
+    let anchor_point = point(item, "0070,0014")?;
+
+    let bounding_box = top_left.zip(bottom_right);
+
+    if bounding_box.is_none() && anchor_point.is_none() {
+        return Err("text object has neither a bounding box nor an anchor point".into());
+    }
+
+    let units = |tag: &str| -> CommonResult<AnnotationUnits> {
+        match optional_str(item, tag)? {
+            Some(v) => parse_units(&v),
+            None => Ok(AnnotationUnits::Pixel),
+        }
+    };
+
+    Ok(TextObject {
+        text: optional_str(item, "0070,0006")?.unwrap_or_default(),
+        bounding_box,
+        bounding_box_units: units("0070,0003")?,
+        anchor_point,
+        anchor_point_units: units("0070,0004")?,
+        anchor_point_visible: optional_str(item, "0070,0015")?.as_deref() == Some("Y"),
+    })
+}
+
+// 读取presentation_state中适用于image第frame_index帧（从0开始）的显示参数
+pub fn get_presentation_state(
+    presentation_state: &[DataElement],
+    image: &[DataElement],
+    frame_index: usize,
+) -> CommonResult<PresentationState> {
+    let sop_instance_uid = optional_str(image, "0008,0018")?;
+    let sop_instance_uid = sop_instance_uid.as_deref();
+    let frame_number = frame_index as i64 + 1;
+
+    let applies = |item: &[DataElement]| applies_to(item, sop_instance_uid, frame_number);
+
+    // Referenced Series Sequence中必须有这幅图像
+    let series = optional_items(presentation_state, "0008,1115")?;
+    if !series.is_empty() {
+        let mut referenced = false;
+
+        for item in series {
+            referenced |= applies(item)?;
+        }
+
+        if !referenced {
+            return Err("presentation state does not reference this image".into());
+        }
+    }
+
+    let pixel_representation = crate::pixel::get_pixel_description(image)?.pixel_representation;
+
+    let modality = if accessor::has_value(presentation_state, "0028,3000")
+        || accessor::has_value(presentation_state, "0028,1053")
+    {
+        Some(crate::lut::get_modality_transform(
+            presentation_state,
+            pixel_representation,
+        )?)
+    } else {
+        None
+    };
+
+    let mut voi = None;
+    for item in optional_items(presentation_state, "0028,3110")? {
+        if applies(item)? {
+            voi = crate::lut::get_voi_transforms(item, pixel_representation)?
+                .into_iter()
+                .next();
+            break;
+        }
+    }
+
+    // Presentation LUT Sequence目前不支持，只处理Presentation LUT Shape
+    let invert = match optional_str(presentation_state, "2050,0020")?.as_deref() {
+        Some("INVERSE") => Some(true),
+        Some("IDENTITY") => Some(false),
+        _ => None,
+    };
+
+    let mut displayed_area = None;
+    for item in optional_items(presentation_state, "0070,005A")? {
+        if applies(item)? {
+            let top_left = point(item, "0070,0052")?.ok_or("0070,0052 is missing")?;
+            let bottom_right = point(item, "0070,0053")?.ok_or("0070,0053 is missing")?;
+
+            displayed_area = Some(DisplayedArea {
+                top_left: (top_left.0 as i64, top_left.1 as i64),
+                bottom_right: (bottom_right.0 as i64, bottom_right.1 as i64),
+                size_mode: optional_str(item, "0070,0100")?.unwrap_or("SCALE TO FIT".to_string()),
+                magnification: if accessor::has_value(item, "0070,0103") {
+                    Some(accessor::get_f64(item, "0070,0103")?)
+                } else {
+                    None
+                },
+            });
+            break;
+        }
+    }
+
+    let rotation = if accessor::has_value(presentation_state, "0070,0042") {
+        accessor::get_u16(presentation_state, "0070,0042")?
+    } else {
+        0
+    };
+
+    if !matches!(rotation, 0 | 90 | 180 | 270) {
+        return Err(format!("image rotation {} is not supported", rotation).into());
+    }
+
+    let mut annotations = Vec::new();
+    for item in optional_items(presentation_state, "0070,0001")? {
+        if !applies(item)? {
+            continue;
+        }
+
+        annotations.push(GraphicAnnotation {
+            layer: optional_str(item, "0070,0002")?.unwrap_or_default(),
+            graphics: optional_items(item, "0070,0009")?
+                .iter()
+                .map(|v| parse_graphic_object(v))
+                .collect::<CommonResult<Vec<GraphicObject>>>()?,
+            texts: optional_items(item, "0070,0008")?
+                .iter()
+                .map(|v| parse_text_object(v))
+                .collect::<CommonResult<Vec<TextObject>>>()?,
+        });
+    }
+
+    let mut layer_colors = Vec::new();
+    for item in optional_items(presentation_state, "0070,0060")? {
+        let layer = optional_str(item, "0070,0002")?.unwrap_or_default();
+
+        if accessor::has_value(item, "0070,0066") {
+            let value = (accessor::get_u16(item, "0070,0066")? >> 8) as u8;
+
+            layer_colors.push((layer, [value; 3]));
+        } else if accessor::has_value(item, "0070,0401") {
+            let lab = accessor::get_u16s(item, "0070,0401")?;
+
+            if let [l, a, b] = lab[..] {
+                layer_colors.push((layer, crate::color::cielab_to_rgb([l, a, b])));
+            }
+        }
+    }
+
+    Ok(PresentationState {
+        modality,
+        voi,
+        invert,
+        display_shutter: crate::shutter::get_display_shutter(presentation_state)?,
+        displayed_area,
+        rotation,
+        horizontal_flip: optional_str(presentation_state, "0070,0041")?.as_deref() == Some("Y"),
+        annotations,
+        layer_colors,
+    })
+}
+
+// 图像坐标（PIXEL，左上角为0.0\0.0）到输出图像坐标的映射
+struct Mapping {
+    offset: (f64, f64),
+    // 显示区域的宽和高（旋转之前）
+    size: (f64, f64),
+    horizontal_flip: bool,
+    rotation: u16,
+    scale: f64,
+}
+
+impl Mapping {
+    fn map(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (width, height) = self.size;
+        let (mut x, y) = (x - self.offset.0, y - self.offset.1);
+
+        if self.horizontal_flip {
+            x = width - x;
+        }
+
+        let (x, y) = match self.rotation {
+            90 => (height - y, x),
+            180 => (width - x, height - y),
+            270 => (y, width - x),
+            _ => (x, y),
+        };
+
+        (x * self.scale, y * self.scale)
+    }
+}
+
+// 按照presentation_state渲染image，options中的window会覆盖presentation state中的VOI
+pub fn render(
+    image: &[DataElement],
+    presentation_state: &[DataElement],
+    options: &RenderOptions,
+) -> CommonResult<DynamicImage> {
+    let frames = crate::pixel::frames(image)?;
+    let description = frames.description().clone();
+
+    if description.samples_per_pixel != 1
+        || !description
+            .photometric_interpretation
+            .starts_with("MONOCHROME")
+    {
+        return Err(format!(
+            "grayscale presentation state can not be applied to {}",
+            description.photometric_interpretation
+        )
+        .into());
+    }
+
+    let state = get_presentation_state(presentation_state, image, options.frame_index)?;
+    let pixel_datas = frames.frame(options.frame_index)?;
+    let pixels = crate::pixel::frame_to_values(&pixel_datas, &description)?;
+
+    let modality_transform = match &state.modality {
+        Some(v) => v.clone(),
+        None => crate::lut::get_modality_transform(image, description.pixel_representation)?,
+    };
+
+    let voi_transform = match (&options.window, &state.voi) {
+        (Some(window), _) => VoiTransform::Window(window.clone()),
+        (None, Some(voi)) => voi.clone(),
+        (None, None) => crate::render::select_voi_transform(
+            image,
+            description.pixel_representation,
+            &pixels,
+            &modality_transform,
+            options,
+        )?,
+    };
+
+    let invert = state
+        .invert
+        .unwrap_or(description.photometric_interpretation == "MONOCHROME1")
+        != options.invert;
+
+    let processed_pixels =
+        crate::lut::apply_grayscale_pipeline(&pixels, &modality_transform, &voi_transform, invert);
+
+    // 和render_frame相同，先处理填充像素，再加shutter和overlay
+    let output = crate::render::apply_pixel_padding(
+        image,
+        &description,
+        &pixel_datas,
+        crate::render::grayscale_to_image(
+            description.columns as u32,
+            description.rows as u32,
+            &processed_pixels,
+            options.bit_depth,
+        )?,
+        options,
+    )?;
+
+    let output = crate::render::apply_display_shutter(output, &state.display_shutter);
+
+    let mut output =
+        crate::render::apply_overlays(image, &description, options.frame_index, output, options)?;
+
+    // 显示区域之外的部分填充黑色
+    let (offset, size) = match &state.displayed_area {
+        Some(area) => {
+            let width = (area.bottom_right.0 - area.top_left.0 + 1).max(1);
+            let height = (area.bottom_right.1 - area.top_left.1 + 1).max(1);
+
+            let mut canvas = DynamicImage::new(width as u32, height as u32, output.color());
+            image::imageops::overlay(
+                &mut canvas,
+                &output,
+                1 - area.top_left.0,
+                1 - area.top_left.1,
+            );
+            output = canvas;
+
+            (
+                ((area.top_left.0 - 1) as f64, (area.top_left.1 - 1) as f64),
+                (width as f64, height as f64),
+            )
+        }
+        None => (
+            (0.0, 0.0),
+            (description.columns as f64, description.rows as f64),
+        ),
+    };
+
+    if state.horizontal_flip {
+        output = output.fliph();
+    }
+
+    output = match state.rotation {
+        90 => output.rotate90(),
+        180 => output.rotate180(),
+        270 => output.rotate270(),
+        _ => output,
+    };
+
+    let scale = match &state.displayed_area {
+        Some(DisplayedArea {
+            size_mode,
+            magnification: Some(magnification),
+            ..
+        }) if size_mode == "MAGNIFY" && *magnification > 0.0 => *magnification,
+        _ => 1.0,
+    };
+
+    if scale != 1.0 {
+        output = crate::render::resize(output, crate::model::RenderSize::Scale(scale));
+    }
+
+    let mapping = Mapping {
+        offset,
+        size,
+        horizontal_flip: state.horizontal_flip,
+        rotation: state.rotation,
+        scale,
+    };
+
+    if !state.annotations.is_empty() {
+        output = draw_annotations(output, &state, &mapping, options.bit_depth);
+    }
+
+    Ok(crate::render::resize(output, options.size))
+}
+
+fn draw_annotations(
+    image: DynamicImage,
+    state: &PresentationState,
+    mapping: &Mapping,
+    bit_depth: OutputBitDepth,
+) -> DynamicImage {
+    let width = image.width() as f64;
+    let height = image.height() as f64;
+
+    // DISPLAY是相对于显示区域的0.0~1.0
+    let to_output = |units: AnnotationUnits, point: (f64, f64)| match units {
+        AnnotationUnits::Pixel => mapping.map(point),
+        AnnotationUnits::Display => (point.0 * width, point.1 * height),
+    };
+
+    let mut points: Vec<(i64, i64, [u8; 3])> = Vec::new();
+
+    for annotation in &state.annotations {
+        let color = state
+            .layer_colors
+            .iter()
+            .find(|(layer, _)| *layer == annotation.layer)
+            .map(|(_, color)| *color)
+            .unwrap_or(DEFAULT_ANNOTATION_COLOR);
+
+        let mut plot = |x: i64, y: i64| points.push((x, y, color));
+
+        for graphic in &annotation.graphics {
+            let graphic_points = graphic
+                .points
+                .iter()
+                .map(|v| to_output(graphic.units, *v))
+                .collect::<Vec<(f64, f64)>>();
+
+            draw_graphic(graphic, &graphic_points, &mut plot);
+        }
+
+        for text in &annotation.texts {
+            draw_text(text, &to_output, &mut plot);
+        }
+    }
+
+    let inside = |x: i64, y: i64| x >= 0 && y >= 0 && (x as f64) < width && (y as f64) < height;
+
+    // 透明的填充像素保留alpha通道
+    if image.color().has_alpha() {
+        return match bit_depth {
+            OutputBitDepth::Eight => {
+                let mut image = image.into_rgba8();
+
+                for (x, y, color) in points {
+                    if inside(x, y) {
+                        let [r, g, b] = color;
+                        image.put_pixel(x as u32, y as u32, Rgba([r, g, b, u8::MAX]));
+                    }
+                }
+
+                DynamicImage::ImageRgba8(image)
+            }
+            OutputBitDepth::Sixteen => {
+                let mut image = image.into_rgba16();
+
+                for (x, y, color) in points {
+                    if inside(x, y) {
+                        let [r, g, b] = color.map(|v| v as u16 * 257);
+                        image.put_pixel(x as u32, y as u32, Rgba([r, g, b, u16::MAX]));
+                    }
+                }
+
+                DynamicImage::ImageRgba16(image)
+            }
+        };
+    }
+
+    match bit_depth {
+        OutputBitDepth::Eight => {
+            let mut image = image.into_rgb8();
+
+            for (x, y, color) in points {
+                if inside(x, y) {
+                    image.put_pixel(x as u32, y as u32, Rgb(color));
+                }
+            }
+
+            DynamicImage::ImageRgb8(image)
+        }
+        OutputBitDepth::Sixteen => {
+            let mut image = image.into_rgb16();
+
+            for (x, y, color) in points {
+                if inside(x, y) {
+                    image.put_pixel(x as u32, y as u32, Rgb(color.map(|v| v as u16 * 257)));
+                }
+            }
+
+            DynamicImage::ImageRgb16(image)
+        }
+    }
+}
+
+fn draw_graphic(graphic: &GraphicObject, points: &[(f64, f64)], plot: &mut dyn FnMut(i64, i64)) {
+    let outline = |polygon: &[(f64, f64)], plot: &mut dyn FnMut(i64, i64)| {
+        if graphic.filled {
+            crate::draw::fill_polygon(polygon, plot);
+        } else {
+            let mut closed = polygon.to_vec();
+            closed.extend(polygon.first());
+            crate::draw::polyline(&closed, plot);
+        }
+    };
+
+    match graphic.graphic_type {
+        GraphicType::Point => {
+            // 每个点画成一个小十字
+            for point in points {
+                crate::draw::line((point.0 - 2.0, point.1), (point.0 + 2.0, point.1), plot);
+                crate::draw::line((point.0, point.1 - 2.0), (point.0, point.1 + 2.0), plot);
+            }
+        }
+        GraphicType::Polyline | GraphicType::Interpolated => {
+            if graphic.filled {
+                crate::draw::fill_polygon(points, plot);
+            } else {
+                crate::draw::polyline(points, plot);
+            }
+        }
+        GraphicType::Circle => {
+            if let [center, edge, ..] = points {
+                let radius = (edge.0 - center.0).hypot(edge.1 - center.1);
+
+                outline(
+                    &crate::draw::ellipse_points(*center, (radius, 0.0), (0.0, radius)),
+                    plot,
+                );
+            }
+        }
+        GraphicType::Ellipse => {
+            if let [major_a, major_b, minor_a, minor_b, ..] = points {
+                let center = ((major_a.0 + major_b.0) / 2.0, (major_a.1 + major_b.1) / 2.0);
+
+                outline(
+                    &crate::draw::ellipse_points(
+                        center,
+                        ((major_b.0 - major_a.0) / 2.0, (major_b.1 - major_a.1) / 2.0),
+                        ((minor_b.0 - minor_a.0) / 2.0, (minor_b.1 - minor_a.1) / 2.0),
+                    ),
+                    plot,
+                );
+            }
+        }
+    }
+}
+
+// 有bounding box时文字画在box内，否则画在anchor point的右下方
+// anchor point可见时从anchor point画一条线连到文字
+fn draw_text(
+    text: &TextObject,
+    to_output: &dyn Fn(AnnotationUnits, (f64, f64)) -> (f64, f64),
+    plot: &mut dyn FnMut(i64, i64),
+) {
+    let anchor = text
+        .anchor_point
+        .map(|v| to_output(text.anchor_point_units, v));
+
+    let (left, top, max_width) = match (text.bounding_box, anchor) {
+        (Some((top_left, bottom_right)), _) => {
+            let a = to_output(text.bounding_box_units, top_left);
+            let b = to_output(text.bounding_box_units, bottom_right);
+
+            (a.0.min(b.0), a.1.min(b.1), Some((a.0 - b.0).abs() as i64))
+        }
+        (None, Some(anchor)) => (anchor.0 + 4.0, anchor.1 + 4.0, None),
+        (None, None) => return,
+    };
+
+    crate::draw::text(
+        &text.text,
+        left.floor() as i64,
+        top.floor() as i64,
+        max_width,
+        plot,
+    );
+
+    if let (Some(anchor), true) = (anchor, text.anchor_point_visible) {
+        crate::draw::line(anchor, (left, top), plot);
+    }
+}
@@ -0,0 +1,530 @@
+use std::io::{Cursor, Seek, Write};
+
+use image::{imageops::FilterType, DynamicImage, ImageBuffer, Luma, Rgb};
+
+use crate::{
+    model::{
+        DataElement, DisplayShutter, OutputBitDepth, PaddingDisplay, PixelDescription,
+        RenderOptions, RenderSize, VoiTransform,
+    },
+    CommonResult,
+};
+
+// 按照options渲染一帧，返回内存中的图像
+pub fn render(
+    data_elements: &[DataElement],
+    options: &RenderOptions,
+) -> CommonResult<DynamicImage> {
+    let frames = crate::pixel::frames(data_elements)?;
+
+    let frame = frames.frame(options.frame_index)?;
+
+    render_frame(
+        data_elements,
+        frames.description(),
+        options.frame_index,
+        &frame,
+        options,
+    )
+}
+
+// 渲染并保存到file_path，格式由options.format决定，与扩展名无关
+pub fn render_to_file<P: AsRef<std::path::Path>>(
+    data_elements: &[DataElement],
+    file_path: P,
+    options: &RenderOptions,
+) -> CommonResult<()> {
+    render(data_elements, options)?.save_with_format(file_path, options.format)?;
+
+    Ok(())
+}
+
+// 渲染并编码到任意的writer，比如网络服务的响应体
+pub fn render_to_writer<W: Write + Seek>(
+    data_elements: &[DataElement],
+    writer: &mut W,
+    options: &RenderOptions,
+) -> CommonResult<()> {
+    render(data_elements, options)?.write_to(writer, options.format)?;
+
+    Ok(())
+}
+
+// 渲染并编码成字节数组
+pub fn render_to_bytes(
+    data_elements: &[DataElement],
+    options: &RenderOptions,
+) -> CommonResult<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+
+    render_to_writer(data_elements, &mut cursor, options)?;
+
+    Ok(cursor.into_inner())
+}
+
+// 渲染第frame_index帧已经解码的像素数据（FrameIter返回的帧），options中的frame_index不起作用
+// frame_index用来找到这一帧对应的overlay
+pub fn render_frame(
+    data_elements: &[DataElement],
+    description: &PixelDescription,
+    frame_index: usize,
+    pixel_datas: &[u8],
+    options: &RenderOptions,
+) -> CommonResult<DynamicImage> {
+    let photometric_interpretation = &description.photometric_interpretation;
+
+    let image = if photometric_interpretation == "PALETTE COLOR" {
+        // 伪彩色图像按照Palette Color LUT转换成RGB输出
+        let values = crate::pixel::frame_to_values(pixel_datas, description)?;
+        let luts =
+            crate::color::get_palette_color_luts(data_elements, description.pixel_representation)?;
+
+        rgb_image(
+            description,
+            crate::color::palette_to_rgb(&values, &luts),
+            options,
+        )?
+    } else if description.samples_per_pixel == 3 {
+        // 彩色图像不需要窗宽窗位，直接转换成RGB输出
+        rgb_image(
+            description,
+            crate::color::frame_to_rgb(pixel_datas, description)?,
+            options,
+        )?
+    } else if photometric_interpretation == "MONOCHROME2"
+        || photometric_interpretation == "MONOCHROME1"
+    {
+        grayscale_image(data_elements, description, pixel_datas, options)?
+    } else {
+        return Err(format!(
+            "photometric interpretation {} is not supported",
+            photometric_interpretation
+        )
+        .into());
+    };
+
+    // 填充像素只处理灰度图像本身，先于shutter和overlay，不会覆盖shutter的颜色和overlay
+    let image = apply_pixel_padding(data_elements, description, pixel_datas, image, options)?;
+
+    let image = apply_display_shutter(image, &crate::shutter::get_display_shutter(data_elements)?);
+
+    let image = apply_overlays(data_elements, description, frame_index, image, options)?;
+
+    Ok(resize(image, options.size))
+}
+
+// 设置了options.overlay_color时把overlay画到图像上，render_frame和presentation::render共用
+pub fn apply_overlays(
+    data_elements: &[DataElement],
+    description: &PixelDescription,
+    frame_index: usize,
+    image: DynamicImage,
+    options: &RenderOptions,
+) -> CommonResult<DynamicImage> {
+    match options.overlay_color {
+        Some(color) => burn_overlays(data_elements, description, frame_index, image, color),
+        None => Ok(image),
+    }
+}
+
+// 把这一帧的所有overlay按照color画到图像上，灰度图像会转换成RGB
+pub fn burn_overlays(
+    data_elements: &[DataElement],
+    description: &PixelDescription,
+    frame_index: usize,
+    image: DynamicImage,
+    color: [u8; 3],
+) -> CommonResult<DynamicImage> {
+    let masks = crate::overlay::get_overlays(data_elements)?
+        .iter()
+        .filter_map(|overlay| {
+            crate::overlay::overlay_mask(
+                overlay,
+                frame_index,
+                description.rows,
+                description.columns,
+            )
+        })
+        .collect::<Vec<Vec<bool>>>();
+
+    if masks.is_empty() {
+        return Ok(image);
+    }
+
+    let visible = |index: usize| masks.iter().any(|mask| mask[index]);
+
+    let image = match image {
+        DynamicImage::ImageLuma16(_) | DynamicImage::ImageRgb16(_) => {
+            let mut image = image.into_rgb16();
+            let color = Rgb(color.map(|v| v as u16 * 257));
+
+            for (index, pixel) in image.pixels_mut().enumerate() {
+                if visible(index) {
+                    *pixel = color;
+                }
+            }
+
+            DynamicImage::ImageRgb16(image)
+        }
+        _ => {
+            let mut image = image.into_rgb8();
+
+            for (index, pixel) in image.pixels_mut().enumerate() {
+                if visible(index) {
+                    *pixel = Rgb(color);
+                }
+            }
+
+            DynamicImage::ImageRgb8(image)
+        }
+    };
+
+    Ok(image)
+}
+
+// 被shutter遮住的像素按照Shutter Presentation Value（灰度）或者CIELab颜色显示
+// 有颜色时灰度图像会转换成RGB
+pub fn apply_display_shutter(
+    image: DynamicImage,
+    display_shutter: &DisplayShutter,
+) -> DynamicImage {
+    if display_shutter.shutters.is_empty() {
+        return image;
+    }
+
+    let mask = crate::shutter::shutter_mask(
+        &display_shutter.shutters,
+        image.height() as u16,
+        image.width() as u16,
+    );
+
+    let gray = display_shutter.presentation_value;
+
+    match (image, display_shutter.presentation_color) {
+        (DynamicImage::ImageLuma8(mut image), None) => {
+            for (pixel, visible) in image.pixels_mut().zip(mask) {
+                if !visible {
+                    *pixel = Luma([(gray >> 8) as u8]);
+                }
+            }
+
+            DynamicImage::ImageLuma8(image)
+        }
+        (DynamicImage::ImageLuma16(mut image), None) => {
+            for (pixel, visible) in image.pixels_mut().zip(mask) {
+                if !visible {
+                    *pixel = Luma([gray]);
+                }
+            }
+
+            DynamicImage::ImageLuma16(image)
+        }
+        (image @ (DynamicImage::ImageLuma16(_) | DynamicImage::ImageRgb16(_)), color) => {
+            let mut image = image.into_rgb16();
+            let color = Rgb(color.map_or([gray; 3], |v| v.map(|v| v as u16 * 257)));
+
+            for (pixel, visible) in image.pixels_mut().zip(mask) {
+                if !visible {
+                    *pixel = color;
+                }
+            }
+
+            DynamicImage::ImageRgb16(image)
+        }
+        (image, color) => {
+            let mut image = image.into_rgb8();
+            let color = Rgb(color.unwrap_or([(gray >> 8) as u8; 3]));
+
+            for (pixel, visible) in image.pixels_mut().zip(mask) {
+                if !visible {
+                    *pixel = color;
+                }
+            }
+
+            DynamicImage::ImageRgb8(image)
+        }
+    }
+}
+
+// 按照options.padding显示填充像素，render_frame和presentation::render共用
+pub fn apply_pixel_padding(
+    data_elements: &[DataElement],
+    description: &PixelDescription,
+    pixel_datas: &[u8],
+    image: DynamicImage,
+    options: &RenderOptions,
+) -> CommonResult<DynamicImage> {
+    match get_padding_mask(data_elements, description, pixel_datas, options)? {
+        Some(mask) => Ok(apply_padding(image, &mask, options.padding)),
+        None => Ok(image),
+    }
+}
+
+// 灰度图像中填充像素的mask，不需要特殊显示或者没有Pixel Padding Value时返回None
+pub fn get_padding_mask(
+    data_elements: &[DataElement],
+    description: &PixelDescription,
+    pixel_datas: &[u8],
+    options: &RenderOptions,
+) -> CommonResult<Option<Vec<bool>>> {
+    if options.padding == PaddingDisplay::Unchanged
+        || description.samples_per_pixel != 1
+        || !description
+            .photometric_interpretation
+            .starts_with("MONOCHROME")
+    {
+        return Ok(None);
+    }
+
+    let padding =
+        crate::padding::get_pixel_padding(data_elements, description.pixel_representation)?;
+
+    match padding {
+        Some(padding) => Ok(Some(crate::padding::padding_mask(
+            &padding,
+            &crate::pixel::frame_to_values(pixel_datas, description)?,
+        ))),
+        None => Ok(None),
+    }
+}
+
+// 填充像素显示为黑色，或者加上alpha通道后显示为透明
+pub fn apply_padding(image: DynamicImage, mask: &[bool], display: PaddingDisplay) -> DynamicImage {
+    match display {
+        PaddingDisplay::Unchanged => image,
+        PaddingDisplay::Black => match image {
+            DynamicImage::ImageLuma8(mut image) => {
+                for (pixel, padding) in image.pixels_mut().zip(mask) {
+                    if *padding {
+                        *pixel = Luma([0]);
+                    }
+                }
+
+                DynamicImage::ImageLuma8(image)
+            }
+            DynamicImage::ImageLuma16(mut image) => {
+                for (pixel, padding) in image.pixels_mut().zip(mask) {
+                    if *padding {
+                        *pixel = Luma([0]);
+                    }
+                }
+
+                DynamicImage::ImageLuma16(image)
+            }
+            DynamicImage::ImageRgb16(mut image) => {
+                for (pixel, padding) in image.pixels_mut().zip(mask) {
+                    if *padding {
+                        *pixel = Rgb([0; 3]);
+                    }
+                }
+
+                DynamicImage::ImageRgb16(image)
+            }
+            image => {
+                let mut image = image.into_rgb8();
+
+                for (pixel, padding) in image.pixels_mut().zip(mask) {
+                    if *padding {
+                        *pixel = Rgb([0; 3]);
+                    }
+                }
+
+                DynamicImage::ImageRgb8(image)
+            }
+        },
+        PaddingDisplay::Transparent => match image {
+            DynamicImage::ImageLuma8(image) => {
+                let mut image = DynamicImage::ImageLuma8(image).into_luma_alpha8();
+
+                for (pixel, padding) in image.pixels_mut().zip(mask) {
+                    if *padding {
+                        pixel.0[1] = 0;
+                    }
+                }
+
+                DynamicImage::ImageLumaA8(image)
+            }
+            DynamicImage::ImageLuma16(image) => {
+                let mut image = DynamicImage::ImageLuma16(image).into_luma_alpha16();
+
+                for (pixel, padding) in image.pixels_mut().zip(mask) {
+                    if *padding {
+                        pixel.0[1] = 0;
+                    }
+                }
+
+                DynamicImage::ImageLumaA16(image)
+            }
+            DynamicImage::ImageRgb16(image) => {
+                let mut image = DynamicImage::ImageRgb16(image).into_rgba16();
+
+                for (pixel, padding) in image.pixels_mut().zip(mask) {
+                    if *padding {
+                        pixel.0[3] = 0;
+                    }
+                }
+
+                DynamicImage::ImageRgba16(image)
+            }
+            image => {
+                let mut image = image.into_rgba8();
+
+                for (pixel, padding) in image.pixels_mut().zip(mask) {
+                    if *padding {
+                        pixel.0[3] = 0;
+                    }
+                }
+
+                DynamicImage::ImageRgba8(image)
+            }
+        },
+    }
+}
+
+fn grayscale_image(
+    data_elements: &[DataElement],
+    description: &PixelDescription,
+    pixel_datas: &[u8],
+    options: &RenderOptions,
+) -> CommonResult<DynamicImage> {
+    // 按照Bits Allocated/Bits Stored/High Bit/Pixel Representation取出每个像素的值
+    let pixels = crate::pixel::frame_to_values(pixel_datas, description)?;
+
+    // Modality LUT -> VOI LUT -> Presentation LUT
+    let modality_transform =
+        crate::lut::get_modality_transform(data_elements, description.pixel_representation)?;
+    let voi_transform = select_voi_transform(
+        data_elements,
+        description.pixel_representation,
+        &pixels,
+        &modality_transform,
+        options,
+    )?;
+
+    let invert = (description.photometric_interpretation == "MONOCHROME1") != options.invert;
+
+    let processed_pixels =
+        crate::lut::apply_grayscale_pipeline(&pixels, &modality_transform, &voi_transform, invert);
+
+    grayscale_to_image(
+        description.columns as u32,
+        description.rows as u32,
+        &processed_pixels,
+        options.bit_depth,
+    )
+}
+
+// 灰度显示流程的输出（0.0~1.0）转换成8位或16位的灰度图像
+pub fn grayscale_to_image(
+    width: u32,
+    height: u32,
+    values: &[f64],
+    bit_depth: OutputBitDepth,
+) -> CommonResult<DynamicImage> {
+    let image = match bit_depth {
+        OutputBitDepth::Eight => DynamicImage::ImageLuma8(
+            ImageBuffer::<Luma<u8>, _>::from_raw(
+                width,
+                height,
+                values.iter().map(|v| (v * 255.0).round() as u8).collect(),
+            )
+            .ok_or("pixel data does not match the image size")?,
+        ),
+        OutputBitDepth::Sixteen => DynamicImage::ImageLuma16(
+            ImageBuffer::<Luma<u16>, _>::from_raw(
+                width,
+                height,
+                values
+                    .iter()
+                    .map(|v| (v * 65535.0).round() as u16)
+                    .collect(),
+            )
+            .ok_or("pixel data does not match the image size")?,
+        ),
+    };
+
+    Ok(image)
+}
+
+// 选择VOI变换：指定的窗宽窗位 > 文件中第voi_index个预设 > 自动计算
+pub fn select_voi_transform(
+    data_elements: &[DataElement],
+    pixel_representation: u16,
+    pixels: &[i64],
+    modality_transform: &crate::model::ModalityTransform,
+    options: &RenderOptions,
+) -> CommonResult<VoiTransform> {
+    if let Some(window) = &options.window {
+        return Ok(VoiTransform::Window(window.clone()));
+    }
+
+    if options.voi_index == 0 {
+        return crate::lut::get_voi_transform_or_auto(
+            data_elements,
+            pixel_representation,
+            pixels,
+            modality_transform,
+            options.auto_window,
+        );
+    }
+
+    let transforms = crate::lut::get_voi_transforms(data_elements, pixel_representation)?;
+    let count = transforms.len();
+
+    transforms
+        .into_iter()
+        .nth(options.voi_index)
+        .ok_or_else(|| {
+            format!(
+                "VOI preset {} is out of range, the file has {} presets",
+                options.voi_index, count
+            )
+            .into()
+        })
+}
+
+// 8位的RGB像素，输出16位时按照255 -> 65535线性扩展
+fn rgb_image(
+    description: &PixelDescription,
+    rgb_pixels: Vec<u8>,
+    options: &RenderOptions,
+) -> CommonResult<DynamicImage> {
+    let width = description.columns as u32;
+    let height = description.rows as u32;
+
+    let mut image = match options.bit_depth {
+        OutputBitDepth::Eight => DynamicImage::ImageRgb8(
+            ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, rgb_pixels)
+                .ok_or("rgb pixel data does not match the image size")?,
+        ),
+        OutputBitDepth::Sixteen => DynamicImage::ImageRgb16(
+            ImageBuffer::<Rgb<u16>, _>::from_raw(
+                width,
+                height,
+                rgb_pixels.iter().map(|v| *v as u16 * 257).collect(),
+            )
+            .ok_or("rgb pixel data does not match the image size")?,
+        ),
+    };
+
+    if options.invert {
+        image.invert();
+    }
+
+    Ok(image)
+}
+
+pub fn resize(image: DynamicImage, size: RenderSize) -> DynamicImage {
+    match size {
+        RenderSize::Original => image,
+        RenderSize::Scale(scale) => {
+            let width = ((image.width() as f64 * scale).round() as u32).max(1);
+            let height = ((image.height() as f64 * scale).round() as u32).max(1);
+
+            image.resize_exact(width, height, FilterType::Triangle)
+        }
+        RenderSize::Fit { width, height } => {
+            image.resize(width.max(1), height.max(1), FilterType::Triangle)
+        }
+    }
+}
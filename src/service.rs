@@ -1,296 +1,565 @@
-use image::{ImageBuffer, Luma};
-
 use crate::CommonResult;
 
-pub fn get_preamble(buffer: &Vec<u8>) -> CommonResult<(String, usize)> {
+pub fn get_preamble(buffer: &[u8]) -> CommonResult<(String, usize)> {
     let length = 128;
 
-    let preamble = String::from_utf8(buffer[..length].try_into()?)?;
+    let preamble = String::from_utf8(buffer[..length].into())?;
 
     Ok((preamble, length))
 }
 
-pub fn get_prefix(buffer: &Vec<u8>) -> CommonResult<(String, usize)> {
+pub fn get_prefix(buffer: &[u8]) -> CommonResult<(String, usize)> {
     let length = 4;
 
-    let prefix = String::from_utf8(buffer[..length].try_into()?)?;
+    let prefix = String::from_utf8(buffer[..length].into())?;
 
     Ok((prefix, length))
 }
 
-// 这里默认都用小端存储
-pub fn get_data_element(buffer: &Vec<u8>) -> CommonResult<(crate::model::DataElement, usize)> {
-    let mut length = 0;
-
-    // 获取tag的group部分
-    let mut group_tag_buffer = buffer[length..length + 2].to_vec();
-    group_tag_buffer.reverse();
-
-    length += 2;
+// 读取整个dicom文件，返回其中所有的数据元素（包括0002组的文件元信息）
+pub fn read_file(file_path: &str) -> CommonResult<Vec<crate::model::DataElement>> {
+    let file_content = std::fs::read(file_path)?;
 
-    let tag_group = crate::util::process_vec_to_tag(&group_tag_buffer);
+    parse_file_content(&file_content, &crate::model::ParseOptions::default())
+}
 
-    // println!("tag_group: {}", tag_group);
+pub fn parse_file_content(
+    file_content: &[u8],
+    options: &crate::model::ParseOptions,
+) -> CommonResult<Vec<crate::model::DataElement>> {
+    let mut offset = 0;
 
-    // 获取tag的element部分
-    let mut element_tag_buffer = buffer[length..length + 2].to_vec();
-    element_tag_buffer.reverse();
+    if file_content.len() < 132 {
+        return Err("file is too short to be a dicom file".into());
+    }
 
-    length += 2;
+    // 导言的内容是任意的（可能不是utf8），这里直接跳过
+    offset += 128;
 
-    let tag_element = crate::util::process_vec_to_tag(&element_tag_buffer);
+    let (prefix, consumed_bytes) = get_prefix(&file_content[offset..])?;
+    offset += consumed_bytes;
 
-    // println!("tag_element: {}", tag_element);
+    if prefix != "DICM" {
+        return Err(format!("invalid dicom prefix: {:?}", prefix).into());
+    }
 
-    let tag = format!("{},{}", tag_group, tag_element);
+    let mut data_elements = Vec::new();
 
-    // println!("tag: {}", tag);
+    // 文件元信息（0002组）固定使用显式小端
+    let meta_options = crate::model::ParseOptions {
+        transfer_syntax: crate::model::TransferSyntax::ExplicitVrLittleEndian,
+        ..*options
+    };
 
-    let tag_for_human = crate::util::get_tag_human_name(&tag)?;
+    while offset + 2 <= file_content.len()
+        && u16::from_le_bytes(file_content[offset..offset + 2].try_into()?) == 0x0002
+    {
+        let result = get_data_element_with_options(&file_content[offset..], &meta_options)?;
+        offset += result.1;
 
-    // 获取vr部分
-    let vr_buffer = buffer[length..length + 2].to_vec();
+        data_elements.push(result.0);
+    }
 
-    length += 2;
+    // 数据集按照文件元信息中的传输语法进行解析
+    let transfer_syntax_uid = crate::accessor::get_str(&data_elements, "0002,0010")?;
+    let transfer_syntax = crate::model::TransferSyntax::from_uid(&transfer_syntax_uid)
+        .ok_or_else(|| format!("transfer syntax {} is not supported", transfer_syntax_uid))?;
 
-    let vr = crate::util::process_vec_to_vr(&vr_buffer);
+    let dataset_options = crate::model::ParseOptions {
+        transfer_syntax,
+        ..*options
+    };
 
-    // println!("vr: {}", vr);
+    while offset < file_content.len() {
+        let result = get_data_element_with_options(&file_content[offset..], &dataset_options)?;
+        offset += result.1;
 
-    // 获取所有vr的可能值
-    let all_vr_values = crate::util::get_vr_values();
+        data_elements.push(result.0);
+    }
 
-    let data_element = if all_vr_values.contains(&vr) {
-        // 计算出data element的长度
-        let data_element_length;
-        if ["OB", "OW", "OF", "UT", "UN"].contains(&vr.as_str()) {
-            // 显式vr特殊结构（带预留）
-            // 跳过保留的字节
-            length += 2;
+    resolve_private_elements(&mut data_elements, &dataset_options)?;
 
-            // 这几种类型的长度是4字节的，这4字节不包含保留的那2字节
-            // https://zhuanlan.zhihu.com/p/671921616
-            data_element_length =
-                u32::from_le_bytes(buffer[length..length + 4].try_into()?) as usize;
+    Ok(data_elements)
+}
 
-            length += 4;
-        } else if vr.as_str() == "SQ" {
-            // 显式vr特殊结构（带预留）
-            // 跳过保留的字节
-            length += 2;
+// 私有数据元素的名字和VR要结合同一个数据集中的Private Creator才能确定，所以在整个数据集解析完之后处理
+// 隐式VR中私有数据元素保留的是原始字节，私有字典中有VR时按照这个VR重新解析
+fn resolve_private_elements(
+    data_elements: &mut [crate::model::DataElement],
+    options: &crate::model::ParseOptions,
+) -> CommonResult<()> {
+    let little_endian = options.transfer_syntax.is_little_endian();
+
+    for index in 0..data_elements.len() {
+        let Some(private_tag) =
+            crate::accessor::get_private_tag(data_elements, &data_elements[index].tag)
+        else {
+            continue;
+        };
+
+        let data_element = &mut data_elements[index];
+        data_element.tag_for_human = private_tag.name;
+
+        let crate::model::DicomValue::Bytes(raw) = &data_element.data else {
+            continue;
+        };
+
+        if data_element.vr != "implicit" || private_tag.vr == "UN" {
+            continue;
+        }
 
-            // 这里SQ的data_element_length的赋值其实没啥用
-            // 只是为了维持结构一致
-            data_element_length = 0;
+        let raw = raw.clone();
+        let vr = private_tag.vr;
+        let mut original_encoding = crate::model::OriginalEncoding {
+            little_endian,
+            ..Default::default()
+        };
+
+        // 字典中的VR不一定和实际的数据相符（比如不同软件版本的私有tag含义不同），
+        // 按照字典中的VR解析失败时保留原始字节，并把错误记录在value_errors中，不影响整个文件的解析
+        let result = match fixed_value_size(&vr) {
+            Some(size) if !raw.len().is_multiple_of(size) => {
+                Err(format!("value length {} is not a multiple of {}", raw.len(), size).into())
+            }
+            _ if vr == "SQ" => parse_sq_data(&raw, raw.len(), options, &mut original_encoding)
+                .map(|result| (result.0, Vec::new())),
+            _ => parse_data(&raw, &vr, raw.len(), little_endian, options),
+        };
+
+        let (data_value, value_errors) = match result {
+            Ok(result) => result,
+            Err(error) => {
+                data_element.value_errors.push(crate::model::ValueError {
+                    index: 0,
+                    raw: raw.iter().map(|v| format!("{:02X}", v)).collect(),
+                    message: format!(
+                        "can not be parsed as {} from the private dictionary: {}",
+                        vr, error
+                    ),
+                });
+                continue;
+            }
+        };
 
-            // let mut buffer = Vec::new();
-            // file.seek(std::io::SeekFrom::Start(offset + length))?;
-            // file.read_to_end(&mut buffer)?;
+        if vr != "SQ" && !is_reproducible(&raw, &vr, &data_value, little_endian).unwrap_or(false) {
+            original_encoding.raw = Some(raw);
+        }
 
-            // parse_sq_data(&buffer);
-            // let result = parse_sq_data(&buffer[length..].to_vec())?;
-        } else {
-            // 显式vr普通结构（无预留）
-            // 显示vr普通结构的长度是2字节的
-            data_element_length =
-                u16::from_le_bytes(buffer[length..length + 2].try_into()?) as usize;
+        data_element.data = data_value;
+        data_element.value_errors = value_errors;
+        data_element.vr = vr;
+        data_element.original_encoding = Some(original_encoding);
+    }
 
-            length += 2;
-        }
+    Ok(())
+}
 
-        // println!("data element length is: {}", data_element_length);
+// 二进制数值类型每个值的字节数，字符串类型等长度不固定的返回None
+fn fixed_value_size(vr: &str) -> Option<usize> {
+    match vr {
+        "US" | "SS" => Some(2),
+        "UL" | "SL" | "FL" | "AT" => Some(4),
+        "FD" | "SV" | "UV" => Some(8),
+        _ => None,
+    }
+}
 
-        // 解析实际数据
-        let data_value;
+// 读取一个tag，返回(group, element)
+fn read_tag(buffer: &[u8], little_endian: bool) -> CommonResult<(u16, u16)> {
+    let group = read_u16(buffer, little_endian)?;
+    let element = read_u16(buffer.get(2..).unwrap_or_default(), little_endian)?;
 
-        // 如果是SQ则使用特殊的方式进行解析
-        if vr.as_str() == "SQ" {
-            let result = parse_sq_data(&buffer[length..].to_vec())?;
+    Ok((group, element))
+}
 
-            data_value = result.0;
-            length += result.1;
-        } else {
-            data_value = parse_data(&buffer[length..].to_vec(), &vr, data_element_length)?;
+fn read_u16(buffer: &[u8], little_endian: bool) -> CommonResult<u16> {
+    let bytes = buffer
+        .get(..2)
+        .ok_or("unexpected end of data")?
+        .try_into()?;
 
-            length += data_element_length;
-        }
+    Ok(if little_endian {
+        u16::from_le_bytes(bytes)
+    } else {
+        u16::from_be_bytes(bytes)
+    })
+}
 
-        // println!("data value is: {:#?}", data_value);
+fn read_u32(buffer: &[u8], little_endian: bool) -> CommonResult<u32> {
+    let bytes = buffer
+        .get(..4)
+        .ok_or("unexpected end of data")?
+        .try_into()?;
 
-        crate::model::DataElement {
-            tag_group: tag_group.clone(),
-            tag_element: tag_element.clone(),
-            tag: tag,
-            tag_for_human: tag_for_human,
-            vr: vr,
-            data: data_value,
-        }
+    Ok(if little_endian {
+        u32::from_le_bytes(bytes)
     } else {
-        println!("这段代码未经过测试，bravo");
-        let data_element_length =
-            u32::from_le_bytes(buffer[length..length + 4].try_into()?) as usize;
-
-        let data_value = buffer[length..length + data_element_length].to_vec();
-
-        crate::model::DataElement {
-            tag_group: tag_group.clone(),
-            tag_element: tag_element.clone(),
-            tag: tag,
-            tag_for_human: tag_for_human,
-            vr: "implicit".to_string(),
-            data: crate::model::DicomValue::Bytes(data_value),
-        }
-    };
+        u32::from_be_bytes(bytes)
+    })
+}
 
-    Ok((data_element, length))
+// 这里默认都用显式小端存储
+pub fn get_data_element(buffer: &[u8]) -> CommonResult<(crate::model::DataElement, usize)> {
+    get_data_element_with_options(buffer, &crate::model::ParseOptions::default())
 }
 
-fn parse_data(
-    buffer: &Vec<u8>,
-    vr: &String,
-    data_length: usize,
-) -> CommonResult<crate::model::DicomValue> {
-    let vr_match = vr.as_str();
+pub fn get_data_element_with_options(
+    buffer: &[u8],
+    options: &crate::model::ParseOptions,
+) -> CommonResult<(crate::model::DataElement, usize)> {
+    let little_endian = options.transfer_syntax.is_little_endian();
 
-    // 这下面的处理逻辑中不会包含SQ
-    // 因为SQ的处理方式比较特殊，所以使用专门的parse_sq_data进行处理
-    let result = match vr_match {
-        "UL" => {
-            let mut offset = 0;
-            let mut datas = Vec::new();
+    let mut length = 0;
 
-            loop {
-                let data = u32::from_le_bytes(buffer[offset..offset + 4].try_into()?);
+    // 获取tag的group部分和element部分
+    let (group, element) = read_tag(&buffer[length..], little_endian)?;
 
-                datas.push(data);
+    length += 4;
 
-                offset += 4;
+    let tag_group = format!("{:04X}", group);
+    let tag_element = format!("{:04X}", element);
 
-                if offset >= data_length {
-                    break;
-                }
-            }
+    let tag = format!("{},{}", tag_group, tag_element);
+
+    // println!("tag: {}", tag);
+
+    let tag_for_human = crate::util::get_tag_human_name(&tag)?;
 
-            crate::model::DicomValue::U32(datas)
+    // 获取vr部分
+    // 隐式vr的文件中没有vr，显式vr的文件中如果vr不合法也按照隐式vr处理
+    let mut vr = "implicit".to_string();
+
+    if options.transfer_syntax.is_explicit_vr() {
+        let explicit_vr = crate::util::process_vec_to_vr(
+            buffer
+                .get(length..length + 2)
+                .ok_or("unexpected end of data")?,
+        );
+
+        // 获取所有vr的可能值
+        if crate::util::get_vr_values().contains(&explicit_vr) {
+            vr = explicit_vr;
+            length += 2;
         }
-        "US" => {
-            let mut offset = 0;
-            let mut datas = Vec::new();
+    }
 
-            loop {
-                let data = u16::from_le_bytes(buffer[offset..offset + 2].try_into()?);
+    // println!("vr: {}", vr);
 
-                datas.push(data);
+    // 计算出data element的长度
+    let data_element_length;
+    if vr == "implicit" {
+        // 隐式vr的长度固定是4字节
+        data_element_length = read_u32(&buffer[length..], little_endian)? as usize;
+
+        length += 4;
+    } else if crate::util::is_long_length_vr(&vr) {
+        // 显式vr特殊结构（带预留）
+        // 跳过保留的字节
+        length += 2;
+
+        // 这几种类型的长度是4字节的，这4字节不包含保留的那2字节
+        // https://zhuanlan.zhihu.com/p/671921616
+        // 具体有哪些vr参考 https://dicom.nema.org/medical/dicom/current/output/chtml/part05/chapter_7.html 的表7.1-1
+        data_element_length =
+            read_u32(buffer.get(length..).unwrap_or_default(), little_endian)? as usize;
+
+        length += 4;
+    } else {
+        // 显式vr普通结构（无预留）
+        // 显示vr普通结构的长度是2字节的
+        data_element_length = read_u16(&buffer[length..], little_endian)? as usize;
 
-                offset += 2;
+        length += 2;
+    }
 
-                if offset >= data_length {
-                    break;
-                }
-            }
+    // println!("data element length is: {}", data_element_length);
+
+    // 截断的文件中元素的长度可能超出剩余的字节数
+    let value_buffer = || {
+        buffer
+            .get(length..length + data_element_length)
+            .ok_or_else(|| {
+                format!(
+                    "{} value length {} exceeds the remaining {} bytes",
+                    tag,
+                    data_element_length,
+                    buffer.len().saturating_sub(length)
+                )
+            })
+    };
 
-            crate::model::DicomValue::U16(datas)
+    // 隐式vr的元素按照标准字典查出vr，私有tag等查不到的仍然作为implicit处理
+    if vr == "implicit" {
+        if let Some(standard_vr) = crate::util::get_standard_vr(&tag) {
+            vr = standard_vr;
         }
-        "FD" => {
-            let mut offset = 0;
-            let mut datas = Vec::new();
+    }
 
-            loop {
-                let data = f64::from_le_bytes(buffer[offset..offset + 8].try_into()?);
+    // 隐式vr中未定义长度的元素只可能是SQ
+    if vr == "implicit" && data_element_length == 0xffffffff {
+        vr = "SQ".to_string();
+    }
 
-                datas.push(data);
+    // 解析实际数据
+    let data_value;
+    let mut value_errors = Vec::new();
+    let mut original_encoding = crate::model::OriginalEncoding {
+        little_endian,
+        ..Default::default()
+    };
 
-                offset += 8;
+    // 如果是SQ则使用特殊的方式进行解析
+    if vr == "SQ" {
+        let result = parse_sq_data(
+            &buffer[length..],
+            data_element_length,
+            options,
+            &mut original_encoding,
+        )?;
+
+        data_value = result.0;
+        length += result.1;
+    } else if (vr == "OB" || vr == "OW") && data_element_length == 0xffffffff {
+        // 未定义长度的OB/OW是封装格式的像素数据
+        let result = parse_fragments(&buffer[length..], little_endian)?;
+
+        data_value = result.0;
+        length += result.1;
+    } else if vr == "implicit" {
+        // 不知道vr时无法解析，只能保留原始数据
+        let raw = value_buffer()?;
+
+        data_value = crate::model::DicomValue::Bytes(raw.to_vec());
+
+        length += data_element_length;
+
+        if !raw.len().is_multiple_of(2) {
+            original_encoding.raw = Some(raw.to_vec());
+        }
+    } else {
+        let raw = value_buffer()?;
 
-                if offset >= data_length {
-                    break;
-                }
-            }
+        let result = parse_data(raw, &vr, data_element_length, little_endian, options)?;
 
-            crate::model::DicomValue::Double(datas)
+        data_value = result.0;
+        value_errors = result.1;
+        length += data_element_length;
+
+        // 如果按照解析出来的值无法重新编码出原始的字节（奇数长度、非标准的填充、DS的书写格式等）
+        // 就把原始字节保留下来，写回文件的时候使用
+        if !is_reproducible(raw, &vr, &data_value, little_endian)? {
+            original_encoding.raw = Some(raw.to_vec());
         }
-        "FL" => {
-            let mut offset = 0;
-            let mut datas = Vec::new();
+    }
 
-            loop {
-                let data = f32::from_le_bytes(buffer[offset..offset + 4].try_into()?);
+    // println!("data value is: {:#?}", data_value);
+
+    let data_element = crate::model::DataElement {
+        tag_group,
+        tag_element,
+        tag,
+        tag_for_human,
+        vr,
+        data: data_value,
+        value_errors,
+        original_encoding: Some(original_encoding),
+    };
 
-                datas.push(data);
+    Ok((data_element, length))
+}
 
-                offset += 4;
+// 判断解析出来的值能否重新编码成原始字节
+fn is_reproducible(
+    raw: &[u8],
+    vr: &str,
+    data_value: &crate::model::DicomValue,
+    little_endian: bool,
+) -> CommonResult<bool> {
+    // 二进制数据在内存中保留的是原始字节（或者按照值的宽度翻转过的字节），偶数长度时一定可以还原
+    if let crate::model::DicomValue::Bytes(_) = data_value {
+        return Ok(raw.len().is_multiple_of(2));
+    }
 
-                if offset >= data_length {
-                    break;
-                }
-            }
+    let encoded = crate::writer::encode_value(vr, data_value, little_endian);
 
-            crate::model::DicomValue::Float(datas)
-        }
-        "DS" => {
-            let (result, _, _) = encoding_rs::GBK.decode(&buffer[..data_length]);
+    Ok(matches!(encoded, Ok(encoded) if encoded == raw))
+}
 
-            let string = result.trim();
+// 按照vr解析一个值，写文件时用来判断保留的原始字节是否仍然与当前的值一致
+pub fn parse_value(
+    buffer: &[u8],
+    vr: &str,
+    little_endian: bool,
+) -> CommonResult<crate::model::DicomValue> {
+    let options = crate::model::ParseOptions::default();
 
-            let string_vec = string.split("\\").collect::<Vec<_>>();
+    Ok(parse_data(buffer, vr, buffer.len(), little_endian, &options)?.0)
+}
 
-            let datas = string_vec
-                .iter()
-                .map(|v| v.parse::<f64>().unwrap())
-                .collect();
+// 按照字节序读取连续的数值，长度为0时VM为0，不能读取任何值
+fn read_numbers<T, const N: usize>(
+    buffer: &[u8],
+    data_length: usize,
+    little_endian: bool,
+    from_le_bytes: fn([u8; N]) -> T,
+    from_be_bytes: fn([u8; N]) -> T,
+) -> CommonResult<Vec<T>> {
+    let mut offset = 0;
+    let mut datas = Vec::new();
 
-            crate::model::DicomValue::Double(datas)
-        }
-        "OW" => {
-            // 对于ow的数据处理，尤其是像素的数据处理比较复杂
-            // 这个交给处理图像的部分进行处理
-            // 这里只是把数据拿出来
-            let pixels = buffer[..data_length].to_vec();
-            crate::model::DicomValue::Bytes(pixels)
-        }
-        "OB" => {
-            let (data, _, _) = encoding_rs::GBK.decode(&buffer[..data_length]);
+    while offset + N <= data_length {
+        let bytes = buffer[offset..offset + N].try_into()?;
 
-            crate::model::DicomValue::String(data.trim().trim_end_matches("\0").to_string())
-        }
-        "SL" => {
-            let mut offset = 0;
-            let mut datas = Vec::new();
+        let data = if little_endian {
+            from_le_bytes(bytes)
+        } else {
+            from_be_bytes(bytes)
+        };
 
-            loop {
-                let data = i32::from_le_bytes(buffer[offset..offset + 4].try_into()?);
+        datas.push(data);
 
-                datas.push(data);
+        offset += N;
+    }
 
-                offset += 4;
+    Ok(datas)
+}
 
-                if offset >= data_length {
-                    break;
-                }
-            }
+fn parse_data(
+    buffer: &[u8],
+    vr: &str,
+    data_length: usize,
+    little_endian: bool,
+    options: &crate::model::ParseOptions,
+) -> CommonResult<(crate::model::DicomValue, Vec<crate::model::ValueError>)> {
+    let mut value_errors = Vec::new();
+    let le = little_endian;
 
-            crate::model::DicomValue::I32(datas)
+    // 这下面的处理逻辑中不会包含SQ
+    // 因为SQ的处理方式比较特殊，所以使用专门的parse_sq_data进行处理
+    let result = match vr {
+        "UL" => crate::model::DicomValue::U32(read_numbers(
+            buffer,
+            data_length,
+            le,
+            u32::from_le_bytes,
+            u32::from_be_bytes,
+        )?),
+        "US" => crate::model::DicomValue::U16(read_numbers(
+            buffer,
+            data_length,
+            le,
+            u16::from_le_bytes,
+            u16::from_be_bytes,
+        )?),
+        "FD" => crate::model::DicomValue::Double(read_numbers(
+            buffer,
+            data_length,
+            le,
+            f64::from_le_bytes,
+            f64::from_be_bytes,
+        )?),
+        "FL" => crate::model::DicomValue::Float(read_numbers(
+            buffer,
+            data_length,
+            le,
+            f32::from_le_bytes,
+            f32::from_be_bytes,
+        )?),
+        "SL" => crate::model::DicomValue::I32(read_numbers(
+            buffer,
+            data_length,
+            le,
+            i32::from_le_bytes,
+            i32::from_be_bytes,
+        )?),
+        "SS" => crate::model::DicomValue::I16(read_numbers(
+            buffer,
+            data_length,
+            le,
+            i16::from_le_bytes,
+            i16::from_be_bytes,
+        )?),
+        "SV" => crate::model::DicomValue::I64(read_numbers(
+            buffer,
+            data_length,
+            le,
+            i64::from_le_bytes,
+            i64::from_be_bytes,
+        )?),
+        "UV" => crate::model::DicomValue::U64(read_numbers(
+            buffer,
+            data_length,
+            le,
+            u64::from_le_bytes,
+            u64::from_be_bytes,
+        )?),
+        "DS" => {
+            let (result, _, _) = encoding_rs::GBK.decode(&buffer[..data_length]);
+
+            let (datas, errors) = crate::util::parse_decimal_strings(&result, options.numeric_mode);
+
+            value_errors = errors;
+
+            crate::model::DicomValue::Double(datas)
         }
-        "SS" => {
-            let mut offset = 0;
-            let mut datas = Vec::new();
+        "IS" => {
+            let (result, _, _) = encoding_rs::GBK.decode(&buffer[..data_length]);
 
-            loop {
-                let data = i16::from_le_bytes(buffer[offset..offset + 2].try_into()?);
+            let (datas, errors) = crate::util::parse_integer_strings(&result, options.numeric_mode);
 
-                datas.push(data);
+            value_errors = errors;
 
-                offset += 2;
+            crate::model::DicomValue::I64(datas)
+        }
+        "OW" | "OF" | "OD" | "OL" | "OV" => {
+            // 对于ow的数据处理，尤其是像素的数据处理比较复杂
+            // 这个交给处理图像的部分进行处理
+            // 这里只是把数据拿出来
+            // 大端存储时按照单个值的宽度翻转，保证内存中始终是小端顺序
+            let pixels = if little_endian {
+                buffer[..data_length].to_vec()
+            } else {
+                let width = match vr {
+                    "OW" => 2,
+                    "OF" | "OL" => 4,
+                    _ => 8,
+                };
+
+                buffer[..data_length]
+                    .chunks(width)
+                    .flat_map(|chunk| chunk.iter().rev().copied())
+                    .collect()
+            };
 
-                if offset >= data_length {
-                    break;
-                }
+            crate::model::DicomValue::Bytes(pixels)
+        }
+        "OB" | "UN" => {
+            // OB和UN中存储的是任意的二进制数据，不能当作字符串解码
+            // 否则写回文件时无法还原
+            crate::model::DicomValue::Bytes(buffer[..data_length].to_vec())
+        }
+        "AT" => {
+            let datas = read_numbers(
+                buffer,
+                data_length,
+                le,
+                u16::from_le_bytes,
+                u16::from_be_bytes,
+            )?;
+
+            // 绝大多数AT只有一个值，这时使用U16Pair保存(group, element)
+            // 多个值时按照group、element交替的顺序保存在U16中
+            if datas.len() == 2 {
+                crate::model::DicomValue::U16Pair((
+                    format!("{:04X}", datas[0]),
+                    format!("{:04X}", datas[1]),
+                ))
+            } else {
+                crate::model::DicomValue::U16(datas)
             }
-
-            crate::model::DicomValue::I16(datas)
         }
-        "UI" | "SH" | "CS" | "DA" | "TM" | "LO" | "PN" | "UN" | "IS" | "DT" | "ST" | "AS"
-        | "AE" | "LT" => {
+        "UI" | "SH" | "CS" | "DA" | "TM" | "LO" | "PN" | "DT" | "ST" | "AS" | "AE" | "LT"
+        | "UT" | "UC" | "UR" => {
             // todo 解析字符集
             // specific character set
             // 0008,0005
@@ -300,409 +569,272 @@ fn parse_data(
 
             crate::model::DicomValue::String(result.trim().trim_end_matches("\0").to_string())
         }
-        "OD" | "OF" | "UT" => {
-            panic!("not support");
-        }
         _ => {
-            panic!("not support");
+            return Err(format!("VR {} is not supported", vr).into());
         }
     };
 
-    Ok(result)
-}
-
-pub fn generate_image(data_elements: &Vec<crate::model::DataElement>) -> CommonResult<()> {
-    // 获取rows数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,0010".to_string());
-
-    if result.is_none() {
-        panic!("no rows data");
-    }
-
-    let dicom_value = result.unwrap().data;
-    let rows;
-
-    if let Some(crate::model::DicomValue::U16(v)) = Some(dicom_value) {
-        rows = v[0];
-    } else {
-        rows = 0;
-    }
-
-    // 获取columns数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,0011".to_string());
-
-    if result.is_none() {
-        panic!("no columns data");
-    }
-
-    let dicom_value = result.unwrap().data;
-    let columns;
-
-    if let Some(crate::model::DicomValue::U16(v)) = Some(dicom_value) {
-        columns = v[0];
-    } else {
-        columns = 0;
-    }
-
-    // 获取photometric interpretation数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,0004".to_string());
-
-    if result.is_none() {
-        panic!("no photometric interpretation data");
-    }
-
-    let dicom_value = result.unwrap().data;
-    let photometric_interpretation;
-
-    if let Some(crate::model::DicomValue::String(v)) = Some(dicom_value) {
-        photometric_interpretation = v;
-    } else {
-        photometric_interpretation = "".to_string();
-    }
-
-    // 获取bit allocated数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,0100".to_string());
-
-    if result.is_none() {
-        panic!("no bit allocated data");
-    }
-
-    let dicom_value = result.unwrap().data;
-    let bit_allocated;
-
-    if let Some(crate::model::DicomValue::U16(v)) = Some(dicom_value) {
-        bit_allocated = v[0];
-    } else {
-        bit_allocated = 0;
-    }
-
-    // 获取bit stored数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,0101".to_string());
-
-    if result.is_none() {
-        panic!("no bit stored data");
+    // 严格模式下任何一个值不合法都视为整个文件解析失败
+    if options.numeric_mode == crate::model::NumericParseMode::Strict {
+        if let Some(value_error) = value_errors.first() {
+            return Err(format!(
+                "invalid {} value #{} {:?}: {}",
+                vr, value_error.index, value_error.raw, value_error.message
+            )
+            .into());
+        }
     }
 
-    let dicom_value = result.unwrap().data;
-    let bit_stored;
+    Ok((result, value_errors))
+}
 
-    if let Some(crate::model::DicomValue::U16(v)) = Some(dicom_value) {
-        bit_stored = v[0];
-    } else {
-        bit_stored = 0;
-    }
+// 渲染第一帧，保存到./images/output.png
+pub fn generate_image(data_elements: &[crate::model::DataElement]) -> CommonResult<()> {
+    generate_frame_image(data_elements, 0, "./images/output.png")
+}
 
-    // 获取window center数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,1050".to_string());
+// 渲染第frame_index帧（从0开始）并保存到file_path
+// 需要更多的选项（窗宽窗位、位深、尺寸、格式）或者不写文件时使用render模块
+pub fn generate_frame_image(
+    data_elements: &[crate::model::DataElement],
+    frame_index: usize,
+    file_path: &str,
+) -> CommonResult<()> {
+    let options = crate::model::RenderOptions {
+        frame_index,
+        ..Default::default()
+    };
 
-    if result.is_none() {
-        panic!("no window center data");
-    }
+    crate::render::render_to_file(data_elements, file_path, &options)
+}
 
-    let dicom_value = result.unwrap().data;
-    let window_center;
+// 渲染所有帧，按照帧号（从1开始）依次保存为output_directory下的frame_0001.png、frame_0002.png...
+// 返回所有生成的文件路径
+pub fn generate_frame_images(
+    data_elements: &[crate::model::DataElement],
+    output_directory: &str,
+) -> CommonResult<Vec<String>> {
+    generate_frame_images_with_options(
+        data_elements,
+        output_directory,
+        &crate::model::RenderOptions::default(),
+    )
+}
 
-    if let Some(crate::model::DicomValue::Double(v)) = Some(dicom_value) {
-        window_center = v[0];
-    } else {
-        window_center = 0.0;
-    }
+// 与generate_frame_images相同，文件中没有窗宽窗位时按照auto_window自动计算
+// 批量生成缩略图时可以选择Percentile，避免少量极端值让图像整体发灰
+pub fn generate_frame_images_with_auto_window(
+    data_elements: &[crate::model::DataElement],
+    output_directory: &str,
+    auto_window: crate::model::AutoWindowStrategy,
+) -> CommonResult<Vec<String>> {
+    let options = crate::model::RenderOptions {
+        auto_window,
+        ..Default::default()
+    };
 
-    // 获取window width数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,1051".to_string());
+    generate_frame_images_with_options(data_elements, output_directory, &options)
+}
 
-    if result.is_none() {
-        panic!("no window width data");
-    }
+// 按照options渲染所有帧，options中的frame_index不起作用，扩展名由options.format决定
+pub fn generate_frame_images_with_options(
+    data_elements: &[crate::model::DataElement],
+    output_directory: &str,
+    options: &crate::model::RenderOptions,
+) -> CommonResult<Vec<String>> {
+    let frames = crate::pixel::frames(data_elements)?;
+    let description = frames.description().clone();
 
-    let dicom_value = result.unwrap().data;
-    let window_width;
+    // 帧数超过9999时加宽编号，保证文件名按字典序排列时顺序不变
+    let number_width = description.number_of_frames.to_string().len().max(4);
+    let extension = options.format.extensions_str().first().unwrap_or(&"png");
 
-    if let Some(crate::model::DicomValue::Double(v)) = Some(dicom_value) {
-        window_width = v[0];
-    } else {
-        window_width = 0.0;
-    }
+    std::fs::create_dir_all(output_directory)?;
 
-    // 获取rescale intercept数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,1052".to_string());
+    let mut file_paths = Vec::new();
 
-    if result.is_none() {
-        panic!("no rescale intercept data");
-    }
+    for (index, frame) in frames.enumerate() {
+        let file_path = format!(
+            "{}/frame_{:0width$}.{}",
+            output_directory.trim_end_matches('/'),
+            index + 1,
+            extension,
+            width = number_width
+        );
 
-    let dicom_value = result.unwrap().data;
-    let rescale_intercept;
+        crate::render::render_frame(data_elements, &description, index, &frame?, options)?
+            .save_with_format(&file_path, options.format)?;
 
-    if let Some(crate::model::DicomValue::Double(v)) = Some(dicom_value) {
-        rescale_intercept = v[0];
-    } else {
-        rescale_intercept = 0.0;
+        file_paths.push(file_path);
     }
 
-    // 获取rescale slope数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "0028,1053".to_string());
+    Ok(file_paths)
+}
 
-    if result.is_none() {
-        panic!("no rescale slope data");
-    }
+// 具体的实现一句参考下方链接里的三个表格
+// https://dicom.nema.org/dicom/2013/output/chtml/part05/sect_7.5.html
+fn parse_sq_data(
+    buffer: &[u8],
+    data_element_length: usize,
+    options: &crate::model::ParseOptions,
+    original_encoding: &mut crate::model::OriginalEncoding,
+) -> CommonResult<(crate::model::DicomValue, usize)> {
+    let little_endian = options.transfer_syntax.is_little_endian();
 
-    let dicom_value = result.unwrap().data;
-    let rescale_slope;
+    let mut offset = 0;
+    let mut items = Vec::new();
 
-    if let Some(crate::model::DicomValue::Double(v)) = Some(dicom_value) {
-        rescale_slope = v[0];
-    } else {
-        rescale_slope = 0.0;
-    }
+    if data_element_length == 0xffffffff {
+        original_encoding.undefined_length = true;
 
-    // 获取图像数据
-    let result = crate::util::get_data_element_via_tag(data_elements, "7FE0,0010".to_string());
+        // 未定义长度的SQ需要逐个解析item，直到遇到Seq. Delim. Tag (FFFE,E0DD)
+        // 不能直接搜索分隔符，因为嵌套的SQ中也会出现同样的分隔符
+        loop {
+            if offset + 8 > buffer.len() {
+                return Err("Seq. Delim. Tag not found".into());
+            }
 
-    if result.is_none() {
-        panic!("no pixel data");
-    }
+            if read_tag(&buffer[offset..], little_endian)? == (0xfffe, 0xe0dd) {
+                offset += 8;
+                break;
+            }
 
-    let dicom_value = result.unwrap().data;
-    let pixel_datas;
+            let result = parse_sq_item(&buffer[offset..], options)?;
 
-    if let Some(crate::model::DicomValue::Bytes(v)) = Some(dicom_value) {
-        pixel_datas = v;
+            items.push(result.0);
+            original_encoding.item_undefined_lengths.push(result.1);
+            offset += result.2;
+        }
     } else {
-        pixel_datas = Vec::new();
-    }
-
-    // 处理像素数据
-    let mut pixels = Vec::new();
-    let mut offset = 0;
-    let mask = 0xff_u8 << (8 - (bit_allocated - bit_stored));
-    let bit_allocated_by_bytes = (bit_allocated / 8) as usize;
-
-    loop {
-        if offset >= pixel_datas.len() {
-            break;
+        // 如果不是8f，那么data_element_length就是实际的长度
+        let end = offset + data_element_length;
+
+        if end > buffer.len() {
+            return Err(format!(
+                "sequence length {} exceeds the remaining {} bytes",
+                data_element_length,
+                buffer.len()
+            )
+            .into());
         }
 
-        let mut pixel_data_buffer = pixel_datas[offset..offset + bit_allocated_by_bytes].to_vec();
-
-        pixel_data_buffer[0] = pixel_data_buffer[0] & mask;
+        while offset < end {
+            let result = parse_sq_item(&buffer[offset..end], options)?;
 
-        let pixel = u16::from_le_bytes(pixel_data_buffer[..].try_into()?);
-
-        pixels.push(pixel);
-
-        offset += bit_allocated_by_bytes;
+            items.push(result.0);
+            original_encoding.item_undefined_lengths.push(result.1);
+            offset += result.2;
+        }
     }
 
-    let processed_pixels = process_image_pixels(
-        &pixels,
-        &photometric_interpretation,
-        rescale_intercept,
-        rescale_slope,
-        window_width,
-        window_center,
-    )?;
-
-    write_image_pixels_to_file(columns as u32, rows as u32, &processed_pixels)?;
-
-    Ok(())
+    Ok((crate::model::DicomValue::Sequence(items), offset))
 }
 
-// 参考了https://github.com/ykuo2/dicom2jpg/blob/main/dicom2jpg/utils.py#L116
-fn process_image_pixels(
-    pixels: &Vec<u16>,
-    photometric_interpretation: &String,
-    rescale_intercept: f64,
-    rescale_slope: f64,
-    window_width: f64,
-    window_center: f64,
-) -> CommonResult<Vec<u8>> {
-    if photometric_interpretation != "MONOCHROME2" && photometric_interpretation != "MONOCHROME1" {
-        panic!("photometric interpretation is not supported");
-    }
+// 解析一个item，返回item中的所有element、item是否为未定义长度以及消耗的字节数
+fn parse_sq_item(
+    buffer: &[u8],
+    options: &crate::model::ParseOptions,
+) -> CommonResult<(Vec<crate::model::DataElement>, bool, usize)> {
+    let little_endian = options.transfer_syntax.is_little_endian();
 
-    let data_min = pixels.iter().min().unwrap().to_owned() as f64;
-    let data_max = pixels.iter().max().unwrap().to_owned() as f64;
-    let data_range = data_max - data_min;
-
-    let pixels = pixels
-        .iter()
-        .map(|v| v.to_owned() as f64)
-        .map(|v| v * rescale_slope + rescale_intercept)
-        .map(|v| {
-            if v <= (window_center - window_width / 2.0) {
-                data_min
-            } else if v > (window_center + window_width / 2.0) {
-                data_max
-            } else {
-                (v - window_center + window_width / 2.0) / window_width * data_range + data_min
-            }
-        })
-        .collect::<Vec<f64>>();
-
-    let piexel_min = pixels
-        .iter()
-        .min_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap();
-    let piexel_max = pixels
-        .iter()
-        .max_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap();
-
-    let mut pixels = pixels
-        .iter()
-        .map(|v| ((v - piexel_min) / (piexel_max - piexel_min)) * 255.0)
-        .map(|v| v.round() as u8)
-        .collect::<Vec<u8>>();
-
-    // 对MONOCHROME1的显示进行额外处理
-    if photometric_interpretation == "MONOCHROME1" {
-        let pixels_max_value = pixels.iter().max().unwrap();
-
-        pixels = pixels
-            .iter()
-            .map(|v| (pixels_max_value - v).to_owned())
-            .collect::<Vec<u8>>();
-    }
+    let mut offset = 0;
 
-    Ok(pixels)
-}
+    let item_tag = read_tag(&buffer[offset..], little_endian)?;
 
-fn write_image_pixels_to_file(width: u32, height: u32, datas: &Vec<u8>) -> CommonResult<()> {
-    // 创建一个256x256的RGB图像
-    let mut img = ImageBuffer::<Luma<u8>, _>::new(width, height);
+    offset += 4;
 
-    // 将数据映射到图像的像素值
-    for (x, y, pixel) in img.enumerate_pixels_mut() {
-        let index = (y * width + x) as usize;
-        let value = datas[index];
-        *pixel = Luma([value]); // 将数据作为RGB值
+    if item_tag != (0xfffe, 0xe000) {
+        return Err(format!("item tag is invalid: {:04X}{:04X}", item_tag.0, item_tag.1).into());
     }
 
-    // 保存图像到文件
-    img.save("./images/output.png")?;
+    let item_length = read_u32(&buffer[offset..], little_endian)? as usize;
 
-    Ok(())
-}
+    offset += 4;
 
-// 具体的实现一句参考下方链接里的三个表格
-// https://dicom.nema.org/dicom/2013/output/chtml/part05/sect_7.5.html
-fn parse_sq_data(buffer: &Vec<u8>) -> CommonResult<(crate::model::DicomValue, usize)> {
-    let mut offset = 0;
+    // 好像一个item value data set中是可以包含多个element的
+    let mut sub_elements = Vec::new();
 
-    let data_element_length = u32::from_le_bytes(buffer[offset..offset + 4].try_into()?) as usize;
+    if item_length == 0xffffffff {
+        // 未定义长度的item以Item Delim. Tag (FFFE,E00D)结束
+        loop {
+            if offset + 8 > buffer.len() {
+                return Err("Item Delim. Tag not found".into());
+            }
 
-    // 这里是跳过4字节的data element length
-    offset += 4;
+            if read_tag(&buffer[offset..], little_endian)? == (0xfffe, 0xe00d) {
+                offset += 8;
+                break;
+            }
 
-    // 用于记录SQ实际存储内容的buffer
-    // 已经去除前后的表示帧界的特定序列
-    // 只包含多个item的数据
-    let data_value_buffer;
+            let result = get_data_element_with_options(&buffer[offset..], options)?;
 
-    if data_element_length == 0xffffffff {
-        let sequence = crate::util::swap_every_two_bytes(&vec![
-            0xFF, 0xFE, 0xE0, 0xDD, 0x00, 0x00, 0x00, 0x00,
-        ]);
-
-        if let Some(index) = buffer[offset..]
-            .windows(sequence.len())
-            .position(|window| window == sequence)
-        {
-            // 这里计算index因为是基于offset做过偏移了
-            // 所以在获取数据的时候是需要在终止点上加上offset的
-            data_value_buffer = buffer[offset..offset + index].to_vec();
-
-            // 因为windows这个函数的匹配不会把sequence本身的长度算进去
-            // 所以这里SQ真正的中止点需要加上sequence的长度
-            offset += index + sequence.len();
-        } else {
-            panic!("Seq. Delim. Tag not found");
+            sub_elements.push(result.0);
+            offset += result.1;
         }
     } else {
-        // 如果不是8f，那么data_element_length就是实际的长度
-        // 这里是长度的4字节
-        data_value_buffer = buffer[offset..offset + data_element_length as usize].to_vec();
+        let end = offset + item_length;
+
+        if end > buffer.len() {
+            return Err(format!(
+                "item length {} exceeds the remaining {} bytes",
+                item_length,
+                buffer.len() - offset
+            )
+            .into());
+        }
+
+        while offset < end {
+            let result = get_data_element_with_options(&buffer[offset..end], options)?;
 
-        offset += data_element_length;
+            sub_elements.push(result.0);
+            offset += result.1;
+        }
     }
 
-    // println!("data_value_buffer {:?}", &data_value_buffer);
-    let sub_elements = crate::model::DicomValue::Sequence(parse_sq_items(&data_value_buffer)?);
+    resolve_private_elements(&mut sub_elements, options)?;
 
-    Ok((sub_elements, offset))
+    Ok((sub_elements, item_length == 0xffffffff, offset))
 }
 
-fn parse_sq_items(buffer: &Vec<u8>) -> CommonResult<Vec<crate::model::DataElement>> {
+// 封装格式的像素数据由若干个item组成，以Seq. Delim. Tag (FFFE,E0DD)结束
+// 第一个item是Basic Offset Table（可能为空），之后每个item是一个fragment
+// https://dicom.nema.org/medical/dicom/current/output/chtml/part05/sect_A.4.html
+fn parse_fragments(
+    buffer: &[u8],
+    little_endian: bool,
+) -> CommonResult<(crate::model::DicomValue, usize)> {
     let mut offset = 0;
-
-    // 因为sq中存储类似element的数组
-    let mut sub_elements = Vec::new();
+    let mut fragments = Vec::new();
 
     loop {
-        if offset >= buffer.len() {
-            break;
+        if offset + 8 > buffer.len() {
+            return Err("Seq. Delim. Tag not found in encapsulated pixel data".into());
         }
 
-        let item_tag_string =
-            crate::util::swap_every_two_bytes_and_echo_string(&buffer[offset..offset + 4].to_vec());
+        let item_tag = read_tag(&buffer[offset..], little_endian)?;
+        let item_length = read_u32(&buffer[offset + 4..], little_endian)? as usize;
 
-        offset += 4;
+        offset += 8;
 
-        if item_tag_string != "FFFEE000".to_string() {
-            panic!("item tag is invalid");
+        if item_tag == (0xfffe, 0xe0dd) {
+            break;
         }
 
-        let mut item_length = u32::from_le_bytes(buffer[offset..offset + 4].try_into()?) as usize;
-        let mut item_content_buffer = Vec::new();
-
-        offset += 4;
-
-        if item_length == 0xffffffff {
-            let sequence = crate::util::swap_every_two_bytes(&vec![
-                0xFF, 0xFE, 0xE0, 0x0D, 0x00, 0x00, 0x00, 0x00,
-            ]);
-
-            if let Some(index) = buffer[offset..]
-                .windows(sequence.len())
-                .position(|window| window == sequence)
-            {
-                item_length = index as usize;
-
-                // 这里计算index因为是基于offset做过偏移了
-                // 所以在获取数据的时候是需要在终止点上加上offset的
-                item_content_buffer = buffer[offset..offset + item_length].to_vec();
-
-                // 因为windows这个函数的匹配不会把sequence本身的长度算进去
-                // 所以这里SQ真正的中止点需要加上sequence的长度
-                offset += index + sequence.len();
-            } else {
-                panic!("Seq. Delim. Tag not found");
-            }
-        } else {
-            println!("这段代码未经过测试，alpha");
-            item_content_buffer = buffer[offset..offset + item_length].to_vec();
-
-            offset += item_length as usize;
+        if item_tag != (0xfffe, 0xe000) {
+            return Err(format!(
+                "fragment tag is invalid: {:04X}{:04X}",
+                item_tag.0, item_tag.1
+            )
+            .into());
         }
 
-        // 好像一个item value data set中是可以包含多个element的
-        let mut item_offset = 0;
-
-        loop {
-            let result = get_data_element(&item_content_buffer[item_offset..].to_vec())?;
-
-            let data_element = result.0;
-            let consumed_bytes = result.1;
-
-            sub_elements.push(data_element);
-            item_offset += consumed_bytes;
-
-            if item_offset >= item_content_buffer.len() {
-                break;
-            }
+        if offset + item_length > buffer.len() {
+            return Err(format!("fragment length {} is out of range", item_length).into());
         }
+
+        fragments.push(buffer[offset..offset + item_length].to_vec());
+        offset += item_length;
     }
 
-    Ok(sub_elements)
+    Ok((crate::model::DicomValue::Fragments(fragments), offset))
 }
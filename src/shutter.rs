@@ -0,0 +1,175 @@
+use crate::{
+    accessor,
+    model::{DataElement, DisplayShutter, Shutter},
+    CommonResult,
+};
+
+fn pairs(values: Vec<i64>, tag: &str) -> CommonResult<Vec<(i64, i64)>> {
+    if !values.len().is_multiple_of(2) {
+        return Err(format!("{} has VM {}, expected pairs", tag, values.len()).into());
+    }
+
+    Ok(values.chunks(2).map(|v| (v[0], v[1])).collect())
+}
+
+// 读取Display Shutter模块，Shutter Shape (0018,1600)可以同时有多个值
+pub fn get_shutters(data_elements: &[DataElement]) -> CommonResult<Vec<Shutter>> {
+    if !accessor::has_value(data_elements, "0018,1600") {
+        return Ok(Vec::new());
+    }
+
+    let mut result = Vec::new();
+
+    for shape in accessor::get_strs(data_elements, "0018,1600")? {
+        match shape.as_str() {
+            "RECTANGULAR" => result.push(Shutter::Rectangular {
+                left: accessor::get_i64(data_elements, "0018,1602")?,
+                right: accessor::get_i64(data_elements, "0018,1604")?,
+                upper: accessor::get_i64(data_elements, "0018,1606")?,
+                lower: accessor::get_i64(data_elements, "0018,1608")?,
+            }),
+            "CIRCULAR" => {
+                let center = pairs(accessor::get_i64s(data_elements, "0018,1610")?, "0018,1610")?;
+
+                result.push(Shutter::Circular {
+                    center: *center.first().ok_or("0018,1610 is empty")?,
+                    radius: accessor::get_i64(data_elements, "0018,1612")?,
+                });
+            }
+            "POLYGONAL" => result.push(Shutter::Polygonal(pairs(
+                accessor::get_i64s(data_elements, "0018,1620")?,
+                "0018,1620",
+            )?)),
+            "BITMAP" => {
+                let group = accessor::get_u16(data_elements, "0018,1623")?;
+
+                result.push(Shutter::Bitmap(crate::overlay::get_overlay(
+                    data_elements,
+                    group,
+                )?));
+            }
+            _ => return Err(format!("shutter shape {} is not supported", shape).into()),
+        }
+    }
+
+    Ok(result)
+}
+
+// 读取shutter以及Shutter Presentation Value和Shutter Presentation Color CIELab Value
+pub fn get_display_shutter(data_elements: &[DataElement]) -> CommonResult<DisplayShutter> {
+    let presentation_value = if accessor::has_value(data_elements, "0018,1622") {
+        accessor::get_u16(data_elements, "0018,1622")?
+    } else {
+        0
+    };
+
+    let presentation_color = if accessor::has_value(data_elements, "0018,1624") {
+        match accessor::get_u16s(data_elements, "0018,1624")?[..] {
+            [l, a, b] => Some(crate::color::cielab_to_rgb([l, a, b])),
+            _ => return Err("0018,1624 should have 3 values".into()),
+        }
+    } else {
+        None
+    };
+
+    Ok(DisplayShutter {
+        shutters: get_shutters(data_elements)?,
+        presentation_value,
+        presentation_color,
+    })
+}
+
+// 点(row, column)（从1开始）是否在shutter的开口内
+fn is_inside(shutter: &Shutter, row: i64, column: i64) -> bool {
+    match shutter {
+        Shutter::Rectangular {
+            left,
+            right,
+            upper,
+            lower,
+        } => column >= *left && column <= *right && row >= *upper && row <= *lower,
+        Shutter::Circular { center, radius } => {
+            (row - center.0).pow(2) + (column - center.1).pow(2) <= radius.pow(2)
+        }
+        Shutter::Polygonal(vertices) => is_inside_polygon(vertices, row, column),
+        Shutter::Bitmap(overlay) => {
+            // 只使用overlay的第一帧，overlay之外的区域不遮挡
+            let overlay_row = row - overlay.origin.0 as i64;
+            let overlay_column = column - overlay.origin.1 as i64;
+
+            if overlay_row < 0
+                || overlay_column < 0
+                || overlay_row >= overlay.rows as i64
+                || overlay_column >= overlay.columns as i64
+            {
+                return true;
+            }
+
+            !overlay.data[(overlay_row * overlay.columns as i64 + overlay_column) as usize]
+        }
+    }
+}
+
+// 射线法判断点是否在多边形内，边上的点也算在多边形内
+fn is_inside_polygon(vertices: &[(i64, i64)], row: i64, column: i64) -> bool {
+    let mut inside = false;
+
+    for index in 0..vertices.len() {
+        let (row_a, column_a) = vertices[index];
+        let (row_b, column_b) = vertices[(index + 1) % vertices.len()];
+
+        // 点在这条边上
+        let cross = (row_b - row_a) * (column - column_a) - (column_b - column_a) * (row - row_a);
+        if cross == 0
+            && row >= row_a.min(row_b)
+            && row <= row_a.max(row_b)
+            && column >= column_a.min(column_b)
+            && column <= column_a.max(column_b)
+        {
+            return true;
+        }
+
+        if (row_a > row) != (row_b > row) {
+            let intersection = column_a as f64
+                + (row - row_a) as f64 * (column_b - column_a) as f64 / (row_b - row_a) as f64;
+
+            if (column as f64) < intersection {
+                inside = !inside;
+            }
+        }
+    }
+
+    inside
+}
+
+// 每个像素是否可见（位于所有shutter的开口之内），按行优先的顺序排列
+pub fn shutter_mask(shutters: &[Shutter], rows: u16, columns: u16) -> Vec<bool> {
+    let mut result = Vec::with_capacity(rows as usize * columns as usize);
+
+    for row in 1..=rows as i64 {
+        for column in 1..=columns as i64 {
+            result.push(
+                shutters
+                    .iter()
+                    .all(|shutter| is_inside(shutter, row, column)),
+            );
+        }
+    }
+
+    result
+}
+
+// 按照文件中的Rows和Columns生成shutter的mask，没有shutter时返回None
+pub fn get_shutter_mask(data_elements: &[DataElement]) -> CommonResult<Option<Vec<bool>>> {
+    let shutters = get_shutters(data_elements)?;
+
+    if shutters.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(shutter_mask(
+        &shutters,
+        accessor::get_u16(data_elements, "0028,0010")?,
+        accessor::get_u16(data_elements, "0028,0011")?,
+    )))
+}
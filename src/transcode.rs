@@ -0,0 +1,149 @@
+use crate::{
+    model::{DataElement, DicomValue, TransferSyntax},
+    CommonResult,
+};
+
+// 读取文件，转换成目标传输语法后写到另一个文件
+pub fn transcode_file(
+    input_path: &str,
+    output_path: &str,
+    transfer_syntax: TransferSyntax,
+) -> CommonResult<()> {
+    let data_elements = crate::service::read_file(input_path)?;
+
+    let data_elements = transcode(&data_elements, transfer_syntax)?;
+
+    let options = crate::writer::WriteOptions {
+        transfer_syntax,
+        ..Default::default()
+    };
+
+    crate::writer::write_file(output_path, &data_elements, &options)
+}
+
+// 把解析出来的数据元素转换成目标传输语法
+// 隐式/显式、大端/小端之间的转换只需要修改0002,0010，写出时按照目标传输语法编码
+// 像素数据在非压缩和封装格式之间转换时需要解码/编码，并修改对应的Image Pixel属性
+pub fn transcode(
+    data_elements: &[DataElement],
+    transfer_syntax: TransferSyntax,
+) -> CommonResult<Vec<DataElement>> {
+    let source_uid = crate::accessor::get_str(data_elements, "0002,0010")?;
+    let source = TransferSyntax::from_uid(&source_uid)
+        .ok_or_else(|| format!("transfer syntax {} is not supported", source_uid))?;
+
+    let mut result = data_elements.to_vec();
+
+    crate::util::set_data_element(
+        &mut result,
+        crate::util::new_data_element(
+            "0002,0010",
+            "UI",
+            DicomValue::String(transfer_syntax.uid().to_string()),
+        )?,
+    );
+
+    let has_pixel_data = result.iter().any(|v| v.tag == "7FE0,0010");
+
+    // 没有像素数据，或者像素数据的格式不需要改变
+    if !has_pixel_data
+        || source == transfer_syntax
+        || (!source.is_encapsulated() && !transfer_syntax.is_encapsulated())
+    {
+        return Ok(result);
+    }
+
+    if !crate::codec::can_decode(source) {
+        return Err(format!("no decoder for transfer syntax {:?}", source).into());
+    }
+
+    if !crate::codec::can_encode(transfer_syntax) {
+        return Err(format!("no encoder for transfer syntax {:?}", transfer_syntax).into());
+    }
+
+    let description = crate::pixel::get_pixel_description(&result)?;
+
+    let pixel_data = crate::accessor::find_data_element(&result, "7FE0,0010")?;
+
+    // 先统一解码成非压缩的帧
+    let frames = match (&pixel_data.data, source.is_encapsulated()) {
+        (DicomValue::Fragments(fragments), true) => {
+            crate::pixel::encapsulated_frames(fragments, description.number_of_frames)?
+                .iter()
+                .map(|frame| crate::codec::decode_frame(source, frame, &description))
+                .collect::<CommonResult<Vec<Vec<u8>>>>()?
+        }
+        (DicomValue::Bytes(pixels), false) => crate::pixel::native_frames(pixels, &description)?,
+        _ => {
+            return Err(format!(
+                "pixel data ({}) does not match transfer syntax {:?}",
+                pixel_data.vr, source
+            )
+            .into())
+        }
+    };
+
+    // 解码后的数据都是交错存储的，JPEG解码得到的彩色图像是RGB
+    let mut description = crate::codec::decoded_description(source, &description);
+
+    let pixel_data_element = if transfer_syntax.is_encapsulated() {
+        // YBR_FULL_422在非压缩格式下是降采样后的布局，RLE中不允许使用
+        if description.photometric_interpretation == "YBR_FULL_422" {
+            return Err("YBR_FULL_422 can not be encoded as RLE".into());
+        }
+
+        let encoded_frames = frames
+            .iter()
+            .map(|frame| crate::codec::encode_frame(transfer_syntax, frame, &description))
+            .collect::<CommonResult<Vec<Vec<u8>>>>()?;
+
+        // 编码时已经按照平面配置读取了数据，RLE中的平面配置固定为0
+        description.planar_configuration = 0;
+
+        crate::util::new_data_element(
+            "7FE0,0010",
+            "OB",
+            DicomValue::Fragments(crate::pixel::encapsulate_frames(&encoded_frames)),
+        )?
+    } else {
+        let vr = if description.bits_allocated > 8 {
+            "OW"
+        } else {
+            "OB"
+        };
+
+        crate::util::new_data_element("7FE0,0010", vr, DicomValue::Bytes(frames.concat()))?
+    };
+
+    crate::util::set_data_element(&mut result, pixel_data_element);
+
+    crate::util::set_data_element(
+        &mut result,
+        crate::util::new_data_element(
+            "0028,0004",
+            "CS",
+            DicomValue::String(description.photometric_interpretation.clone()),
+        )?,
+    );
+
+    if description.samples_per_pixel > 1 {
+        crate::util::set_data_element(
+            &mut result,
+            crate::util::new_data_element(
+                "0028,0006",
+                "US",
+                DicomValue::U16(vec![description.planar_configuration]),
+            )?,
+        );
+    }
+
+    // 有损压缩过的图像即使解压之后也必须标记出来
+    if source.is_lossy() {
+        crate::util::set_data_element(
+            &mut result,
+            crate::util::new_data_element("0028,2110", "CS", DicomValue::String("01".to_string()))?,
+        );
+    }
+
+    Ok(result)
+}
@@ -6,7 +6,10 @@ use std::{
 
 use regex::Regex;
 
-use crate::CommonResult;
+use crate::{
+    model::{NumericParseMode, PrivateDictionary, PrivateTag, ValueError},
+    CommonResult,
+};
 
 pub fn get_file(file_path: &str) -> CommonResult<File> {
     let f = OpenOptions::new().read(true).open(file_path)?;
@@ -16,7 +19,7 @@ pub fn get_file(file_path: &str) -> CommonResult<File> {
 
 // 因为从buffer中读取到的就是一个u8
 // 需要把u8补上前导0，然后拼接在一起
-pub fn process_vec_to_tag(buffer: &Vec<u8>) -> String {
+pub fn process_vec_to_tag(buffer: &[u8]) -> String {
     let result = buffer
         .iter()
         .map(|ele| format!("{:02X}", ele))
@@ -25,7 +28,7 @@ pub fn process_vec_to_tag(buffer: &Vec<u8>) -> String {
     result.join("")
 }
 
-pub fn process_vec_to_vr(buffer: &Vec<u8>) -> String {
+pub fn process_vec_to_vr(buffer: &[u8]) -> String {
     let result = buffer
         .iter()
         .map(|ele| (ele.to_owned() as char).to_string())
@@ -51,6 +54,8 @@ fn vr_mapping() -> Vec<(String, String)> {
         ("OB", "Other Byte String"),
         ("OD", "Other Double String"),
         ("OF", "Other Float String"),
+        ("OL", "Other Long"),
+        ("OV", "Other 64-bit Very Long"),
         ("OW", "Other Word String"),
         ("PN", "Person Name"),
         ("SH", "Short String"),
@@ -58,12 +63,19 @@ fn vr_mapping() -> Vec<(String, String)> {
         ("SQ", "Sequence of Items"),
         ("SS", "Signed Short"),
         ("ST", "Short Text"),
+        ("SV", "Signed 64-bit Very Long"),
         ("TM", "Time"),
+        ("UC", "Unlimited Characters"),
         ("UI", "Unique Identifier (UID)"),
         ("UL", "Unsigned Long"),
         ("UN", "Unknown"),
+        (
+            "UR",
+            "Universal Resource Identifier or Universal Resource Locator (URI/URL)",
+        ),
         ("US", "Unsigned Short"),
         ("UT", "Unlimited Text"),
+        ("UV", "Unsigned 64-bit Very Long"),
     ];
 
     let result = mapping
@@ -85,7 +97,15 @@ pub fn get_vr_values() -> Vec<String> {
     result
 }
 
-pub fn swap_every_two_bytes_and_echo_string(bytes: &Vec<u8>) -> String {
+// 显式vr中长度字段为4字节（前面带2字节保留）的vr
+pub fn is_long_length_vr(vr: &str) -> bool {
+    [
+        "OB", "OD", "OF", "OL", "OV", "OW", "SQ", "SV", "UC", "UN", "UR", "UT", "UV",
+    ]
+    .contains(&vr)
+}
+
+pub fn swap_every_two_bytes_and_echo_string(bytes: &[u8]) -> String {
     let mut result = Vec::new();
 
     let mut counter = 0;
@@ -116,7 +136,7 @@ pub fn swap_every_two_bytes_and_echo_string(bytes: &Vec<u8>) -> String {
     result.join("")
 }
 
-pub fn swap_every_two_bytes(bytes: &Vec<u8>) -> Vec<u8> {
+pub fn swap_every_two_bytes(bytes: &[u8]) -> Vec<u8> {
     let mut result = Vec::new();
 
     let mut counter = 0;
@@ -148,7 +168,7 @@ pub fn swap_every_two_bytes(bytes: &Vec<u8>) -> Vec<u8> {
 }
 
 pub fn get_data_element_via_tag(
-    data_elements: &Vec<crate::model::DataElement>,
+    data_elements: &[crate::model::DataElement],
     tag: String,
 ) -> Option<crate::model::DataElement> {
     for data_element in data_elements {
@@ -160,9 +180,66 @@ pub fn get_data_element_via_tag(
     None
 }
 
-pub fn get_tag_human_name(tag: &String) -> CommonResult<String> {
+// 根据tag（形如"0028,0010"）构造一个新的数据元素，用于修改或者补充头信息
+pub fn new_data_element(
+    tag: &str,
+    vr: &str,
+    data: crate::model::DicomValue,
+) -> CommonResult<crate::model::DataElement> {
+    let (tag_group, tag_element) = tag
+        .split_once(',')
+        .ok_or_else(|| format!("invalid tag: {}", tag))?;
+
+    Ok(crate::model::DataElement {
+        tag_group: tag_group.to_uppercase(),
+        tag_element: tag_element.to_uppercase(),
+        tag: tag.to_uppercase(),
+        tag_for_human: get_tag_human_name(&tag.to_uppercase())?,
+        vr: vr.to_string(),
+        data,
+        value_errors: Vec::new(),
+        original_encoding: None,
+    })
+}
+
+// 如果已经存在相同tag的数据元素则替换，否则追加
+pub fn set_data_element(
+    data_elements: &mut Vec<crate::model::DataElement>,
+    data_element: crate::model::DataElement,
+) {
+    match data_elements.iter_mut().find(|v| v.tag == data_element.tag) {
+        Some(v) => *v = data_element,
+        None => data_elements.push(data_element),
+    }
+}
+
+// 把"0028,0010"这样的tag转换成(group, element)
+pub fn parse_tag(tag: &str) -> CommonResult<(u16, u16)> {
+    let (group, element) = tag
+        .split_once(',')
+        .ok_or_else(|| format!("invalid tag: {}", tag))?;
+
+    Ok((
+        u16::from_str_radix(group, 16)?,
+        u16::from_str_radix(element, 16)?,
+    ))
+}
+
+pub fn get_tag_human_name(tag: &str) -> CommonResult<String> {
     let mut result = "unknown".to_string();
 
+    // 私有组（奇数group）不能按照标准字典查询，否则6001,0010会被60xx,0010匹配成Overlay Rows
+    // 私有数据元素的名字要结合Private Creator在私有字典中查询，见accessor::get_private_tag
+    if let Ok((group, element)) = parse_tag(tag) {
+        if group % 2 == 1 && element != 0x0000 {
+            if (0x0010..=0x00ff).contains(&element) {
+                result = "Private Creator".to_string();
+            }
+
+            return Ok(result);
+        }
+    }
+
     let _result = crate::FULL_MATCH_MAPPING.get(tag);
 
     if _result.is_some() {
@@ -219,7 +296,285 @@ pub fn load_and_convert_tag_mapping(
     Ok((full_match_mapping, partial_match_mapping))
 }
 
-pub fn show_buffer_by_hex(buffer: &Vec<u8>) {
+// 读取tag_vr_mapping.txt，每行是"tag\tVR"
+// 隐式vr的文件中没有vr，需要通过这个字典查出标准中规定的vr
+pub fn load_tag_vr_mapping() -> CommonResult<HashMap<String, String>> {
+    let file = get_file("./tag_vr_mapping.txt")?;
+
+    let mut vr_mapping = HashMap::new();
+
+    let reader = io::BufReader::new(file);
+
+    for line in reader.lines() {
+        let text = line?;
+
+        if let Some((standard_tag, standard_vr)) = text.split_once('\t') {
+            vr_mapping.insert(standard_tag.to_string(), standard_vr.trim().to_string());
+        }
+    }
+
+    Ok(vr_mapping)
+}
+
+// 查询tag在标准中的vr，查不到时返回None
+// 有多种可能的vr（US/SS、OB/OW）时字典中只记录了其中一种
+pub fn get_standard_vr(tag: &str) -> Option<String> {
+    let (group, element) = parse_tag(tag).ok()?;
+
+    // 每个group的group length都是UL
+    if element == 0x0000 {
+        return Some("UL".to_string());
+    }
+
+    // 私有组中的Private Creator固定是LO
+    if group % 2 == 1 && (0x0010..=0x00ff).contains(&element) {
+        return Some("LO".to_string());
+    }
+
+    if let Some(vr) = crate::VR_MAPPING.get(tag) {
+        return Some(vr.to_string());
+    }
+
+    // 重复组（曲线50xx、覆盖层60xx）在字典中写成50xx,eeee的形式
+    if (0x5000..=0x50ff).contains(&group) || (0x6000..=0x60ff).contains(&group) {
+        let repeating_tag = format!("{:02X}xx,{:04X}", group >> 8, element);
+
+        return crate::VR_MAPPING.get(&repeating_tag).cloned();
+    }
+
+    None
+}
+
+// 解析私有字典，每行是"gggg,xxee\tPrivate Creator\tVR\t名字"，空行和#开头的行会被忽略
+// xx是Private Creator保留的block，不同的文件中可能不同，所以字典中只记录element的低8位
+pub fn parse_private_dictionary(content: &str) -> CommonResult<PrivateDictionary> {
+    let mut dictionary = PrivateDictionary::new();
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = line.split('\t').collect::<Vec<&str>>();
+
+        let [tag, creator, vr, name] = fields[..] else {
+            return Err(format!(
+                "line {} of private dictionary has {} fields",
+                index + 1,
+                fields.len()
+            )
+            .into());
+        };
+
+        let (group, element_offset) = tag
+            .split_once(',')
+            .and_then(|(group, element)| {
+                let element_offset = element.strip_prefix("xx")?;
+
+                Some((
+                    u16::from_str_radix(group, 16).ok()?,
+                    u8::from_str_radix(element_offset, 16).ok()?,
+                ))
+            })
+            .filter(|(group, _)| group % 2 == 1)
+            .ok_or_else(|| format!("invalid private tag {} at line {}", tag, index + 1))?;
+
+        dictionary.insert(
+            (group, creator.to_string(), element_offset),
+            PrivateTag {
+                group,
+                creator: creator.to_string(),
+                element_offset,
+                vr: vr.trim().to_string(),
+                name: name.trim().to_string(),
+            },
+        );
+    }
+
+    Ok(dictionary)
+}
+
+pub fn load_private_dictionary(file_path: &str) -> CommonResult<PrivateDictionary> {
+    parse_private_dictionary(&std::fs::read_to_string(file_path)?)
+}
+
+// 把其他的私有字典合并到默认的私有字典中，相同的项会被覆盖，返回读取到的项数
+pub fn add_private_dictionary(file_path: &str) -> CommonResult<usize> {
+    let dictionary = load_private_dictionary(file_path)?;
+    let count = dictionary.len();
+
+    crate::PRIVATE_DICTIONARY
+        .write()
+        .map_err(|_| "private dictionary is poisoned")?
+        .extend(dictionary);
+
+    Ok(count)
+}
+
+// 按照(group, Private Creator, element的低8位)查询私有字典，Private Creator首尾的空格会被忽略
+pub fn get_private_tag(group: u16, creator: &str, element_offset: u8) -> Option<PrivateTag> {
+    let key = (
+        group,
+        creator
+            .trim_matches(|c: char| c == ' ' || c == '\0')
+            .to_string(),
+        element_offset,
+    );
+
+    crate::PRIVATE_DICTIONARY.read().ok()?.get(&key).cloned()
+}
+
+// 将DS/IS的原始字符串按照\\拆分成多个值
+// 返回每个值在原始字符串中的位置、原始内容以及去除填充后的内容
+fn split_numeric_values(raw: &str, mode: NumericParseMode) -> Vec<(usize, String, String)> {
+    raw.split('\\')
+        .enumerate()
+        .map(|(index, part)| {
+            let value = match mode {
+                NumericParseMode::Lenient => part
+                    .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+                    .to_string(),
+                NumericParseMode::Strict => part.trim_matches(' ').to_string(),
+            };
+
+            (index, part.to_string(), value)
+        })
+        .collect()
+}
+
+fn value_error(index: usize, raw: &str, message: &str) -> ValueError {
+    ValueError {
+        index,
+        raw: raw.to_string(),
+        message: message.to_string(),
+    }
+}
+
+// 解析DS(Decimal String)
+// 参考 https://dicom.nema.org/medical/dicom/current/output/chtml/part05/sect_6.2.html
+// 每个值最多16字节，只能包含0-9、+、-、E、e、.以及首尾的空格
+pub fn parse_decimal_strings(raw: &str, mode: NumericParseMode) -> (Vec<f64>, Vec<ValueError>) {
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+
+    // 整个值为空时VM为0，不算错误
+    if raw
+        .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+        .is_empty()
+    {
+        return (values, errors);
+    }
+
+    for (index, part, value) in split_numeric_values(raw, mode) {
+        if value.is_empty() {
+            if mode == NumericParseMode::Strict {
+                errors.push(value_error(index, &part, "empty value"));
+            }
+            continue;
+        }
+
+        if mode == NumericParseMode::Strict && part.len() > 16 {
+            errors.push(value_error(index, &part, "DS value exceeds 16 bytes"));
+            continue;
+        }
+
+        if !value
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-Ee.".contains(c))
+        {
+            errors.push(value_error(index, &part, "invalid character in DS value"));
+            continue;
+        }
+
+        match value.parse::<f64>() {
+            Ok(v) if v.is_finite() => values.push(v),
+            Ok(_) => errors.push(value_error(index, &part, "DS value out of range")),
+            Err(_) => errors.push(value_error(index, &part, "not a decimal number")),
+        }
+    }
+
+    (values, errors)
+}
+
+// 解析IS(Integer String)
+// 每个值最多12字节，只能包含0-9、+、-以及首尾的空格，取值范围是-2^31 ~ 2^31-1
+// 宽松模式下允许超出i32的范围（只要在i64以内），也允许"12.0"这种小数部分为0的写法
+pub fn parse_integer_strings(raw: &str, mode: NumericParseMode) -> (Vec<i64>, Vec<ValueError>) {
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+
+    if raw
+        .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+        .is_empty()
+    {
+        return (values, errors);
+    }
+
+    for (index, part, value) in split_numeric_values(raw, mode) {
+        if value.is_empty() {
+            if mode == NumericParseMode::Strict {
+                errors.push(value_error(index, &part, "empty value"));
+            }
+            continue;
+        }
+
+        match mode {
+            NumericParseMode::Strict => {
+                if part.len() > 12 {
+                    errors.push(value_error(index, &part, "IS value exceeds 12 bytes"));
+                    continue;
+                }
+
+                if !value
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || "+-".contains(c))
+                {
+                    errors.push(value_error(index, &part, "invalid character in IS value"));
+                    continue;
+                }
+
+                match value.parse::<i64>() {
+                    Ok(v) if i32::try_from(v).is_ok() => values.push(v),
+                    Ok(_) => errors.push(value_error(index, &part, "IS value out of range")),
+                    Err(_) => errors.push(value_error(index, &part, "not an integer")),
+                }
+            }
+            NumericParseMode::Lenient => {
+                if let Ok(v) = value.parse::<i64>() {
+                    values.push(v);
+                    continue;
+                }
+
+                // 可能是溢出，也可能是带小数点或指数的写法
+                if !value
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || "+-Ee.".contains(c))
+                {
+                    errors.push(value_error(index, &part, "not an integer"));
+                    continue;
+                }
+
+                match value.parse::<f64>() {
+                    Ok(v) if v.is_finite() && v.fract() == 0.0 => {
+                        if v >= i64::MIN as f64 && v < i64::MAX as f64 {
+                            values.push(v as i64);
+                        } else {
+                            errors.push(value_error(index, &part, "IS value out of range"));
+                        }
+                    }
+                    Ok(v) if !v.is_finite() => {
+                        errors.push(value_error(index, &part, "IS value out of range"))
+                    }
+                    _ => errors.push(value_error(index, &part, "not an integer")),
+                }
+            }
+        }
+    }
+
+    (values, errors)
+}
+
+pub fn show_buffer_by_hex(buffer: &[u8]) {
     let result = buffer
         .iter()
         .map(|v| format!("{:02X}", v))
@@ -0,0 +1,472 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    accessor, geometry,
+    model::{
+        DataElement, ImageGeometry, ModalityTransform, NpyData, SkippedFile, Volume, VolumeIssue,
+    },
+    CommonResult,
+};
+
+// 把同一个序列的单帧图像按照空间位置排列成体数据
+// 层的顺序由Image Position (Patient)在法线方向（row_direction × column_direction）上的投影决定，不依赖Instance Number
+
+// 法线方向上的距离小于这个值（mm）的两层认为位置相同
+const DUPLICATE_DISTANCE: f64 = 0.01;
+// 两层之间的距离超过层间距的这个倍数时认为中间缺了层
+const GAP_RATIO: f64 = 1.5;
+// 层的排列方向和法线的夹角超过这个值（度）时认为有机架倾斜
+const TILT_ANGLE: f64 = 0.01;
+
+// 一个序列中的所有文件和解析出的数据元素
+pub type SeriesFiles = Vec<(PathBuf, Vec<DataElement>)>;
+
+// 无法组成体数据的序列，(Series Instance UID, 原因)
+pub type SeriesFailures = Vec<(String, String)>;
+
+// (法线方向上的位置, 文件, 数据元素, 空间位置)
+type PositionedSlice = (f64, PathBuf, Vec<DataElement>, ImageGeometry);
+
+// 读取目录中的所有dicom图像，按照Series Instance UID (0020,000E)分组，没有Series Instance UID的文件分在空字符串一组
+// 无法解析和没有像素数据的文件会被跳过并和原因一起返回
+pub fn read_series<P: AsRef<Path>>(
+    directory: P,
+) -> CommonResult<(BTreeMap<String, SeriesFiles>, Vec<SkippedFile>)> {
+    let mut result = BTreeMap::new();
+    let mut skipped = Vec::new();
+
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if !path.is_file() {
+            continue;
+        }
+
+        let data_elements = match path
+            .to_str()
+            .ok_or_else(|| "file path is not valid utf-8".into())
+            .and_then(crate::service::read_file)
+        {
+            Ok(data_elements) => data_elements,
+            Err(e) => {
+                skipped.push(SkippedFile {
+                    file_path: path,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        if !accessor::has_value(&data_elements, "7FE0,0010") {
+            skipped.push(SkippedFile {
+                file_path: path,
+                reason: "no pixel data".to_string(),
+            });
+            continue;
+        }
+
+        let series_instance_uid = if accessor::has_value(&data_elements, "0020,000E") {
+            accessor::get_str(&data_elements, "0020,000E")?
+        } else {
+            String::new()
+        };
+
+        result
+            .entry(series_instance_uid)
+            .or_insert_with(Vec::new)
+            .push((path, data_elements));
+    }
+
+    Ok((result, skipped))
+}
+
+// 目录中的每个序列组成一个体数据，按照Series Instance UID排序
+// 无法组成体数据的序列（比如彩色的截图、剂量报告）不影响其他序列，和原因一起单独返回
+pub fn read_volumes<P: AsRef<Path>>(directory: P) -> CommonResult<(Vec<Volume>, SeriesFailures)> {
+    let (series, _) = read_series(directory)?;
+
+    if series.is_empty() {
+        return Err("no dicom image found in the directory".into());
+    }
+
+    let mut volumes = Vec::new();
+    let mut failures = Vec::new();
+
+    for (series_instance_uid, slices) in series {
+        match assemble_volume(&series_instance_uid, slices) {
+            Ok(volume) => volumes.push(volume),
+            Err(e) => failures.push((series_instance_uid, e.to_string())),
+        }
+    }
+
+    Ok((volumes, failures))
+}
+
+// 单帧灰度图像的空间位置
+fn slice_geometry(data_elements: &[DataElement]) -> CommonResult<ImageGeometry> {
+    let description = crate::pixel::get_pixel_description(data_elements)?;
+
+    if description.number_of_frames > 1 {
+        return Err(format!(
+            "multi-frame image with {} frames",
+            description.number_of_frames
+        )
+        .into());
+    }
+
+    if description.samples_per_pixel != 1
+        || !description
+            .photometric_interpretation
+            .starts_with("MONOCHROME")
+    {
+        return Err(format!(
+            "photometric interpretation {} is not grayscale",
+            description.photometric_interpretation
+        )
+        .into());
+    }
+
+    geometry::get_image_geometry(data_elements)
+}
+
+// 大小和方向都相同的两层可以放在同一个体数据中
+fn same_plane(a: &ImageGeometry, b: &ImageGeometry) -> bool {
+    a.rows == b.rows
+        && a.columns == b.columns
+        && geometry::dot(a.row_direction, b.row_direction) > 1.0 - 1e-4
+        && geometry::dot(a.column_direction, b.column_direction) > 1.0 - 1e-4
+        && (a.pixel_spacing.0 - b.pixel_spacing.0).abs() < 1e-4
+        && (a.pixel_spacing.1 - b.pixel_spacing.1).abs() < 1e-4
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut values = values.to_vec();
+    values.sort_by(|a, b| a.total_cmp(b));
+
+    values[values.len() / 2]
+}
+
+// 把各层的数据拼接起来，数据类型不同时返回None
+fn concat(arrays: Vec<NpyData>) -> Option<NpyData> {
+    let mut arrays = arrays.into_iter();
+    let mut result = arrays.next()?;
+
+    for array in arrays {
+        match (&mut result, array) {
+            (NpyData::U8(result), NpyData::U8(array)) => result.extend(array),
+            (NpyData::U16(result), NpyData::U16(array)) => result.extend(array),
+            (NpyData::I16(result), NpyData::I16(array)) => result.extend(array),
+            (NpyData::F32(result), NpyData::F32(array)) => result.extend(array),
+            _ => return None,
+        }
+    }
+
+    Some(result)
+}
+
+// 所有层的Rescale都相同时返回(slope, intercept)
+fn common_rescale(slices: &[(PathBuf, Vec<DataElement>)]) -> CommonResult<Option<(f64, f64)>> {
+    let mut result = None;
+
+    for (_, data_elements) in slices {
+        let description = crate::pixel::get_pixel_description(data_elements)?;
+
+        let rescale = match crate::lut::get_modality_transform(
+            data_elements,
+            description.pixel_representation,
+        )? {
+            ModalityTransform::Rescale { slope, intercept } => (slope, intercept),
+            ModalityTransform::Lut(_) => return Ok(None),
+        };
+
+        match result {
+            None => result = Some(rescale),
+            Some(value) if value != rescale => return Ok(None),
+            _ => {}
+        }
+    }
+
+    Ok(result)
+}
+
+// 把一个序列的所有层组成体数据，Siemens的mosaic先拆成单独的层，无法拆开的mosaic记录为Excluded
+// 大小、方向或者Pixel Spacing和大多数层不同的图像（比如同一个序列中的定位像）不放进体数据，记录为Excluded
+// 位置相同的层只保留排在前面的一个（位置完全相同时Instance Number小的排在前面），记录为Duplicate
+// 每个位置上的层数相同并且Acquisition Number各不相同时，组成按照Acquisition Number排列的时间序列
+// 层间距取相邻两层距离的中位数，缺层、层间距不一致和机架倾斜都记录在issues中，不会返回错误
+pub fn assemble_volume(series_instance_uid: &str, slices: SeriesFiles) -> CommonResult<Volume> {
+    let mut issues = Vec::new();
+    let mut candidates = Vec::new();
+
+    for (file_path, data_elements) in slices {
+        // Siemens的mosaic拆成单独的层，只有确定不是mosaic时才按照普通的图像处理
+        let images = match crate::mosaic::get_images_in_mosaic(&data_elements) {
+            Ok(None) => Ok(vec![data_elements]),
+            Ok(Some(_)) => crate::mosaic::unpack_mosaic(&data_elements),
+            Err(e) => Err(e),
+        };
+
+        let images = match images {
+            Ok(images) => images,
+            Err(e) => {
+                issues.push(VolumeIssue::Excluded {
+                    file_path,
+                    reason: format!("mosaic can not be unpacked: {}", e),
+                });
+                continue;
+            }
+        };
+
+        for data_elements in images {
+            match slice_geometry(&data_elements) {
+                Ok(geometry) => candidates.push((file_path.clone(), data_elements, geometry)),
+                Err(e) => issues.push(VolumeIssue::Excluded {
+                    file_path: file_path.clone(),
+                    reason: e.to_string(),
+                }),
+            }
+        }
+    }
+
+    // 以和其他层一致的数量最多的层为准
+    let reference = candidates
+        .iter()
+        .map(|(_, _, a)| {
+            (
+                candidates
+                    .iter()
+                    .filter(|(_, _, b)| same_plane(a, b))
+                    .count(),
+                a,
+            )
+        })
+        .rev()
+        .max_by_key(|(count, _)| *count)
+        .map(|(_, geometry)| geometry.clone())
+        .ok_or("no grayscale single-frame image with a valid geometry")?;
+
+    let normal = geometry::normal(&reference);
+    let mut sorted = Vec::new();
+
+    for (file_path, data_elements, geometry) in candidates {
+        if !same_plane(&reference, &geometry) {
+            issues.push(VolumeIssue::Excluded {
+                file_path,
+                reason: "size, orientation or pixel spacing differs from the other slices"
+                    .to_string(),
+            });
+            continue;
+        }
+
+        let distance = geometry::dot(geometry.position, normal);
+        let instance_number = accessor::get_i64(&data_elements, "0020,0013").ok();
+
+        sorted.push((
+            distance,
+            instance_number,
+            file_path,
+            data_elements,
+            geometry,
+        ));
+    }
+
+    sorted.sort_by(|a, b| {
+        a.0.total_cmp(&b.0)
+            .then_with(|| (a.1, &a.2).cmp(&(b.1, &b.2)))
+    });
+
+    // 按照位置分组，同一组中的层位置相同
+    let mut groups: Vec<Vec<PositionedSlice>> = Vec::new();
+
+    for (distance, _, file_path, data_elements, geometry) in sorted {
+        match groups.last_mut() {
+            Some(group) if distance - group[0].0 < DUPLICATE_DISTANCE => {
+                group.push((distance, file_path, data_elements, geometry))
+            }
+            _ => groups.push(vec![(distance, file_path, data_elements, geometry)]),
+        }
+    }
+
+    // 每个位置上的层数都相同，并且Acquisition Number (0020,0012)各不相同时，是多次采集的时间序列（比如fMRI）
+    let acquisition_number =
+        |data_elements: &[DataElement]| accessor::get_i64(data_elements, "0020,0012").ok();
+    let time_points = groups.first().map(|v| v.len()).unwrap_or(1);
+    let time_series = time_points > 1
+        && groups.iter().all(|group| {
+            let mut numbers = group
+                .iter()
+                .map(|v| acquisition_number(&v.2))
+                .collect::<Option<Vec<i64>>>()
+                .unwrap_or_default();
+
+            numbers.sort();
+            numbers.dedup();
+
+            group.len() == time_points && numbers.len() == time_points
+        });
+
+    if time_series {
+        for group in groups.iter_mut() {
+            group.sort_by_key(|v| acquisition_number(&v.2));
+        }
+    } else {
+        for group in groups.iter_mut() {
+            let kept = group[0].1.clone();
+
+            for (_, file_path, _, _) in group.drain(1..) {
+                issues.push(VolumeIssue::Duplicate {
+                    file_path,
+                    kept: kept.clone(),
+                });
+            }
+        }
+    }
+
+    let time_points = if time_series { time_points } else { 1 };
+    let kept = groups
+        .iter()
+        .map(|v| (v[0].0, v[0].3.clone()))
+        .collect::<Vec<(f64, ImageGeometry)>>();
+
+    let distances = kept
+        .windows(2)
+        .map(|v| v[1].0 - v[0].0)
+        .collect::<Vec<f64>>();
+
+    let slice_spacing = if distances.is_empty() {
+        reference
+            .spacing_between_slices
+            .or(reference.slice_thickness)
+            .filter(|v| *v > 0.0)
+            .unwrap_or(1.0)
+    } else {
+        median(&distances)
+    };
+
+    for (index, distance) in distances.iter().enumerate() {
+        let between = (index, index + 1);
+
+        if *distance > slice_spacing * GAP_RATIO {
+            issues.push(VolumeIssue::Gap {
+                between,
+                distance: *distance,
+                expected: slice_spacing,
+            });
+        } else if (distance - slice_spacing).abs() > (slice_spacing * 0.01).max(DUPLICATE_DISTANCE)
+        {
+            issues.push(VolumeIssue::InconsistentSpacing {
+                between,
+                distance: *distance,
+                expected: slice_spacing,
+            });
+        }
+    }
+
+    // 层的排列方向是第一层到最后一层的连线，机架倾斜时和法线不平行
+    // slice方向的向量在法线上的投影等于层间距
+    let mut slice_direction = normal;
+
+    if let (Some(first), Some(last)) = (kept.first(), kept.last()) {
+        let stacking = [0, 1, 2].map(|axis| last.1.position[axis] - first.1.position[axis]);
+
+        if let Some(stacking) = geometry::normalize(stacking).filter(|_| kept.len() > 1) {
+            let cosine = geometry::dot(stacking, normal).clamp(-1.0, 1.0);
+            let angle = cosine.acos().to_degrees();
+
+            if angle > TILT_ANGLE {
+                issues.push(VolumeIssue::GantryTilt { angle });
+            }
+
+            slice_direction = stacking.map(|v| v / cosine);
+        }
+    }
+
+    let first = kept[0].1.clone();
+    let (row_spacing, column_spacing) = first.pixel_spacing;
+
+    let mut affine = [[0.0, 0.0, 0.0, 1.0]; 4];
+
+    for axis in 0..3 {
+        affine[axis] = [
+            first.row_direction[axis] * column_spacing,
+            first.column_direction[axis] * row_spacing,
+            slice_direction[axis] * slice_spacing,
+            first.position[axis],
+        ];
+    }
+
+    // 时间序列按照时间、层的顺序排列
+    let mut slices = SeriesFiles::new();
+
+    for time_point in 0..time_points {
+        for group in groups.iter_mut() {
+            let (_, file_path, data_elements, _) = &mut group[time_point];
+
+            slices.push((std::mem::take(file_path), std::mem::take(data_elements)));
+        }
+    }
+
+    // Rescale不同的层不能共用一组slope和intercept，只能保存Modality LUT的输出值
+    let rescale = common_rescale(&slices)?;
+
+    let stored = match rescale {
+        Some(_) => concat(
+            slices
+                .iter()
+                .map(|(_, data_elements)| {
+                    crate::export::pixel_array(data_elements, false).map(|v| v.data)
+                })
+                .collect::<CommonResult<Vec<NpyData>>>()?,
+        ),
+        None => None,
+    };
+
+    let (data, (rescale_slope, rescale_intercept)) = match (stored, rescale) {
+        (Some(data), Some(rescale)) => (data, rescale),
+        _ => (
+            concat(
+                slices
+                    .iter()
+                    .map(|(_, data_elements)| {
+                        crate::export::pixel_array(data_elements, true).map(|v| v.data)
+                    })
+                    .collect::<CommonResult<Vec<NpyData>>>()?,
+            )
+            .ok_or("modality values should all be float32")?,
+            (1.0, 0.0),
+        ),
+    };
+
+    let attributes = slices[0]
+        .1
+        .iter()
+        .filter(|v| v.tag != "7FE0,0010")
+        .cloned()
+        .collect();
+
+    Ok(Volume {
+        series_instance_uid: series_instance_uid.to_string(),
+        shape: [kept.len(), first.rows as usize, first.columns as usize],
+        time_points,
+        data,
+        rescale_slope,
+        rescale_intercept,
+        spacing: [column_spacing, row_spacing, slice_spacing],
+        affine,
+        acquisition_times: slices
+            .iter()
+            .take(kept.len())
+            .map(|(_, data_elements)| {
+                accessor::get_str(data_elements, "0008,0032")
+                    .ok()
+                    .and_then(|v| accessor::parse_time(&v))
+            })
+            .collect(),
+        file_paths: slices.into_iter().map(|(file_path, _)| file_path).collect(),
+        attributes,
+        issues,
+    })
+}
@@ -0,0 +1,568 @@
+use crate::{
+    model::{DataElement, DicomValue, TransferSyntax},
+    CommonResult,
+};
+
+// 写文件时如果源文件中没有Implementation Class UID，就使用这个
+// 2.25开头的UID不需要注册组织根，参考PS3.5 B.2
+pub const IMPLEMENTATION_CLASS_UID: &str = "2.25.161173898236543011263316219640391858613";
+pub const IMPLEMENTATION_VERSION_NAME: &str = "DICOM_PARSER_01";
+
+#[derive(Debug, Clone, Copy)]
+pub struct WriteOptions {
+    pub transfer_syntax: TransferSyntax,
+    // 为true时SQ和item都使用未定义长度(0xFFFFFFFF)加分隔符的方式写出
+    // 从文件中读取的SQ会沿用原来的长度形式
+    pub undefined_sequence_length: bool,
+    // 128字节的导言，为None时全部填0
+    pub preamble: Option<[u8; 128]>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            transfer_syntax: TransferSyntax::ExplicitVrLittleEndian,
+            undefined_sequence_length: false,
+            preamble: None,
+        }
+    }
+}
+
+pub fn write_file(
+    file_path: &str,
+    data_elements: &[DataElement],
+    options: &WriteOptions,
+) -> CommonResult<()> {
+    let buffer = write_to_bytes(data_elements, options)?;
+
+    std::fs::write(file_path, buffer)?;
+
+    Ok(())
+}
+
+// 按照PS3.10的格式输出：128字节导言 + "DICM" + 文件元信息 + 数据集
+// data_elements中0002组的元素作为文件元信息，其余的作为数据集
+pub fn write_to_bytes(
+    data_elements: &[DataElement],
+    options: &WriteOptions,
+) -> CommonResult<Vec<u8>> {
+    let mut buffer = options.preamble.unwrap_or([0_u8; 128]).to_vec();
+    buffer.extend_from_slice(b"DICM");
+
+    let (meta_elements, dataset_elements): (Vec<DataElement>, Vec<DataElement>) = data_elements
+        .iter()
+        .cloned()
+        .partition(|data_element| data_element.tag_group == "0002");
+
+    let meta_elements = build_file_meta_group(meta_elements, &dataset_elements, options)?;
+
+    // 文件元信息固定使用显式小端
+    write_data_elements(
+        &meta_elements,
+        TransferSyntax::ExplicitVrLittleEndian,
+        options.undefined_sequence_length,
+        &mut buffer,
+    )?;
+
+    write_data_elements(
+        &dataset_elements,
+        options.transfer_syntax,
+        options.undefined_sequence_length,
+        &mut buffer,
+    )?;
+
+    Ok(buffer)
+}
+
+// 补齐文件元信息中必须存在的元素，并把传输语法改成实际写出时使用的传输语法
+fn build_file_meta_group(
+    mut meta_elements: Vec<DataElement>,
+    dataset_elements: &[DataElement],
+    options: &WriteOptions,
+) -> CommonResult<Vec<DataElement>> {
+    // group length的值会在写出时重新计算，这里只需要保证它存在
+    if !meta_elements.iter().any(|v| v.tag == "0002,0000") {
+        meta_elements.push(crate::util::new_data_element(
+            "0002,0000",
+            "UL",
+            DicomValue::U32(vec![0]),
+        )?);
+    }
+
+    if !meta_elements.iter().any(|v| v.tag == "0002,0001") {
+        meta_elements.push(crate::util::new_data_element(
+            "0002,0001",
+            "OB",
+            DicomValue::Bytes(vec![0x00, 0x01]),
+        )?);
+    }
+
+    // Media Storage SOP Class/Instance UID缺失时从数据集中的SOP Class/Instance UID获取
+    for (meta_tag, dataset_tag) in [("0002,0002", "0008,0016"), ("0002,0003", "0008,0018")] {
+        if !meta_elements.iter().any(|v| v.tag == meta_tag) {
+            if let Some(data_element) = dataset_elements.iter().find(|v| v.tag == dataset_tag) {
+                meta_elements.push(crate::util::new_data_element(
+                    meta_tag,
+                    "UI",
+                    data_element.data.clone(),
+                )?);
+            }
+        }
+    }
+
+    // 传输语法没有变化时保留原来的元素，避免丢失原始编码
+    let transfer_syntax_uid = DicomValue::String(options.transfer_syntax.uid().to_string());
+
+    if !meta_elements
+        .iter()
+        .any(|v| v.tag == "0002,0010" && v.data == transfer_syntax_uid)
+    {
+        crate::util::set_data_element(
+            &mut meta_elements,
+            crate::util::new_data_element("0002,0010", "UI", transfer_syntax_uid)?,
+        );
+    }
+
+    if !meta_elements.iter().any(|v| v.tag == "0002,0012") {
+        meta_elements.push(crate::util::new_data_element(
+            "0002,0012",
+            "UI",
+            DicomValue::String(IMPLEMENTATION_CLASS_UID.to_string()),
+        )?);
+        crate::util::set_data_element(
+            &mut meta_elements,
+            crate::util::new_data_element(
+                "0002,0013",
+                "SH",
+                DicomValue::String(IMPLEMENTATION_VERSION_NAME.to_string()),
+            )?,
+        );
+    }
+
+    Ok(meta_elements)
+}
+
+// 按照tag从小到大的顺序写出数据元素，(gggg,0000)的group length会重新计算
+pub fn write_data_elements(
+    data_elements: &[DataElement],
+    transfer_syntax: TransferSyntax,
+    undefined_sequence_length: bool,
+    buffer: &mut Vec<u8>,
+) -> CommonResult<()> {
+    let mut tagged_elements = data_elements
+        .iter()
+        .map(|data_element| Ok((crate::util::parse_tag(&data_element.tag)?, data_element)))
+        .collect::<CommonResult<Vec<_>>>()?;
+
+    tagged_elements.sort_by_key(|v| v.0);
+
+    let mut encoded_elements = Vec::new();
+
+    for ((group, element), data_element) in &tagged_elements {
+        let encoded = if *element == 0x0000 {
+            // 先占位，等同组的其他元素都编码完之后再计算
+            Vec::new()
+        } else {
+            encode_data_element(data_element, transfer_syntax, undefined_sequence_length)?
+        };
+
+        encoded_elements.push((*group, *element, encoded));
+    }
+
+    for index in 0..encoded_elements.len() {
+        let (group, element, _) = encoded_elements[index];
+
+        if element != 0x0000 {
+            continue;
+        }
+
+        let group_length = encoded_elements
+            .iter()
+            .filter(|v| v.0 == group && v.1 != 0x0000)
+            .map(|v| v.2.len())
+            .sum::<usize>();
+
+        let mut group_length_element = tagged_elements[index].1.clone();
+        group_length_element.vr = "UL".to_string();
+        group_length_element.data = DicomValue::U32(vec![u32::try_from(group_length)?]);
+
+        encoded_elements[index].2 = encode_data_element(
+            &group_length_element,
+            transfer_syntax,
+            undefined_sequence_length,
+        )?;
+    }
+
+    for (_, _, encoded) in encoded_elements {
+        buffer.extend_from_slice(&encoded);
+    }
+
+    Ok(())
+}
+
+fn write_tag(group: u16, element: u16, little_endian: bool, buffer: &mut Vec<u8>) {
+    if little_endian {
+        buffer.extend_from_slice(&group.to_le_bytes());
+        buffer.extend_from_slice(&element.to_le_bytes());
+    } else {
+        buffer.extend_from_slice(&group.to_be_bytes());
+        buffer.extend_from_slice(&element.to_be_bytes());
+    }
+}
+
+fn write_u32(value: u32, little_endian: bool, buffer: &mut Vec<u8>) {
+    if little_endian {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    } else {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+// 写出tag、vr（显式时）以及长度字段
+fn write_header(
+    group: u16,
+    element: u16,
+    vr: &str,
+    length: u32,
+    transfer_syntax: TransferSyntax,
+    buffer: &mut Vec<u8>,
+) -> CommonResult<()> {
+    let little_endian = transfer_syntax.is_little_endian();
+
+    write_tag(group, element, little_endian, buffer);
+
+    if !transfer_syntax.is_explicit_vr() {
+        write_u32(length, little_endian, buffer);
+        return Ok(());
+    }
+
+    buffer.extend_from_slice(vr.as_bytes());
+
+    if crate::util::is_long_length_vr(vr) {
+        // 2字节保留 + 4字节长度
+        buffer.extend_from_slice(&[0x00, 0x00]);
+        write_u32(length, little_endian, buffer);
+    } else {
+        let length = u16::try_from(length).map_err(|_| {
+            format!(
+                "({:04X},{:04X}) value length {} does not fit in the 2 byte length of {}",
+                group, element, length, vr
+            )
+        })?;
+
+        if little_endian {
+            buffer.extend_from_slice(&length.to_le_bytes());
+        } else {
+            buffer.extend_from_slice(&length.to_be_bytes());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn encode_data_element(
+    data_element: &DataElement,
+    transfer_syntax: TransferSyntax,
+    undefined_sequence_length: bool,
+) -> CommonResult<Vec<u8>> {
+    let (group, element) = crate::util::parse_tag(&data_element.tag)?;
+
+    // 隐式vr文件中读出的元素不知道实际的vr，显式写出时只能作为UN
+    let vr = if data_element.vr == "implicit" {
+        "UN"
+    } else {
+        data_element.vr.as_str()
+    };
+
+    let mut buffer = Vec::new();
+
+    let original_encoding = data_element.original_encoding.as_ref();
+
+    if let DicomValue::Sequence(items) = &data_element.data {
+        let mut items_buffer = Vec::new();
+
+        for (index, item) in items.iter().enumerate() {
+            // 从文件中读取的item沿用原来的长度形式
+            let undefined_item_length = original_encoding
+                .and_then(|v| v.item_undefined_lengths.get(index).copied())
+                .unwrap_or(undefined_sequence_length);
+
+            encode_sq_item(
+                item,
+                transfer_syntax,
+                undefined_sequence_length,
+                undefined_item_length,
+                &mut items_buffer,
+            )?;
+        }
+
+        let undefined_length = original_encoding
+            .map(|v| v.undefined_length)
+            .unwrap_or(undefined_sequence_length);
+
+        if undefined_length {
+            write_header(
+                group,
+                element,
+                "SQ",
+                0xffffffff,
+                transfer_syntax,
+                &mut buffer,
+            )?;
+            buffer.extend_from_slice(&items_buffer);
+            // Seq. Delim. Tag
+            write_tag(
+                0xfffe,
+                0xe0dd,
+                transfer_syntax.is_little_endian(),
+                &mut buffer,
+            );
+            write_u32(0, transfer_syntax.is_little_endian(), &mut buffer);
+        } else {
+            let length = u32::try_from(items_buffer.len())?;
+            write_header(group, element, "SQ", length, transfer_syntax, &mut buffer)?;
+            buffer.extend_from_slice(&items_buffer);
+        }
+
+        return Ok(buffer);
+    }
+
+    let little_endian = transfer_syntax.is_little_endian();
+
+    // 封装格式的像素数据使用未定义长度，每个fragment是一个item
+    if let DicomValue::Fragments(fragments) = &data_element.data {
+        if !transfer_syntax.is_encapsulated() {
+            return Err(format!(
+                "{}: encapsulated pixel data can not be written as {:?}",
+                data_element.tag, transfer_syntax
+            )
+            .into());
+        }
+
+        write_header(group, element, vr, 0xffffffff, transfer_syntax, &mut buffer)?;
+
+        for fragment in fragments {
+            // fragment的长度也必须是偶数
+            let padding = fragment.len() % 2;
+
+            write_tag(0xfffe, 0xe000, little_endian, &mut buffer);
+            write_u32(
+                u32::try_from(fragment.len() + padding)?,
+                little_endian,
+                &mut buffer,
+            );
+            buffer.extend_from_slice(fragment);
+            buffer.extend(std::iter::repeat_n(0x00, padding));
+        }
+
+        // Seq. Delim. Tag
+        write_tag(0xfffe, 0xe0dd, little_endian, &mut buffer);
+        write_u32(0, little_endian, &mut buffer);
+
+        return Ok(buffer);
+    }
+
+    // 值没有被修改过时直接使用原始字节
+    let value = match original_encoding {
+        Some(crate::model::OriginalEncoding {
+            raw: Some(raw),
+            little_endian: raw_little_endian,
+            ..
+        }) if (*raw_little_endian == little_endian || is_byte_order_free_vr(vr))
+            && crate::service::parse_value(raw, vr, *raw_little_endian)
+                .ok()
+                .as_ref()
+                == Some(&data_element.data) =>
+        {
+            raw.clone()
+        }
+        _ => encode_value(vr, &data_element.data, little_endian)
+            .map_err(|error| format!("{}: {}", data_element.tag, error))?,
+    };
+
+    write_header(
+        group,
+        element,
+        vr,
+        u32::try_from(value.len())?,
+        transfer_syntax,
+        &mut buffer,
+    )?;
+    buffer.extend_from_slice(&value);
+
+    Ok(buffer)
+}
+
+fn encode_sq_item(
+    item: &[DataElement],
+    transfer_syntax: TransferSyntax,
+    undefined_sequence_length: bool,
+    undefined_item_length: bool,
+    buffer: &mut Vec<u8>,
+) -> CommonResult<()> {
+    let little_endian = transfer_syntax.is_little_endian();
+
+    let mut item_buffer = Vec::new();
+    write_data_elements(
+        item,
+        transfer_syntax,
+        undefined_sequence_length,
+        &mut item_buffer,
+    )?;
+
+    // Item Tag
+    write_tag(0xfffe, 0xe000, little_endian, buffer);
+
+    if undefined_item_length {
+        write_u32(0xffffffff, little_endian, buffer);
+        buffer.extend_from_slice(&item_buffer);
+        // Item Delim. Tag
+        write_tag(0xfffe, 0xe00d, little_endian, buffer);
+        write_u32(0, little_endian, buffer);
+    } else {
+        write_u32(u32::try_from(item_buffer.len())?, little_endian, buffer);
+        buffer.extend_from_slice(&item_buffer);
+    }
+
+    Ok(())
+}
+
+fn encode_numbers<T: Copy, const N: usize>(
+    values: &[T],
+    little_endian: bool,
+    to_le_bytes: fn(T) -> [u8; N],
+    to_be_bytes: fn(T) -> [u8; N],
+) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| {
+            if little_endian {
+                to_le_bytes(*v)
+            } else {
+                to_be_bytes(*v)
+            }
+        })
+        .collect()
+}
+
+// DS每个值最多16字节，超出时改用指数形式并逐步降低精度
+pub fn format_decimal_string(value: f64) -> String {
+    let result = format!("{}", value);
+
+    if result.len() <= 16 {
+        return result;
+    }
+
+    for precision in (0..=15).rev() {
+        let result = format!("{:.*e}", precision, value);
+
+        if result.len() <= 16 {
+            return result;
+        }
+    }
+
+    format!("{:e}", value)
+}
+
+// 字符串类的vr，编码与字节序无关
+fn is_text_vr(vr: &str) -> bool {
+    [
+        "AE", "AS", "CS", "DA", "DS", "DT", "IS", "LO", "LT", "PN", "SH", "ST", "TM", "UC", "UI",
+        "UR", "UT",
+    ]
+    .contains(&vr)
+}
+
+// 编码与字节序无关的vr
+fn is_byte_order_free_vr(vr: &str) -> bool {
+    is_text_vr(vr) || vr == "OB" || vr == "UN"
+}
+
+// 将值编码成字节，并按照vr的要求填充到偶数长度
+// 内存中OW/OF/OD/OL/OV的字节始终是小端顺序，写大端时需要按照单个值的宽度翻转
+pub fn encode_value(vr: &str, data: &DicomValue, little_endian: bool) -> CommonResult<Vec<u8>> {
+    let mut value = match (vr, data) {
+        ("DS", DicomValue::Double(v)) => v
+            .iter()
+            .map(|v| format_decimal_string(*v))
+            .collect::<Vec<String>>()
+            .join("\\")
+            .into_bytes(),
+        ("IS", DicomValue::I64(v)) => v
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>()
+            .join("\\")
+            .into_bytes(),
+        (_, DicomValue::String(v)) if is_text_vr(vr) => {
+            // 读取时使用gbk解码，这里使用同样的编码写回
+            let (result, _, _) = encoding_rs::GBK.encode(v);
+            result.into_owned()
+        }
+        ("US", DicomValue::U16(v)) => {
+            encode_numbers(v, little_endian, u16::to_le_bytes, u16::to_be_bytes)
+        }
+        ("SS", DicomValue::I16(v)) => {
+            encode_numbers(v, little_endian, i16::to_le_bytes, i16::to_be_bytes)
+        }
+        ("UL", DicomValue::U32(v)) => {
+            encode_numbers(v, little_endian, u32::to_le_bytes, u32::to_be_bytes)
+        }
+        ("SL", DicomValue::I32(v)) => {
+            encode_numbers(v, little_endian, i32::to_le_bytes, i32::to_be_bytes)
+        }
+        ("SV", DicomValue::I64(v)) => {
+            encode_numbers(v, little_endian, i64::to_le_bytes, i64::to_be_bytes)
+        }
+        ("UV", DicomValue::U64(v)) => {
+            encode_numbers(v, little_endian, u64::to_le_bytes, u64::to_be_bytes)
+        }
+        ("FL", DicomValue::Float(v)) => {
+            encode_numbers(v, little_endian, f32::to_le_bytes, f32::to_be_bytes)
+        }
+        ("FD", DicomValue::Double(v)) => {
+            encode_numbers(v, little_endian, f64::to_le_bytes, f64::to_be_bytes)
+        }
+        ("AT", DicomValue::U16Pair((group, element))) => encode_numbers(
+            &[
+                u16::from_str_radix(group, 16)?,
+                u16::from_str_radix(element, 16)?,
+            ],
+            little_endian,
+            u16::to_le_bytes,
+            u16::to_be_bytes,
+        ),
+        ("AT", DicomValue::U16(v)) => {
+            encode_numbers(v, little_endian, u16::to_le_bytes, u16::to_be_bytes)
+        }
+        ("OB" | "UN", DicomValue::Bytes(v)) => v.clone(),
+        ("OW" | "OF" | "OD" | "OL" | "OV", DicomValue::Bytes(v)) => {
+            let width = match vr {
+                "OW" => 2,
+                "OF" | "OL" => 4,
+                _ => 8,
+            };
+
+            if little_endian {
+                v.clone()
+            } else {
+                v.chunks(width)
+                    .flat_map(|chunk| chunk.iter().rev().copied())
+                    .collect()
+            }
+        }
+        _ => return Err(format!("can not encode {:?} as {}", data, vr).into()),
+    };
+
+    // 值的长度必须是偶数，UI/OB/UN使用\0填充，其余字符串类使用空格填充
+    if value.len() % 2 == 1 {
+        let padding = match vr {
+            "UI" | "OB" | "UN" => 0x00,
+            _ => b' ',
+        };
+
+        value.push(padding);
+    }
+
+    Ok(value)
+}
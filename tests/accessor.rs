@@ -0,0 +1,151 @@
+use dicom_parser::{
+    accessor,
+    model::{DicomDate, DicomValue, ValueError},
+};
+
+mod common;
+
+use common::{element, string};
+
+#[test]
+fn values_are_converted_between_compatible_types() {
+    let data_elements = vec![
+        element("0028,0010", "US", DicomValue::U16(vec![512])),
+        element("0028,0011", "IS", DicomValue::I64(vec![256])),
+        string("0020,0013", "IS", " 7 "),
+        element("0018,0050", "DS", DicomValue::Double(vec![2.0])),
+        element("0028,0030", "DS", DicomValue::Double(vec![0.5, 0.25])),
+        string("0010,0010", "PN", "Doe^John "),
+    ];
+
+    assert_eq!(accessor::get_u16(&data_elements, "0028,0010").unwrap(), 512);
+    assert_eq!(accessor::get_u32(&data_elements, "0028,0010").unwrap(), 512);
+    assert_eq!(accessor::get_u16(&data_elements, "0028,0011").unwrap(), 256);
+    assert_eq!(accessor::get_i32(&data_elements, "0020,0013").unwrap(), 7);
+    assert_eq!(accessor::get_i64(&data_elements, "0018,0050").unwrap(), 2);
+    assert_eq!(
+        accessor::get_f64(&data_elements, "0028,0011").unwrap(),
+        256.0
+    );
+    assert_eq!(
+        accessor::get_f64s(&data_elements, "0028,0030").unwrap(),
+        vec![0.5, 0.25]
+    );
+    assert_eq!(
+        accessor::get_str(&data_elements, "0010,0010").unwrap(),
+        "Doe^John"
+    );
+}
+
+#[test]
+fn text_values_are_not_split_on_backslashes() {
+    let data_elements = vec![
+        string("0020,4000", "LT", "  C:\\images\\scan 1 "),
+        string("0008,0081", "ST", "a\\b"),
+        string("0040,A160", "UT", "x\\y\\z"),
+        string("0008,0190", "UR", "http://host/a\\b"),
+        string("0008,0008", "CS", "ORIGINAL\\PRIMARY"),
+    ];
+
+    // 首部的空格保留，尾部的空格去掉
+    assert_eq!(
+        accessor::get_str(&data_elements, "0020,4000").unwrap(),
+        "  C:\\images\\scan 1"
+    );
+    assert_eq!(
+        accessor::get_str(&data_elements, "0008,0081").unwrap(),
+        "a\\b"
+    );
+    assert_eq!(
+        accessor::get_strs(&data_elements, "0040,A160")
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(
+        accessor::get_str(&data_elements, "0008,0190").unwrap(),
+        "http://host/a\\b"
+    );
+
+    // 其他字符串类型仍然按照\拆分
+    assert_eq!(
+        accessor::get_strs(&data_elements, "0008,0008").unwrap(),
+        vec!["ORIGINAL", "PRIMARY"]
+    );
+    assert!(accessor::get_str(&data_elements, "0008,0008").is_err());
+}
+
+#[test]
+fn dates_are_parsed_and_validated() {
+    let data_elements = vec![
+        string("0008,0020", "DA", "20240229"),
+        string("0008,0021", "DA", "1999.12.31"),
+        string("0008,0022", "DA", "20230229"),
+        string("0008,0023", "DA", "2024\\20250101"),
+    ];
+
+    assert_eq!(
+        accessor::get_date(&data_elements, "0008,0020").unwrap(),
+        DicomDate {
+            year: 2024,
+            month: 2,
+            day: 29
+        }
+    );
+    assert_eq!(
+        accessor::get_date(&data_elements, "0008,0021").unwrap(),
+        DicomDate {
+            year: 1999,
+            month: 12,
+            day: 31
+        }
+    );
+    assert!(accessor::get_date(&data_elements, "0008,0022").is_err());
+    assert!(accessor::get_date(&data_elements, "0008,0023").is_err());
+
+    // 非ASCII字符不能导致切片panic
+    assert_eq!(accessor::parse_date("2024é0.01"), None);
+    assert_eq!(accessor::parse_date("20é4.01.01"), None);
+    assert_eq!(accessor::parse_date("日期日期"), None);
+}
+
+#[test]
+fn wrong_vm_and_wrong_type_are_errors() {
+    let data_elements = vec![
+        element("0028,0030", "DS", DicomValue::Double(vec![0.5, 0.25])),
+        element("0018,0050", "DS", DicomValue::Double(vec![2.5])),
+        element("0028,0106", "SS", DicomValue::I16(vec![-1])),
+        element("7FE0,0010", "OW", DicomValue::Bytes(vec![0, 1])),
+        string("0010,0010", "PN", "Doe^John"),
+    ];
+
+    let error = accessor::get_f64(&data_elements, "0028,0030").unwrap_err();
+    assert_eq!(error.to_string(), "0028,0030 has VM 2, expected 1");
+
+    // 有小数部分的DS不能当作整数，负数不能当作无符号数
+    assert!(accessor::get_i64(&data_elements, "0018,0050").is_err());
+    assert!(accessor::get_u16(&data_elements, "0028,0106").is_err());
+    assert_eq!(accessor::get_i32(&data_elements, "0028,0106").unwrap(), -1);
+
+    assert!(accessor::get_f64s(&data_elements, "7FE0,0010").is_err());
+    assert!(accessor::get_str(&data_elements, "7FE0,0010").is_err());
+    assert!(accessor::get_u32(&data_elements, "0010,0010").is_err());
+
+    let error = accessor::get_u16(&data_elements, "0028,0010").unwrap_err();
+    assert_eq!(error.to_string(), "0028,0010 is missing");
+}
+
+#[test]
+fn recorded_value_errors_make_the_value_unreliable() {
+    let mut data_element = element("0018,0050", "DS", DicomValue::Double(vec![1.5]));
+    data_element.value_errors.push(ValueError {
+        index: 1,
+        raw: "x".to_string(),
+        message: "invalid character in DS value".to_string(),
+    });
+
+    let data_elements = vec![data_element];
+
+    assert!(accessor::get_f64(&data_elements, "0018,0050").is_err());
+    assert!(accessor::get_f64s(&data_elements, "0018,0050").is_err());
+}
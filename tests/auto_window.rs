@@ -0,0 +1,127 @@
+use dicom_parser::{
+    lut,
+    model::{
+        AutoWindowStrategy, DataElement, DicomValue, ModalityTransform, RenderOptions,
+        VoiLutFunction, VoiTransform,
+    },
+    render, service,
+};
+
+mod common;
+
+use common::{element, string, words, Grayscale};
+
+// 没有Rescale Slope/Intercept和窗宽窗位的MR图像
+fn build_dataset(pixels: &[u16]) -> Vec<DataElement> {
+    Grayscale {
+        columns: pixels.len() as u16,
+        bits_stored: 12,
+        ..Default::default()
+    }
+    .build(words(pixels), vec![string("0008,0060", "CS", "MR")])
+}
+
+#[test]
+fn missing_rescale_uses_identity() {
+    let data_elements = build_dataset(&[0, 1]);
+
+    assert_eq!(
+        lut::get_modality_transform(&data_elements, 0).unwrap(),
+        ModalityTransform::Rescale {
+            slope: 1.0,
+            intercept: 0.0
+        }
+    );
+}
+
+#[test]
+fn min_max_window_covers_all_values() {
+    let window =
+        lut::compute_auto_window(&[100.0, 300.0, 200.0], AutoWindowStrategy::MinMax).unwrap();
+
+    assert_eq!(window.center, 200.0);
+    assert_eq!(window.width, 200.0);
+    assert_eq!(window.function, VoiLutFunction::LinearExact);
+
+    let transform = VoiTransform::Window(window);
+    assert_eq!(lut::apply_voi(100.0, &transform), 0.0);
+    assert_eq!(lut::apply_voi(300.0, &transform), 1.0);
+}
+
+#[test]
+fn percentile_window_ignores_outliers() {
+    let mut values = (0..=100).map(|v| v as f64).collect::<Vec<f64>>();
+    values.push(10000.0);
+    values.push(-10000.0);
+
+    let window = lut::compute_auto_window(
+        &values,
+        AutoWindowStrategy::Percentile {
+            lower: 1.0,
+            upper: 99.0,
+        },
+    )
+    .unwrap();
+
+    assert!(window.center.abs() < 100.0);
+    assert!(window.width < 200.0);
+}
+
+#[test]
+fn constant_image_still_has_a_window() {
+    let window = lut::compute_auto_window(&[5.0; 4], AutoWindowStrategy::MinMax).unwrap();
+
+    assert_eq!(window.center, 5.0);
+    assert_eq!(window.width, 1.0);
+
+    assert!(lut::compute_auto_window(&[], AutoWindowStrategy::MinMax).is_none());
+}
+
+#[test]
+fn images_without_window_attributes_are_rendered() {
+    let data_elements = build_dataset(&[0, 1000, 2000, 4000]);
+
+    let output_directory =
+        std::env::temp_dir().join(format!("dicom_parser_auto_window_{}", std::process::id()));
+    let output_directory = output_directory.to_str().unwrap();
+
+    let file_paths = service::generate_frame_images_with_auto_window(
+        &data_elements,
+        output_directory,
+        AutoWindowStrategy::MinMax,
+    )
+    .unwrap();
+
+    let image = image::open(&file_paths[0]).unwrap().to_luma8();
+    let pixels = image.pixels().map(|v| v.0[0]).collect::<Vec<u8>>();
+
+    assert_eq!(pixels[0], 0);
+    assert_eq!(pixels[3], 255);
+    assert!(pixels[1] < pixels[2]);
+
+    std::fs::remove_dir_all(output_directory).unwrap();
+}
+
+#[test]
+fn half_present_window_falls_back_to_the_auto_window() {
+    let full = build_dataset(&[0, 1000, 2000, 4000]);
+
+    for tag in ["0028,1050", "0028,1051"] {
+        let mut data_elements = full.clone();
+        data_elements.push(element(tag, "DS", DicomValue::Double(vec![40.0])));
+
+        assert!(lut::get_windows(&data_elements).unwrap().is_empty());
+
+        // 和没有窗宽窗位时的渲染结果相同
+        assert_eq!(
+            render::render(&data_elements, &RenderOptions::default())
+                .unwrap()
+                .to_luma8()
+                .into_raw(),
+            render::render(&full, &RenderOptions::default())
+                .unwrap()
+                .to_luma8()
+                .into_raw()
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use dicom_parser::{color, model::PixelDescription};
+
+// 1行2列的8位彩色图像
+fn description(photometric_interpretation: &str, planar_configuration: u16) -> PixelDescription {
+    PixelDescription {
+        rows: 1,
+        columns: 2,
+        samples_per_pixel: 3,
+        bits_allocated: 8,
+        bits_stored: 8,
+        high_bit: 7,
+        pixel_representation: 0,
+        planar_configuration,
+        number_of_frames: 1,
+        photometric_interpretation: photometric_interpretation.to_string(),
+    }
+}
+
+#[test]
+fn rgb_planar_configuration_is_interleaved() {
+    let interleaved = [255, 0, 0, 0, 0, 255];
+    let planar = [255, 0, 0, 0, 0, 255];
+
+    assert_eq!(
+        color::frame_to_rgb(&interleaved, &description("RGB", 0)).unwrap(),
+        vec![255, 0, 0, 0, 0, 255]
+    );
+    // R R G G B B
+    assert_eq!(
+        color::frame_to_rgb(&planar, &description("RGB", 1)).unwrap(),
+        vec![255, 0, 0, 0, 0, 255]
+    );
+}
+
+#[test]
+fn ybr_full_is_converted_to_rgb() {
+    // 灰色和纯红色（JFIF中红色约为Y=76 Cb=85 Cr=255）
+    let frame = [128, 128, 128, 76, 85, 255];
+
+    let rgb = color::frame_to_rgb(&frame, &description("YBR_FULL", 0)).unwrap();
+
+    assert_eq!(rgb[..3], [128, 128, 128]);
+    assert!(rgb[3] >= 253 && rgb[4] <= 2 && rgb[5] <= 2, "{:?}", rgb);
+}
+
+#[test]
+fn ybr_full_422_shares_chroma_between_two_pixels() {
+    // Y1 Y2 Cb Cr
+    let frame = [50, 200, 128, 128];
+
+    assert_eq!(
+        color::frame_to_rgb(&frame, &description("YBR_FULL_422", 0)).unwrap(),
+        vec![50, 50, 50, 200, 200, 200]
+    );
+
+    // 奇数列时最后一个像素没有对应的Cb、Cr，返回错误而不是越界
+    let odd_columns = PixelDescription {
+        columns: 3,
+        ..description("YBR_PARTIAL_422", 0)
+    };
+    let error = color::frame_to_rgb(&[50, 200, 128, 128, 100, 0], &odd_columns).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "YBR_PARTIAL_422 requires an even number of columns, got 3"
+    );
+}
+
+#[test]
+fn ybr_rct_is_reversible() {
+    // R=200 G=100 B=50：Y=floor((R+2G+B)/4)=112，Cb=B-G=-50，Cr=R-G=100，Cb、Cr按128偏移存储
+    let frame = [112, 78, 228, 0, 128, 128];
+
+    assert_eq!(
+        color::frame_to_rgb(&frame, &description("YBR_RCT", 0)).unwrap(),
+        vec![200, 100, 50, 0, 0, 0]
+    );
+}
+
+#[test]
+fn monochrome_is_not_a_color_image() {
+    let mut description = description("MONOCHROME2", 0);
+    description.samples_per_pixel = 1;
+
+    assert!(color::frame_to_rgb(&[0, 0], &description).is_err());
+}
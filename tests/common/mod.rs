@@ -0,0 +1,100 @@
+// 各个集成测试共用的构造数据元素的辅助函数
+// 每个测试文件单独编译，用不到的函数会被当作dead code
+#![allow(dead_code)]
+
+use dicom_parser::{
+    model::{DataElement, DicomValue, TransferSyntax},
+    util,
+};
+
+pub fn element(tag: &str, vr: &str, data: DicomValue) -> DataElement {
+    util::new_data_element(tag, vr, data).unwrap()
+}
+
+pub fn string(tag: &str, vr: &str, value: &str) -> DataElement {
+    element(tag, vr, DicomValue::String(value.to_string()))
+}
+
+pub fn sequence(tag: &str, items: Vec<Vec<DataElement>>) -> DataElement {
+    element(tag, "SQ", DicomValue::Sequence(items))
+}
+
+// 小端、未压缩的灰度图像，其他测试文件中的图像都由这个结构构造
+// 只有多帧时才写Number of Frames (0028,0008)，extra中的数据元素（Rescale、窗宽窗位等）按照tag合并进去
+pub struct Grayscale {
+    pub rows: u16,
+    pub columns: u16,
+    pub frames: usize,
+    pub bits_allocated: u16,
+    pub bits_stored: u16,
+    pub pixel_representation: u16,
+    pub photometric_interpretation: &'static str,
+}
+
+impl Default for Grayscale {
+    fn default() -> Self {
+        Grayscale {
+            rows: 1,
+            columns: 1,
+            frames: 1,
+            bits_allocated: 16,
+            bits_stored: 16,
+            pixel_representation: 0,
+            photometric_interpretation: "MONOCHROME2",
+        }
+    }
+}
+
+impl Grayscale {
+    pub fn build(&self, pixels: Vec<u8>, extra: Vec<DataElement>) -> Vec<DataElement> {
+        let vr = if self.bits_allocated > 8 { "OW" } else { "OB" };
+
+        let mut result = vec![
+            string(
+                "0002,0010",
+                "UI",
+                TransferSyntax::ExplicitVrLittleEndian.uid(),
+            ),
+            string("0028,0004", "CS", self.photometric_interpretation),
+            element("0028,0010", "US", DicomValue::U16(vec![self.rows])),
+            element("0028,0011", "US", DicomValue::U16(vec![self.columns])),
+            element(
+                "0028,0100",
+                "US",
+                DicomValue::U16(vec![self.bits_allocated]),
+            ),
+            element("0028,0101", "US", DicomValue::U16(vec![self.bits_stored])),
+            element(
+                "0028,0102",
+                "US",
+                DicomValue::U16(vec![self.bits_stored - 1]),
+            ),
+            element(
+                "0028,0103",
+                "US",
+                DicomValue::U16(vec![self.pixel_representation]),
+            ),
+            element("7FE0,0010", vr, DicomValue::Bytes(pixels)),
+        ];
+
+        if self.frames > 1 {
+            result.push(element(
+                "0028,0008",
+                "IS",
+                DicomValue::I64(vec![self.frames as i64]),
+            ));
+        }
+
+        result.extend(extra);
+        result.sort_by(|a, b| a.tag.cmp(&b.tag));
+        result
+    }
+}
+
+// 16位像素值按照小端顺序转换成字节
+pub fn words<T: Copy + Into<i32>>(values: &[T]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| ((*v).into() as u16).to_le_bytes())
+        .collect()
+}
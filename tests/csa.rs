@@ -0,0 +1,210 @@
+use dicom_parser::{
+    accessor, csa,
+    model::{CsaFormat, DicomValue, TransferSyntax},
+    mosaic,
+};
+
+mod common;
+
+use common::{element, string};
+
+// 按照CSA header的格式写入元素，每个元素有vm个值和item_count - vm个空的item
+// 旧格式中item长度的第一个i32要加上第一个元素的item个数
+fn build_csa(sv10: bool, elements: &[(&str, &str, Vec<&str>)], item_count: i32) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    if sv10 {
+        buffer.extend(b"SV10\x04\x03\x02\x01");
+    }
+
+    buffer.extend((elements.len() as u32).to_le_bytes());
+    buffer.extend(77_u32.to_le_bytes());
+
+    for (name, vr, values) in elements {
+        let mut name_bytes = name.as_bytes().to_vec();
+        name_bytes.resize(64, 0);
+        buffer.extend(name_bytes);
+        buffer.extend((values.len() as i32).to_le_bytes());
+
+        let mut vr_bytes = vr.as_bytes().to_vec();
+        vr_bytes.resize(4, 0);
+        buffer.extend(vr_bytes);
+        buffer.extend(3_i32.to_le_bytes());
+        buffer.extend(item_count.to_le_bytes());
+        buffer.extend(77_i32.to_le_bytes());
+
+        for index in 0..item_count as usize {
+            let mut value = values
+                .get(index)
+                .map(|v| format!("{}\0", v).into_bytes())
+                .unwrap_or_default();
+            let length = value.len() as i32;
+            let first = if sv10 { length } else { length + item_count };
+
+            for v in [first, length, 77, length] {
+                buffer.extend(v.to_le_bytes());
+            }
+
+            value.resize(value.len().div_ceil(4) * 4, 0);
+            buffer.extend(value);
+        }
+    }
+
+    buffer
+}
+
+#[test]
+fn sv10_header_exposes_values_by_name() {
+    let buffer = build_csa(
+        true,
+        &[
+            ("B_value", "IS", vec!["1000"]),
+            ("DiffusionGradientDirection", "FD", vec!["0.6", "-0.8", "0"]),
+            ("PhaseEncodingDirectionPositive", "IS", vec!["1"]),
+            ("ImaComments", "LT", vec![]),
+        ],
+        6,
+    );
+
+    let header = csa::parse_csa_header(&buffer).unwrap();
+
+    assert_eq!(header.format, CsaFormat::Sv10);
+    assert_eq!(header.elements.len(), 4);
+    assert_eq!(csa::get_csa_f64(&header, "B_value").unwrap(), 1000.0);
+    assert_eq!(
+        csa::get_csa_f64s(&header, "DiffusionGradientDirection").unwrap(),
+        vec![0.6, -0.8, 0.0]
+    );
+
+    let element = &header.elements["DiffusionGradientDirection"];
+    assert_eq!((element.vr.as_str(), element.vm), ("FD", 3));
+
+    // 没有值的元素，以及不存在的元素
+    assert!(csa::get_csa_strs(&header, "ImaComments")
+        .unwrap()
+        .is_empty());
+    assert!(csa::get_csa_f64(&header, "ImaComments").is_err());
+    assert!(csa::get_csa_f64(&header, "SliceTiming").is_err());
+}
+
+#[test]
+fn legacy_header_subtracts_the_item_count_from_lengths() {
+    let buffer = build_csa(
+        false,
+        &[
+            ("EchoLinePosition", "IS", vec!["64"]),
+            ("SliceMeasurementDuration", "DS", vec!["120.5", "240"]),
+        ],
+        3,
+    );
+
+    let header = csa::parse_csa_header(&buffer).unwrap();
+
+    assert_eq!(header.format, CsaFormat::Legacy);
+    assert_eq!(csa::get_csa_f64(&header, "EchoLinePosition").unwrap(), 64.0);
+    assert_eq!(
+        csa::get_csa_f64s(&header, "SliceMeasurementDuration").unwrap(),
+        vec![120.5, 240.0]
+    );
+
+    // 截断的数据和不是CSA header的数据
+    assert!(csa::parse_csa_header(&buffer[..100]).is_err());
+    assert!(csa::parse_csa_header(b"ORIGINAL\\PRIMARY").is_err());
+}
+
+#[test]
+fn image_and_series_headers_are_found_by_the_private_creator() {
+    let image_header = build_csa(true, &[("B_value", "IS", vec!["800"])], 6);
+    let series_header = build_csa(true, &[("UsedPatientWeight", "DS", vec!["70"])], 6);
+
+    // Private Creator在0029,0011，对应的block是0029,11xx；OB的值保持二进制，不会按照字符集解码
+    let data_elements = vec![
+        string("0029,0010", "LO", "SIEMENS CSA NON-IMAGE"),
+        element("0029,1010", "OB", DicomValue::Bytes(vec![0; 8])),
+        string("0029,0011", "LO", "SIEMENS CSA HEADER"),
+        element("0029,1110", "OB", DicomValue::Bytes(image_header)),
+        element("0029,1120", "OB", DicomValue::Bytes(series_header)),
+    ];
+
+    let header = csa::get_csa_image_header(&data_elements).unwrap().unwrap();
+    assert_eq!(csa::get_csa_f64(&header, "B_value").unwrap(), 800.0);
+
+    let header = csa::get_csa_series_header(&data_elements).unwrap().unwrap();
+    assert_eq!(
+        csa::get_csa_f64(&header, "UsedPatientWeight").unwrap(),
+        70.0
+    );
+
+    assert!(csa::get_csa_image_header(&data_elements[..2])
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn mosaic_uses_csa_slice_normal_and_slice_times() {
+    let image_header = build_csa(
+        true,
+        &[
+            ("NumberOfImagesInMosaic", "US", vec!["4"]),
+            ("SliceNormalVector", "FD", vec!["0", "0", "-1"]),
+            (
+                "MosaicRefAcqTimes",
+                "FD",
+                vec!["0", "1000", "500", "1500.5"],
+            ),
+        ],
+        6,
+    );
+
+    // 没有0019组的4层mosaic，每层2x2
+    let data_elements = vec![
+        string(
+            "0002,0010",
+            "UI",
+            TransferSyntax::ExplicitVrLittleEndian.uid(),
+        ),
+        string("0008,0008", "CS", "ORIGINAL\\PRIMARY\\M\\ND\\MOSAIC"),
+        string("0008,0032", "TM", "120000"),
+        string("0018,0088", "DS", "3"),
+        string("0020,0032", "DS", "0\\0\\0"),
+        string("0020,0037", "DS", "1\\0\\0\\0\\1\\0"),
+        string("0028,0004", "CS", "MONOCHROME2"),
+        element("0028,0010", "US", DicomValue::U16(vec![4])),
+        element("0028,0011", "US", DicomValue::U16(vec![4])),
+        string("0028,0030", "DS", "1\\1"),
+        element("0028,0100", "US", DicomValue::U16(vec![8])),
+        element("0028,0101", "US", DicomValue::U16(vec![8])),
+        element("0028,0102", "US", DicomValue::U16(vec![7])),
+        element("0028,0103", "US", DicomValue::U16(vec![0])),
+        string("0029,0010", "LO", "SIEMENS CSA HEADER"),
+        element("0029,1010", "OB", DicomValue::Bytes(image_header)),
+        element("7FE0,0010", "OB", DicomValue::Bytes(vec![0; 16])),
+    ];
+
+    assert_eq!(
+        mosaic::get_images_in_mosaic(&data_elements).unwrap(),
+        Some(4)
+    );
+
+    let slices = mosaic::unpack_mosaic(&data_elements).unwrap();
+
+    assert_eq!(slices.len(), 4);
+
+    // SliceNormalVector和row_direction × column_direction相反，各层沿着-z排列
+    assert_eq!(
+        accessor::get_f64s(&slices[1], "0020,0032").unwrap(),
+        vec![1.0, 1.0, -3.0]
+    );
+    assert_eq!(
+        slices
+            .iter()
+            .map(|v| accessor::get_str(v, "0008,0032").unwrap())
+            .collect::<Vec<String>>(),
+        vec![
+            "120000.000000",
+            "120001.000000",
+            "120000.500000",
+            "120001.500500"
+        ]
+    );
+}
@@ -0,0 +1,140 @@
+use dicom_parser::{
+    export,
+    model::{DataElement, DicomValue, ExportFormat},
+    util,
+};
+
+mod common;
+
+use common::{element, string, words, Grayscale};
+
+// 1x4的CT图像，存储值0、1000、2000、4095，CT值为-1024、-24、976、3071
+fn build_dataset() -> Vec<DataElement> {
+    Grayscale {
+        columns: 4,
+        bits_stored: 12,
+        ..Default::default()
+    }
+    .build(
+        words(&[0_u16, 1000, 2000, 4095]),
+        vec![
+            element("0018,0050", "DS", DicomValue::Double(vec![2.5])),
+            element("0028,0030", "DS", DicomValue::Double(vec![0.5, 0.75])),
+            element("0028,1052", "DS", DicomValue::Double(vec![-1024.0])),
+            element("0028,1053", "DS", DicomValue::Double(vec![1.0])),
+            string("0028,1054", "LO", "HU"),
+        ],
+    )
+}
+
+fn output_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "dicom_parser_export_{}_{}",
+        std::process::id(),
+        name
+    ))
+}
+
+#[test]
+fn modality_values_keep_the_full_range() {
+    let (description, values) = export::modality_frame(&build_dataset(), 0).unwrap();
+
+    assert_eq!(description.columns, 4);
+    assert_eq!(values, vec![-1024.0, -24.0, 976.0, 3071.0]);
+}
+
+#[test]
+fn sixteen_bit_png_is_offset_and_described_by_the_sidecar() {
+    let file_path = output_path("png16.png");
+
+    export::export_frame(&build_dataset(), 0, &file_path, ExportFormat::Png16).unwrap();
+
+    let image = image::open(&file_path).unwrap().to_luma16();
+    assert_eq!(image.into_raw(), vec![0, 1000, 2000, 4095]);
+
+    let sidecar = std::fs::read_to_string(file_path.with_extension("json")).unwrap();
+    assert!(sidecar.contains("\"value_offset\": -1024"));
+    assert!(sidecar.contains("\"rescale_intercept\": -1024"));
+    assert!(sidecar.contains("\"rescale_type\": \"HU\""));
+    assert!(sidecar.contains("\"pixel_spacing\": [0.5, 0.75]"));
+    assert!(sidecar.contains("\"slice_thickness\": 2.5"));
+    assert!(sidecar.contains("\"spacing_between_slices\": null"));
+
+    std::fs::remove_file(&file_path).unwrap();
+    std::fs::remove_file(file_path.with_extension("json")).unwrap();
+}
+
+#[test]
+fn sixteen_bit_export_refuses_to_round_or_clamp() {
+    let file_path = output_path("lossy.png");
+
+    // Rescale Slope为0.5时有小数部分
+    let mut data_elements = build_dataset();
+    util::set_data_element(
+        &mut data_elements,
+        element("0028,1053", "DS", DicomValue::Double(vec![0.5])),
+    );
+
+    let error = export::export_frame(&data_elements, 0, &file_path, ExportFormat::Png16)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("1023.5 is not an integer"));
+
+    // Rescale Slope为100时范围超出65535
+    util::set_data_element(
+        &mut data_elements,
+        element("0028,1053", "DS", DicomValue::Double(vec![100.0])),
+    );
+
+    let error = export::export_frame(&data_elements, 0, &file_path, ExportFormat::Tiff16)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("does not fit in 16 bits"));
+
+    assert!(!file_path.exists());
+}
+
+#[test]
+fn float_tiff_keeps_negative_values() {
+    let file_path = output_path("float.tiff");
+
+    export::export_frame(&build_dataset(), 0, &file_path, ExportFormat::TiffFloat).unwrap();
+
+    let mut decoder =
+        tiff::decoder::Decoder::new(std::fs::File::open(&file_path).unwrap()).unwrap();
+
+    match decoder.read_image().unwrap() {
+        tiff::decoder::DecodingResult::F32(values) => {
+            assert_eq!(values, vec![-1024.0, -24.0, 976.0, 3071.0])
+        }
+        _ => panic!("tiff is not float32"),
+    }
+
+    std::fs::remove_file(&file_path).unwrap();
+    std::fs::remove_file(file_path.with_extension("json")).unwrap();
+}
+
+#[test]
+fn npy_has_an_aligned_header() {
+    let file_path = output_path("values.npy");
+
+    export::export_frame(&build_dataset(), 0, &file_path, ExportFormat::Npy).unwrap();
+
+    let content = std::fs::read(&file_path).unwrap();
+    let header_length = u16::from_le_bytes([content[8], content[9]]) as usize;
+    let header = std::str::from_utf8(&content[10..10 + header_length]).unwrap();
+
+    assert_eq!(&content[..6], b"\x93NUMPY");
+    assert_eq!((10 + header_length) % 64, 0);
+    assert!(header.contains("'descr': '<f4'"));
+    assert!(header.contains("'shape': (1, 4)"));
+
+    let values = content[10 + header_length..]
+        .chunks(4)
+        .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+        .collect::<Vec<f32>>();
+    assert_eq!(values, vec![-1024.0, -24.0, 976.0, 3071.0]);
+
+    std::fs::remove_file(&file_path).unwrap();
+    std::fs::remove_file(file_path.with_extension("json")).unwrap();
+}
@@ -0,0 +1,109 @@
+use dicom_parser::{
+    model::{DataElement, DicomValue, TransferSyntax},
+    pixel, service, transcode,
+};
+
+mod common;
+
+use common::{element, Grayscale};
+
+const ROWS: u16 = 2;
+const COLUMNS: u16 = 3;
+const FRAMES: usize = 3;
+
+// 每一帧的像素值都等于(帧号 * 100 + 像素位置)，方便判断帧的顺序
+fn frame_pixels(frame: usize) -> Vec<u8> {
+    (0..ROWS as usize * COLUMNS as usize)
+        .flat_map(|v| ((frame * 100 + v) as u16).to_le_bytes())
+        .collect()
+}
+
+fn build_dataset() -> Vec<DataElement> {
+    Grayscale {
+        rows: ROWS,
+        columns: COLUMNS,
+        frames: FRAMES,
+        ..Default::default()
+    }
+    .build(
+        (0..FRAMES).flat_map(frame_pixels).collect(),
+        vec![
+            element("0028,1050", "DS", DicomValue::Double(vec![150.0])),
+            element("0028,1051", "DS", DicomValue::Double(vec![300.0])),
+            element("0028,1052", "DS", DicomValue::Double(vec![0.0])),
+            element("0028,1053", "DS", DicomValue::Double(vec![1.0])),
+        ],
+    )
+}
+
+#[test]
+fn native_frames_are_iterated_in_order() {
+    let data_elements = build_dataset();
+
+    let frames = pixel::frames(&data_elements).unwrap();
+    assert_eq!(frames.len(), FRAMES);
+
+    let frames = frames.collect::<Result<Vec<_>, _>>().unwrap();
+
+    for (index, frame) in frames.iter().enumerate() {
+        assert_eq!(frame, &frame_pixels(index));
+    }
+}
+
+#[test]
+fn encapsulated_frames_are_decoded_in_order() {
+    let data_elements =
+        transcode::transcode(&build_dataset(), TransferSyntax::RleLossless).unwrap();
+
+    let frames = pixel::frames(&data_elements)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(frames.len(), FRAMES);
+
+    for (index, frame) in frames.iter().enumerate() {
+        assert_eq!(frame, &frame_pixels(index));
+    }
+
+    assert_eq!(
+        pixel::get_frame(&data_elements, 2).unwrap(),
+        frame_pixels(2)
+    );
+    assert!(pixel::get_frame(&data_elements, FRAMES).is_err());
+}
+
+#[test]
+fn missing_number_of_frames_is_a_single_frame() {
+    let data_elements = build_dataset()
+        .into_iter()
+        .filter(|v| v.tag != "0028,0008")
+        .collect::<Vec<_>>();
+
+    let frames = pixel::frames(&data_elements).unwrap();
+
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames.frame(0).unwrap(), frame_pixels(0));
+}
+
+#[test]
+fn all_frames_are_written_to_numbered_images() {
+    let output_directory =
+        std::env::temp_dir().join(format!("dicom_parser_frames_{}", std::process::id()));
+    let output_directory = output_directory.to_str().unwrap();
+
+    let file_paths = service::generate_frame_images(&build_dataset(), output_directory).unwrap();
+
+    assert_eq!(file_paths.len(), FRAMES);
+    assert!(file_paths[0].ends_with("frame_0001.png"));
+    assert!(file_paths[2].ends_with("frame_0003.png"));
+
+    for file_path in &file_paths {
+        let image = image::open(file_path).unwrap();
+
+        assert_eq!(image.width(), COLUMNS as u32);
+        assert_eq!(image.height(), ROWS as u32);
+    }
+
+    std::fs::remove_dir_all(output_directory).unwrap();
+}
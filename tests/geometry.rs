@@ -0,0 +1,157 @@
+use dicom_parser::{
+    geometry,
+    model::{DataElement, DicomValue},
+};
+
+mod common;
+
+use common::{element, string};
+
+fn build_dataset(position: &str, orientation: &str) -> Vec<DataElement> {
+    vec![
+        string("0018,0050", "DS", "2.5"),
+        string("0020,0032", "DS", position),
+        string("0020,0037", "DS", orientation),
+        element("0028,0010", "US", DicomValue::U16(vec![256])),
+        element("0028,0011", "US", DicomValue::U16(vec![128])),
+        string("0028,0030", "DS", "0.5\\0.25"),
+    ]
+}
+
+fn assert_close(actual: [f64; 3], expected: [f64; 3]) {
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn geometry_attributes_are_typed() {
+    let geometry =
+        geometry::get_image_geometry(&build_dataset("-100\\-50\\20", "1\\0\\0\\0\\1\\0")).unwrap();
+
+    assert_eq!((geometry.rows, geometry.columns), (256, 128));
+    assert_eq!(geometry.position, [-100.0, -50.0, 20.0]);
+    assert_eq!(geometry.pixel_spacing, (0.5, 0.25));
+    assert_eq!(geometry.slice_thickness, Some(2.5));
+    assert_eq!(geometry.spacing_between_slices, None);
+    assert_eq!(geometry::normal(&geometry), [0.0, 0.0, 1.0]);
+
+    // 缺少Image Orientation (Patient)
+    let mut data_elements = build_dataset("0\\0\\0", "1\\0\\0\\0\\1\\0");
+    data_elements.retain(|v| v.tag != "0020,0037");
+    assert!(geometry::get_image_geometry(&data_elements).is_err());
+}
+
+#[test]
+fn pixels_are_mapped_to_patient_coordinates_and_back() {
+    let geometry =
+        geometry::get_image_geometry(&build_dataset("-100\\-50\\20", "1\\0\\0\\0\\1\\0")).unwrap();
+
+    // 第10行第20列：x增加20 * 0.25，y增加10 * 0.5
+    assert_close(
+        geometry::pixel_to_patient(&geometry, 10.0, 20.0),
+        [-95.0, -45.0, 20.0],
+    );
+
+    let (row, column, distance) = geometry::patient_to_pixel(&geometry, [-95.0, -45.0, 23.0]);
+    assert_close([row, column, distance], [10.0, 20.0, 3.0]);
+
+    // 斜的平面也可以来回转换
+    let geometry =
+        geometry::get_image_geometry(&build_dataset("10\\20\\30", "0.8\\0.6\\0\\0\\0\\-1"))
+            .unwrap();
+    let point = geometry::pixel_to_patient(&geometry, 7.5, 3.25);
+    let (row, column, distance) = geometry::patient_to_pixel(&geometry, point);
+    assert_close([row, column, distance], [7.5, 3.25, 0.0]);
+}
+
+#[test]
+fn edges_are_labelled_with_patient_directions() {
+    // 轴位：左边是病人的右边，上边是病人的前面
+    let axial =
+        geometry::get_image_geometry(&build_dataset("0\\0\\0", "1\\0\\0\\0\\1\\0")).unwrap();
+    let labels = geometry::orientation_labels(&axial);
+
+    assert_eq!(
+        (
+            labels.left.as_str(),
+            labels.right.as_str(),
+            labels.top.as_str(),
+            labels.bottom.as_str()
+        ),
+        ("R", "L", "A", "P")
+    );
+
+    // 矢状位
+    let sagittal =
+        geometry::get_image_geometry(&build_dataset("0\\0\\0", "0\\1\\0\\0\\0\\-1")).unwrap();
+    let labels = geometry::orientation_labels(&sagittal);
+
+    assert_eq!((labels.left.as_str(), labels.top.as_str()), ("A", "H"));
+
+    // 斜的方向按照分量从大到小排列
+    assert_eq!(geometry::orientation_label([0.8, -0.6, 0.0]), "LA");
+    assert_eq!(geometry::orientation_label([-0.1, 0.2, -0.97]), "FPR");
+}
+
+#[test]
+fn enhanced_multi_frame_geometry_comes_from_functional_groups() {
+    let frame = |z: &str| {
+        vec![element(
+            "0020,9113",
+            "SQ",
+            DicomValue::Sequence(vec![vec![string(
+                "0020,0032",
+                "DS",
+                &format!("0\\0\\{}", z),
+            )]]),
+        )]
+    };
+
+    let data_elements = vec![
+        element("0028,0010", "US", DicomValue::U16(vec![4])),
+        element("0028,0011", "US", DicomValue::U16(vec![4])),
+        element(
+            "5200,9229",
+            "SQ",
+            DicomValue::Sequence(vec![vec![
+                element(
+                    "0020,9116",
+                    "SQ",
+                    DicomValue::Sequence(vec![vec![string("0020,0037", "DS", "1\\0\\0\\0\\1\\0")]]),
+                ),
+                element(
+                    "0028,9110",
+                    "SQ",
+                    DicomValue::Sequence(vec![vec![
+                        string("0028,0030", "DS", "1\\1"),
+                        string("0018,0050", "DS", "3"),
+                        string("0018,0088", "DS", "4"),
+                    ]]),
+                ),
+            ]]),
+        ),
+        element(
+            "5200,9230",
+            "SQ",
+            DicomValue::Sequence(vec![frame("0"), frame("4")]),
+        ),
+    ];
+
+    let geometry = geometry::get_frame_geometry(&data_elements, 1).unwrap();
+
+    assert_eq!(geometry.position, [0.0, 0.0, 4.0]);
+    assert_eq!(geometry.pixel_spacing, (1.0, 1.0));
+    assert_eq!(geometry.spacing_between_slices, Some(4.0));
+    assert_eq!(
+        geometry::get_frame_geometry(&data_elements, 0)
+            .unwrap()
+            .position,
+        [0.0, 0.0, 0.0]
+    );
+}
@@ -0,0 +1,166 @@
+use dicom_parser::{
+    lut,
+    model::{
+        DataElement, DicomValue, LookupTable, ModalityTransform, VoiLutFunction, VoiTransform,
+        Window,
+    },
+};
+
+mod common;
+
+use common::element;
+
+fn window(center: f64, width: f64, function: VoiLutFunction) -> VoiTransform {
+    VoiTransform::Window(Window {
+        center,
+        width,
+        function,
+        explanation: None,
+    })
+}
+
+fn lut_item(descriptor: [u16; 3], data: &[u16]) -> Vec<DataElement> {
+    vec![
+        element("0028,3002", "US", DicomValue::U16(descriptor.to_vec())),
+        element("0028,3006", "US", DicomValue::U16(data.to_vec())),
+    ]
+}
+
+#[test]
+fn every_window_preset_is_exposed() {
+    let data_elements = vec![
+        element("0028,1050", "DS", DicomValue::Double(vec![40.0, -600.0])),
+        element("0028,1051", "DS", DicomValue::Double(vec![400.0, 1500.0])),
+        element(
+            "0028,1055",
+            "LO",
+            DicomValue::String("SOFT TISSUE\\LUNG".to_string()),
+        ),
+        element("0028,1056", "CS", DicomValue::String("SIGMOID".to_string())),
+    ];
+
+    let windows = lut::get_windows(&data_elements).unwrap();
+
+    assert_eq!(windows.len(), 2);
+    assert_eq!(windows[1].center, -600.0);
+    assert_eq!(windows[1].width, 1500.0);
+    assert_eq!(windows[1].function, VoiLutFunction::Sigmoid);
+    assert_eq!(windows[1].explanation.as_deref(), Some("LUNG"));
+}
+
+#[test]
+fn mismatched_window_values_are_rejected() {
+    let data_elements = vec![
+        element("0028,1050", "DS", DicomValue::Double(vec![40.0, -600.0])),
+        element("0028,1051", "DS", DicomValue::Double(vec![400.0])),
+    ];
+
+    assert!(lut::get_windows(&data_elements).is_err());
+}
+
+#[test]
+fn window_functions() {
+    let linear = window(100.0, 101.0, VoiLutFunction::Linear);
+    assert_eq!(lut::apply_voi(49.5, &linear), 0.0);
+    assert_eq!(lut::apply_voi(99.5, &linear), 0.5);
+    assert_eq!(lut::apply_voi(150.0, &linear), 1.0);
+
+    let linear_exact = window(100.0, 100.0, VoiLutFunction::LinearExact);
+    assert_eq!(lut::apply_voi(50.0, &linear_exact), 0.0);
+    assert_eq!(lut::apply_voi(100.0, &linear_exact), 0.5);
+    assert_eq!(lut::apply_voi(125.0, &linear_exact), 0.75);
+
+    let sigmoid = window(100.0, 100.0, VoiLutFunction::Sigmoid);
+    assert_eq!(lut::apply_voi(100.0, &sigmoid), 0.5);
+    assert!(lut::apply_voi(300.0, &sigmoid) > 0.99);
+    assert!(lut::apply_voi(-100.0, &sigmoid) < 0.01);
+}
+
+#[test]
+fn modality_lut_sequence_takes_precedence_over_rescale() {
+    let data_elements = vec![
+        element("0028,1052", "DS", DicomValue::Double(vec![-1024.0])),
+        element("0028,1053", "DS", DicomValue::Double(vec![1.0])),
+        element(
+            "0028,3000",
+            "SQ",
+            DicomValue::Sequence(vec![lut_item([3, 10, 16], &[100, 200, 300])]),
+        ),
+    ];
+
+    let modality = lut::get_modality_transform(&data_elements, 0).unwrap();
+
+    assert_eq!(
+        modality,
+        ModalityTransform::Lut(LookupTable {
+            first_mapped_value: 10,
+            bits_per_entry: 16,
+            data: vec![100, 200, 300],
+        })
+    );
+    assert_eq!(lut::apply_modality(11, &modality), 200.0);
+    assert_eq!(lut::apply_modality(0, &modality), 100.0);
+}
+
+#[test]
+fn rescale_is_used_without_modality_lut() {
+    let data_elements = vec![
+        element("0028,1052", "DS", DicomValue::Double(vec![-1024.0])),
+        element("0028,1053", "DS", DicomValue::Double(vec![2.0])),
+    ];
+
+    let modality = lut::get_modality_transform(&data_elements, 1).unwrap();
+
+    assert_eq!(lut::apply_modality(-10, &modality), -1044.0);
+}
+
+#[test]
+fn voi_luts_follow_window_presets() {
+    let data_elements = vec![
+        element("0028,1050", "DS", DicomValue::Double(vec![40.0])),
+        element("0028,1051", "DS", DicomValue::Double(vec![400.0])),
+        element(
+            "0028,3010",
+            "SQ",
+            DicomValue::Sequence(vec![
+                lut_item([2, 0, 8], &[0, 255]),
+                lut_item([2, 0, 16], &[0, 65535]),
+            ]),
+        ),
+    ];
+
+    let transforms = lut::get_voi_transforms(&data_elements, 0).unwrap();
+
+    assert_eq!(transforms.len(), 3);
+    assert!(matches!(transforms[0], VoiTransform::Window(_)));
+    assert_eq!(lut::apply_voi(1.0, &transforms[1]), 1.0);
+    assert_eq!(lut::apply_voi(0.0, &transforms[2]), 0.0);
+}
+
+#[test]
+fn voi_lut_without_window_is_available() {
+    let data_elements = vec![element(
+        "0028,3010",
+        "SQ",
+        DicomValue::Sequence(vec![lut_item([4, 0, 8], &[0, 85, 170, 255])]),
+    )];
+
+    let transforms = lut::get_voi_transforms(&data_elements, 0).unwrap();
+
+    assert_eq!(transforms.len(), 1);
+    assert_eq!(lut::apply_voi(2.0, &transforms[0]), 170.0 / 255.0);
+}
+
+#[test]
+fn monochrome1_is_inverted() {
+    let modality = ModalityTransform::Rescale {
+        slope: 1.0,
+        intercept: 0.0,
+    };
+    let voi = window(50.0, 100.0, VoiLutFunction::LinearExact);
+
+    assert_eq!(
+        lut::apply_grayscale_pipeline(&[0, 50, 100], &modality, &voi, true),
+        vec![1.0, 0.5, 0.0]
+    );
+}
@@ -0,0 +1,194 @@
+use dicom_parser::{
+    accessor,
+    model::{DataElement, DicomValue, NiftiVersion, NpyData, TransferSyntax, VolumeIssue},
+    mosaic, nifti, volume,
+    writer::{self, WriteOptions},
+};
+
+mod common;
+
+use common::{element, string};
+
+// 4x4的mosaic，2x2的网格中每层是2x2，第index层的像素值为index * 10 + 行号 * 2 + 列号
+fn build_mosaic(count: u16, acquisition_number: i64, offset: u16) -> Vec<DataElement> {
+    let pixels = (0..16_u16)
+        .map(|v| {
+            let (row, column) = (v / 4, v % 4);
+            (row / 2 * 2 + column / 2) * 10 + row % 2 * 2 + column % 2 + offset
+        })
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<u8>>();
+
+    vec![
+        string(
+            "0002,0010",
+            "UI",
+            TransferSyntax::ExplicitVrLittleEndian.uid(),
+        ),
+        string("0008,0008", "CS", "ORIGINAL\\PRIMARY\\M\\ND\\MOSAIC"),
+        string("0018,0050", "DS", "2"),
+        string("0018,0088", "DS", "3"),
+        string("0019,0010", "LO", "SIEMENS MR HEADER"),
+        element("0019,100A", "US", DicomValue::U16(vec![count])),
+        string("0020,000E", "UI", "1.2.3"),
+        element("0020,0012", "IS", DicomValue::I64(vec![acquisition_number])),
+        string("0020,0032", "DS", "0\\0\\0"),
+        string("0020,0037", "DS", "1\\0\\0\\0\\1\\0"),
+        string("0028,0004", "CS", "MONOCHROME2"),
+        element("0028,0010", "US", DicomValue::U16(vec![4])),
+        element("0028,0011", "US", DicomValue::U16(vec![4])),
+        string("0028,0030", "DS", "1\\1"),
+        element("0028,0100", "US", DicomValue::U16(vec![16])),
+        element("0028,0101", "US", DicomValue::U16(vec![12])),
+        element("0028,0102", "US", DicomValue::U16(vec![11])),
+        element("0028,0103", "US", DicomValue::U16(vec![0])),
+        element("7FE0,0010", "OW", DicomValue::Bytes(pixels)),
+    ]
+}
+
+#[test]
+fn mosaic_is_detected_by_the_private_creator() {
+    let data_elements = build_mosaic(4, 1, 0);
+    assert_eq!(
+        mosaic::get_images_in_mosaic(&data_elements).unwrap(),
+        Some(4)
+    );
+
+    // Private Creator在其他的block中
+    let moved = data_elements
+        .iter()
+        .map(|v| match v.tag.as_str() {
+            "0019,0010" => string("0019,0012", "LO", "SIEMENS MR HEADER"),
+            "0019,100A" => element("0019,120A", "US", DicomValue::U16(vec![4])),
+            _ => v.clone(),
+        })
+        .collect::<Vec<DataElement>>();
+    assert_eq!(mosaic::get_images_in_mosaic(&moved).unwrap(), Some(4));
+
+    // 没有Private Creator的0019,100A不是Number of Images in Mosaic
+    let without_creator = data_elements
+        .iter()
+        .filter(|v| v.tag != "0019,0010")
+        .cloned()
+        .collect::<Vec<DataElement>>();
+    assert!(mosaic::get_images_in_mosaic(&without_creator).is_err());
+
+    let not_mosaic = without_creator
+        .into_iter()
+        .filter(|v| v.tag != "0008,0008")
+        .collect::<Vec<DataElement>>();
+    assert_eq!(mosaic::get_images_in_mosaic(&not_mosaic).unwrap(), None);
+}
+
+#[test]
+fn mosaic_is_unpacked_into_positioned_slices() {
+    let slices = mosaic::unpack_mosaic(&build_mosaic(4, 1, 0)).unwrap();
+
+    assert_eq!(slices.len(), 4);
+
+    for (index, slice) in slices.iter().enumerate() {
+        assert_eq!(accessor::get_u16(slice, "0028,0010").unwrap(), 2);
+        assert_eq!(accessor::get_u16(slice, "0028,0011").unwrap(), 2);
+        assert_eq!(
+            accessor::get_strs(slice, "0008,0008").unwrap(),
+            vec!["ORIGINAL", "PRIMARY", "M", "ND"]
+        );
+        assert_eq!(mosaic::get_images_in_mosaic(slice).unwrap(), None);
+
+        // 第一层的左上角在mosaic左上角向右、向下各移动1个像素的位置，之后每层沿着法线移动3mm
+        assert_eq!(
+            accessor::get_f64s(slice, "0020,0032").unwrap(),
+            vec![1.0, 1.0, index as f64 * 3.0]
+        );
+
+        let values = match &accessor::find_data_element(slice, "7FE0,0010")
+            .unwrap()
+            .data
+        {
+            DicomValue::Bytes(v) => v
+                .chunks(2)
+                .map(|v| u16::from_le_bytes([v[0], v[1]]))
+                .collect::<Vec<u16>>(),
+            data => panic!("unexpected pixel data {:?}", data),
+        };
+        let base = index as u16 * 10;
+        assert_eq!(values, vec![base, base + 1, base + 2, base + 3]);
+    }
+}
+
+#[test]
+fn mosaic_time_series_becomes_a_4d_volume() {
+    let directory =
+        std::env::temp_dir().join(format!("dicom_parser_mosaic_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    // 3层的mosaic，网格中最后一格是空的
+    for (file_name, acquisition_number, offset) in [("2.dcm", 2, 100), ("1.dcm", 1, 0)] {
+        writer::write_file(
+            directory.join(file_name).to_str().unwrap(),
+            &build_mosaic(3, acquisition_number, offset),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+    }
+
+    let volumes = volume::read_volumes(&directory).unwrap().0;
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(volumes.len(), 1);
+
+    let volume = &volumes[0];
+
+    assert!(volume.issues.is_empty());
+    assert_eq!(volume.shape, [3, 2, 2]);
+    assert_eq!(volume.time_points, 2);
+    assert_eq!(volume.spacing[2], 3.0);
+    assert_eq!(volume.affine[2][2], 3.0);
+
+    match &volume.data {
+        NpyData::U16(data) => {
+            assert_eq!(data.len(), 24);
+            // 第一个时间点的第3层，第二个时间点的第1层
+            assert_eq!((data[8], data[12]), (20, 100));
+        }
+        data => panic!("unexpected data {:?}", data),
+    }
+
+    let content = nifti::encode_nifti(volume, NiftiVersion::Nifti1).unwrap();
+    let dim = (0..5)
+        .map(|v| i16::from_le_bytes([content[40 + v * 2], content[41 + v * 2]]))
+        .collect::<Vec<i16>>();
+
+    assert_eq!(dim, vec![4, 2, 2, 3, 2]);
+}
+
+#[test]
+fn mosaic_that_can_not_be_unpacked_is_excluded_with_its_reason() {
+    let directory =
+        std::env::temp_dir().join(format!("dicom_parser_bad_mosaic_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    // 9层需要3x3的网格，4x4的图像无法等分
+    for (file_name, count) in [("1.dcm", 4), ("2.dcm", 9)] {
+        writer::write_file(
+            directory.join(file_name).to_str().unwrap(),
+            &build_mosaic(count, 1, 0),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+    }
+
+    let (volumes, failures) = volume::read_volumes(&directory).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert!(failures.is_empty());
+    assert_eq!(volumes[0].shape, [4, 2, 2]);
+
+    match &volumes[0].issues[..] {
+        [VolumeIssue::Excluded { file_path, reason }] => {
+            assert!(file_path.ends_with("2.dcm"));
+            assert!(reason.contains("can not be divided"));
+        }
+        issues => panic!("unexpected issues {:?}", issues),
+    }
+}
@@ -0,0 +1,234 @@
+use dicom_parser::{
+    geometry,
+    model::{
+        DataElement, DicomValue, NpyData, Projection, RenderOptions, ResliceOptions, ReslicePlane,
+        VoiLutFunction, Volume, Window,
+    },
+    mpr,
+};
+
+mod common;
+
+use common::element;
+
+// 3层、每层4x5、体素大小为1mm的轴位体数据，体素值为层号 * 100 + 行号 * 10 + 列号
+fn build_volume(attributes: Vec<DataElement>) -> Volume {
+    let data = (0..3_i16)
+        .flat_map(|slice| {
+            (0..4_i16)
+                .flat_map(move |row| (0..5_i16).map(move |column| slice * 100 + row * 10 + column))
+        })
+        .collect::<Vec<i16>>();
+
+    Volume {
+        series_instance_uid: "1.2.3".to_string(),
+        shape: [3, 4, 5],
+        time_points: 1,
+        data: NpyData::I16(data),
+        rescale_slope: 1.0,
+        rescale_intercept: 0.0,
+        spacing: [1.0, 1.0, 1.0],
+        affine: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+        file_paths: Vec::new(),
+        acquisition_times: Vec::new(),
+        attributes,
+        issues: Vec::new(),
+    }
+}
+
+#[test]
+fn orthogonal_planes_are_resliced_through_the_center() {
+    let volume = build_volume(Vec::new());
+
+    // 横断面经过中间一层，和原来的层完全相同
+    let axial = mpr::reslice(&volume, &ResliceOptions::default()).unwrap();
+    assert_eq!((axial.geometry.rows, axial.geometry.columns), (4, 5));
+    assert_eq!(axial.geometry.position, [0.0, 0.0, 1.0]);
+    assert_eq!(axial.values[..5], [100.0, 101.0, 102.0, 103.0, 104.0]);
+    assert_eq!(axial.values[19], 134.0);
+
+    // 冠状面经过y = 1.5，在第1行和第2行之间插值，第一行是最上面的一层
+    let coronal = mpr::reslice(
+        &volume,
+        &ResliceOptions {
+            plane: ReslicePlane::Coronal,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!((coronal.geometry.rows, coronal.geometry.columns), (3, 5));
+    assert_eq!(coronal.values[..2], [215.0, 216.0]);
+    assert_eq!(coronal.values[10], 15.0);
+
+    let labels = geometry::orientation_labels(&coronal.geometry);
+    assert_eq!((labels.left.as_str(), labels.top.as_str()), ("R", "H"));
+
+    // 矢状面经过x = 2，沿着一行从前向后
+    let sagittal = mpr::reslice(
+        &volume,
+        &ResliceOptions {
+            plane: ReslicePlane::Sagittal,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!((sagittal.geometry.rows, sagittal.geometry.columns), (3, 4));
+    assert_eq!(sagittal.values[..4], [202.0, 212.0, 222.0, 232.0]);
+}
+
+#[test]
+fn oblique_plane_uses_trilinear_interpolation() {
+    let volume = build_volume(Vec::new());
+
+    // 沿着一行的方向是(1, 0, 1)，经过(1, 1, 0.5)的点
+    let reslice = mpr::reslice(
+        &volume,
+        &ResliceOptions {
+            plane: ReslicePlane::Oblique {
+                row_direction: [1.0, 0.0, 1.0],
+                column_direction: [0.0, 1.0, 0.0],
+            },
+            center: Some([1.0, 1.0, 0.5]),
+            pixel_spacing: Some(0.5_f64.sqrt()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // 体素值是坐标的线性函数，三线性插值的结果也应该是100 * z + 10 * y + x
+    let columns = reslice.geometry.columns as usize;
+    let spacing = reslice.geometry.pixel_spacing.0;
+    let mut count = 0;
+
+    for (index, value) in reslice.values.iter().enumerate() {
+        if value.is_nan() {
+            continue;
+        }
+
+        let (row, column) = (index / columns, index % columns);
+        let [x, y, z] = [0, 1, 2].map(|axis| {
+            reslice.geometry.position[axis]
+                + reslice.geometry.row_direction[axis] * column as f64 * spacing
+                + reslice.geometry.column_direction[axis] * row as f64 * spacing
+        });
+
+        // 边界外半个体素以内的点使用边界上的值
+        if [x, y, z]
+            .iter()
+            .zip([4.0, 3.0, 2.0])
+            .all(|(v, max)| (0.0..=max).contains(v))
+        {
+            assert!((value - (100.0 * z + 10.0 * y + x)).abs() < 1e-9);
+            count += 1;
+        }
+    }
+
+    assert!(count > 0);
+
+    // 切面上超出体数据的角是NaN
+    assert!(reslice.values.iter().any(|v| v.is_nan()));
+
+    assert!(mpr::reslice(
+        &volume,
+        &ResliceOptions {
+            plane: ReslicePlane::Oblique {
+                row_direction: [1.0, 0.0, 0.0],
+                column_direction: [1.0, 1.0, 0.0],
+            },
+            ..Default::default()
+        },
+    )
+    .is_err());
+    assert!(mpr::reslice(
+        &volume,
+        &ResliceOptions {
+            time_point: 1,
+            ..Default::default()
+        },
+    )
+    .is_err());
+}
+
+#[test]
+fn slab_projections_skip_padding() {
+    // 第0层的第一个体素是填充像素
+    let mut volume = build_volume(vec![
+        element("0028,0103", "US", DicomValue::U16(vec![1])),
+        element("0028,0120", "SS", DicomValue::I16(vec![-2000])),
+    ]);
+
+    if let NpyData::I16(data) = &mut volume.data {
+        data[0] = -2000;
+    }
+
+    let project = |projection| {
+        mpr::reslice(
+            &volume,
+            &ResliceOptions {
+                slab_thickness: 2.0,
+                projection,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .values
+    };
+
+    let maximum = project(Projection::Maximum);
+    let minimum = project(Projection::Minimum);
+    let average = project(Projection::Average);
+
+    assert_eq!((maximum[1], minimum[1], average[1]), (201.0, 1.0, 101.0));
+    assert_eq!((maximum[0], minimum[0], average[0]), (200.0, 100.0, 150.0));
+}
+
+#[test]
+fn reslice_is_rendered_with_the_window() {
+    let volume = build_volume(Vec::new());
+    let file_path =
+        std::env::temp_dir().join(format!("dicom_parser_mpr_{}.png", std::process::id()));
+
+    let render_options = RenderOptions {
+        window: Some(Window {
+            center: 150.0,
+            width: 100.0,
+            function: VoiLutFunction::LinearExact,
+            explanation: None,
+        }),
+        ..Default::default()
+    };
+
+    mpr::reslice_to_file(
+        &volume,
+        &file_path,
+        &ResliceOptions {
+            plane: ReslicePlane::Coronal,
+            ..Default::default()
+        },
+        &render_options,
+    )
+    .unwrap();
+
+    let image = image::open(&file_path).unwrap().into_luma8();
+    std::fs::remove_file(&file_path).unwrap();
+
+    assert_eq!(image.dimensions(), (5, 3));
+    // 最上面一行（第2层）超出窗口显示为白色，最下面一行（第0层）为黑色
+    assert_eq!(image.get_pixel(0, 0).0, [255]);
+    assert_eq!(image.get_pixel(0, 1).0, [38]);
+    assert_eq!(image.get_pixel(0, 2).0, [0]);
+
+    // 没有窗宽窗位时按照最小值和最大值自动计算
+    let reslice = mpr::reslice(&volume, &ResliceOptions::default()).unwrap();
+    let image = mpr::render_reslice(&volume, &reslice, &RenderOptions::default())
+        .unwrap()
+        .into_luma8();
+
+    assert_eq!(image.get_pixel(0, 0).0, [0]);
+    assert_eq!(image.get_pixel(4, 3).0, [255]);
+}
@@ -0,0 +1,226 @@
+use dicom_parser::{
+    accessor,
+    model::{DataElement, DicomValue, NiftiVersion, NpyData, Volume},
+    nifti,
+};
+
+mod common;
+
+use common::element;
+
+fn f32_at(content: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes(content[offset..offset + 4].try_into().unwrap())
+}
+
+fn f64_at(content: &[u8], offset: usize) -> f64 {
+    f64::from_le_bytes(content[offset..offset + 8].try_into().unwrap())
+}
+
+fn i16_at(content: &[u8], offset: usize) -> i16 {
+    i16::from_le_bytes(content[offset..offset + 2].try_into().unwrap())
+}
+
+// 2层、每层2x3的轴位CT体数据
+fn build_volume(affine: [[f64; 4]; 4], attributes: Vec<DataElement>) -> Volume {
+    Volume {
+        series_instance_uid: "1.2.3".to_string(),
+        shape: [2, 2, 3],
+        time_points: 1,
+        data: NpyData::I16((0..12).map(|v| v * 10 - 50).collect()),
+        rescale_slope: 1.0,
+        rescale_intercept: -1024.0,
+        spacing: [0.25, 0.5, 2.5],
+        affine,
+        file_paths: Vec::new(),
+        acquisition_times: vec![Some(100.0), Some(100.5)],
+        attributes,
+        issues: Vec::new(),
+    }
+}
+
+const AXIAL: [[f64; 4]; 4] = [
+    [0.25, 0.0, 0.0, -10.0],
+    [0.0, 0.5, 0.0, -20.0],
+    [0.0, 0.0, 2.5, 30.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+#[test]
+fn nifti1_header_has_geometry_and_rescale() {
+    let content =
+        nifti::encode_nifti(&build_volume(AXIAL, Vec::new()), NiftiVersion::Nifti1).unwrap();
+
+    assert_eq!(content.len(), 352 + 24);
+    assert_eq!(i32::from_le_bytes(content[..4].try_into().unwrap()), 348);
+    assert_eq!(&content[344..348], b"n+1\0");
+
+    // dim和datatype（DT_INT16）
+    assert_eq!(
+        (0..4)
+            .map(|v| i16_at(&content, 40 + v * 2))
+            .collect::<Vec<i16>>(),
+        vec![3, 3, 2, 2]
+    );
+    assert_eq!((i16_at(&content, 70), i16_at(&content, 72)), (4, 16));
+    assert_eq!(
+        (1..4)
+            .map(|v| f32_at(&content, 76 + v * 4))
+            .collect::<Vec<f32>>(),
+        vec![0.25, 0.5, 2.5]
+    );
+    assert_eq!(f32_at(&content, 108), 352.0);
+    assert_eq!(
+        (f32_at(&content, 112), f32_at(&content, 116)),
+        (1.0, -1024.0)
+    );
+
+    // LPS转换成RAS，x和y取反
+    assert_eq!(
+        (0..4)
+            .map(|v| f32_at(&content, 280 + v * 4))
+            .collect::<Vec<f32>>(),
+        vec![-0.25, 0.0, 0.0, 10.0]
+    );
+    assert_eq!(
+        (0..4)
+            .map(|v| f32_at(&content, 296 + v * 4))
+            .collect::<Vec<f32>>(),
+        vec![0.0, -0.5, 0.0, 20.0]
+    );
+
+    // 绕z轴旋转180度
+    assert_eq!((i16_at(&content, 252), i16_at(&content, 254)), (1, 1));
+    assert_eq!(f32_at(&content, 76), 1.0);
+    assert_eq!(
+        (0..6)
+            .map(|v| f32_at(&content, 256 + v * 4))
+            .collect::<Vec<f32>>(),
+        vec![0.0, 0.0, 1.0, 10.0, 20.0, 30.0]
+    );
+
+    assert_eq!(i16_at(&content, 352), -50);
+    assert_eq!(i16_at(&content, 352 + 22), 60);
+}
+
+#[test]
+fn nifti2_header_uses_64_bit_fields() {
+    let content =
+        nifti::encode_nifti(&build_volume(AXIAL, Vec::new()), NiftiVersion::Nifti2).unwrap();
+
+    assert_eq!(content.len(), 544 + 24);
+    assert_eq!(i32::from_le_bytes(content[..4].try_into().unwrap()), 540);
+    assert_eq!(&content[4..12], b"n+2\0\r\n\x1a\n");
+    assert_eq!(
+        (0..4)
+            .map(|v| i64::from_le_bytes(content[16 + v * 8..24 + v * 8].try_into().unwrap()))
+            .collect::<Vec<i64>>(),
+        vec![3, 3, 2, 2]
+    );
+    assert_eq!(
+        i64::from_le_bytes(content[168..176].try_into().unwrap()),
+        544
+    );
+    assert_eq!(f64_at(&content, 184), -1024.0);
+    assert_eq!(f64_at(&content, 464 + 16), 2.5);
+    assert_eq!(f64_at(&content, 464 + 24), 30.0);
+    assert_eq!(i16_at(&content, 544), -50);
+}
+
+#[test]
+fn tilted_volume_keeps_the_shear_in_the_sform_only() {
+    // 矢状位：沿着一行指向病人的前面（-y），沿着一列指向脚（-z），层的方向向左并且有倾斜
+    let affine = [
+        [0.0, 0.0, 2.0, 0.0],
+        [-1.0, 0.0, 1.0, 0.0],
+        [0.0, -1.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    let content =
+        nifti::encode_nifti(&build_volume(affine, Vec::new()), NiftiVersion::Nifti2).unwrap();
+
+    // sform的第三列保留倾斜
+    assert_eq!(
+        (0..3)
+            .map(|v| f64_at(&content, 400 + v * 32 + 16))
+            .collect::<Vec<f64>>(),
+        vec![-2.0, -1.0, 0.0]
+    );
+
+    // qform的第三列是法线方向，由四元数还原出旋转矩阵
+    let (b, c, d) = (
+        f64_at(&content, 352),
+        f64_at(&content, 360),
+        f64_at(&content, 368),
+    );
+    let a = (1.0 - b * b - c * c - d * d).max(0.0).sqrt();
+    let rotation = [
+        [
+            a * a + b * b - c * c - d * d,
+            2.0 * (b * c - a * d),
+            2.0 * (b * d + a * c),
+        ],
+        [
+            2.0 * (b * c + a * d),
+            a * a + c * c - b * b - d * d,
+            2.0 * (c * d - a * b),
+        ],
+        [
+            2.0 * (b * d - a * c),
+            2.0 * (c * d + a * b),
+            a * a + d * d - c * c - b * b,
+        ],
+    ];
+    let expected = [[0.0, 0.0, -1.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]];
+    let qfac = f64_at(&content, 104);
+
+    for row in 0..3 {
+        for column in 0..3 {
+            let value = rotation[row][column] * if column == 2 { qfac } else { 1.0 };
+            assert!((value - expected[row][column]).abs() < 1e-9);
+        }
+    }
+}
+
+#[test]
+fn bids_sidecar_is_written_next_to_the_image() {
+    let attributes = vec![
+        element("0008,0060", "CS", DicomValue::String("MR".to_string())),
+        element("0018,0080", "DS", DicomValue::Double(vec![2000.0])),
+        element("0018,0081", "DS", DicomValue::Double(vec![30.0])),
+        element("0018,1314", "DS", DicomValue::Double(vec![90.0])),
+        element("0020,0011", "IS", DicomValue::I64(vec![7])),
+    ];
+
+    let file_path = std::env::temp_dir().join(format!("dicom_parser_{}.nii", std::process::id()));
+    let sidecar_path = file_path.with_extension("json");
+
+    nifti::write_nifti(
+        &build_volume(AXIAL, attributes),
+        &file_path,
+        NiftiVersion::Nifti1,
+    )
+    .unwrap();
+
+    let content = std::fs::read(&file_path).unwrap();
+    let sidecar = std::fs::read_to_string(&sidecar_path).unwrap();
+
+    std::fs::remove_file(&file_path).unwrap();
+    std::fs::remove_file(&sidecar_path).unwrap();
+
+    // pixdim[4]是以秒为单位的TR
+    assert_eq!(f32_at(&content, 76 + 16), 2.0);
+
+    assert!(sidecar.contains("\"Modality\": \"MR\""));
+    assert!(sidecar.contains("\"SeriesNumber\": 7"));
+    assert!(sidecar.contains("\"RepetitionTime\": 2"));
+    assert!(sidecar.contains("\"EchoTime\": 0.03,"));
+    assert!(sidecar.contains("\"FlipAngle\": 90"));
+    assert!(sidecar.contains("\"SliceTiming\": [0, 0.5]"));
+    assert!(!sidecar.contains("InversionTime"));
+
+    // Acquisition Time按照TM解析成从0点开始的秒数
+    assert_eq!(accessor::parse_time("010203.25"), Some(3723.25));
+    assert_eq!(accessor::parse_time("01:02"), Some(3720.0));
+    assert_eq!(accessor::parse_time("2500"), None);
+}
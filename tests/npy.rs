@@ -0,0 +1,163 @@
+use dicom_parser::{
+    export,
+    model::{DataElement, DicomValue, NpyArray, NpyData},
+    writer::{self, WriteOptions},
+};
+
+mod common;
+
+use common::{element, words, Grayscale};
+
+// 2帧、每帧2x3的16位灰度图像
+fn build_dataset(pixel_representation: u16, instance_number: i64) -> Vec<DataElement> {
+    let pixels = (0..12_i16).map(|v| v * 100 - 500).collect::<Vec<i16>>();
+
+    Grayscale {
+        rows: 2,
+        columns: 3,
+        frames: 2,
+        pixel_representation,
+        ..Default::default()
+    }
+    .build(
+        words(&pixels),
+        vec![
+            element("0020,0013", "IS", DicomValue::I64(vec![instance_number])),
+            element("0028,1052", "DS", DicomValue::Double(vec![0.5])),
+            element("0028,1053", "DS", DicomValue::Double(vec![2.0])),
+        ],
+    )
+}
+
+fn npy_header(content: &[u8]) -> (String, usize) {
+    let header_length = u16::from_le_bytes([content[8], content[9]]) as usize;
+
+    (
+        String::from_utf8(content[10..10 + header_length].to_vec()).unwrap(),
+        10 + header_length,
+    )
+}
+
+#[test]
+fn stored_values_keep_their_dtype() {
+    let signed = export::pixel_array(&build_dataset(1, 1), false).unwrap();
+
+    assert_eq!(signed.shape, vec![2, 2, 3, 1]);
+    match &signed.data {
+        NpyData::I16(values) => assert_eq!(values[..3], [-500, -400, -300]),
+        data => panic!("unexpected dtype {:?}", data),
+    }
+
+    let unsigned = export::pixel_array(&build_dataset(0, 1), false).unwrap();
+    assert!(matches!(unsigned.data, NpyData::U16(_)));
+
+    let modality = export::pixel_array(&build_dataset(1, 1), true).unwrap();
+    match &modality.data {
+        NpyData::F32(values) => assert_eq!(values[0], -999.5),
+        data => panic!("unexpected dtype {:?}", data),
+    }
+}
+
+#[test]
+fn npy_is_c_ordered_with_the_full_shape() {
+    let content =
+        export::encode_npy(&export::pixel_array(&build_dataset(1, 1), false).unwrap()).unwrap();
+    let (header, data_offset) = npy_header(&content);
+
+    assert!(header.contains("'descr': '<i2'"));
+    assert!(header.contains("'fortran_order': False"));
+    assert!(header.contains("'shape': (2, 2, 3, 1)"));
+    assert_eq!(data_offset % 64, 0);
+    assert_eq!(content.len() - data_offset, 12 * 2);
+
+    // 第二帧的第一个像素紧跟在第一帧的最后一个像素之后
+    assert_eq!(
+        i16::from_le_bytes([content[data_offset + 12], content[data_offset + 13]]),
+        100
+    );
+}
+
+#[test]
+fn mismatched_shape_is_rejected() {
+    let array = NpyArray {
+        shape: vec![2, 2],
+        data: NpyData::U8(vec![0; 3]),
+    };
+
+    assert!(export::encode_npy(&array).is_err());
+}
+
+#[test]
+fn series_is_written_to_one_npz() {
+    let directory = std::env::temp_dir().join(format!("dicom_parser_npz_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    // 文件名的顺序与Instance Number相反
+    for (file_name, instance_number) in [("a.dcm", 2), ("b.dcm", 1)] {
+        writer::write_file(
+            directory.join(file_name).to_str().unwrap(),
+            &build_dataset(1, instance_number),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+    }
+    std::fs::write(directory.join("notes.txt"), "not a dicom file").unwrap();
+
+    let npz_path = std::env::temp_dir().join(format!("dicom_parser_{}.npz", std::process::id()));
+
+    let (count, skipped) = export::export_series_npz(&directory, &npz_path, false).unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].file_path, directory.join("notes.txt"));
+
+    let content = std::fs::read(&npz_path).unwrap();
+
+    assert_eq!(&content[..4], b"PK\x03\x04");
+    assert_eq!(&content[30..30 + 17], b"instance_0001.npy");
+
+    // end of central directory中记录了2个文件
+    let end = &content[content.len() - 22..];
+    assert_eq!(&end[..4], b"PK\x05\x06");
+    assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+
+    // 第一个文件的crc与数据一致
+    let size = u32::from_le_bytes([content[18], content[19], content[20], content[21]]) as usize;
+    let crc = u32::from_le_bytes([content[14], content[15], content[16], content[17]]);
+    assert_eq!(crc32fast::hash(&content[47..47 + size]), crc);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+    std::fs::remove_file(&npz_path).unwrap();
+}
+
+#[test]
+fn directory_with_several_series_is_rejected() {
+    let directory =
+        std::env::temp_dir().join(format!("dicom_parser_npz_mixed_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    for (file_name, series_instance_uid) in [("a.dcm", "1.2.3"), ("b.dcm", "1.2.4")] {
+        let mut data_elements = build_dataset(1, 1);
+        data_elements.push(element(
+            "0020,000E",
+            "UI",
+            DicomValue::String(series_instance_uid.to_string()),
+        ));
+
+        writer::write_file(
+            directory.join(file_name).to_str().unwrap(),
+            &data_elements,
+            &WriteOptions::default(),
+        )
+        .unwrap();
+    }
+
+    let npz_path =
+        std::env::temp_dir().join(format!("dicom_parser_mixed_{}.npz", std::process::id()));
+    let error = export::export_series_npz(&directory, &npz_path, false).unwrap_err();
+
+    assert!(error.to_string().contains("2 series"));
+    assert!(!npz_path.exists());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
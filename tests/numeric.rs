@@ -0,0 +1,111 @@
+use dicom_parser::{
+    model::{DicomValue, NumericParseMode, ParseOptions, ValueError},
+    service, util,
+};
+
+const LENIENT: NumericParseMode = NumericParseMode::Lenient;
+const STRICT: NumericParseMode = NumericParseMode::Strict;
+
+// 出错的值在多值字符串中的位置
+fn error_indexes(errors: &[ValueError]) -> Vec<usize> {
+    errors.iter().map(|v| v.index).collect()
+}
+
+#[test]
+fn decimal_strings_accept_padding_and_exponents() {
+    for mode in [LENIENT, STRICT] {
+        let (values, errors) = util::parse_decimal_strings(" 1.5\\-2 \\1e3\\+.5E-1", mode);
+
+        assert_eq!(values, vec![1.5, -2.0, 1000.0, 0.05]);
+        assert!(errors.is_empty());
+
+        // 整个值为空时VM为0
+        let (values, errors) = util::parse_decimal_strings("  ", mode);
+        assert!(values.is_empty() && errors.is_empty());
+    }
+
+    // 宽松模式下允许NUL填充，严格模式下只允许空格
+    let (values, errors) = util::parse_decimal_strings("1.5\0", LENIENT);
+    assert_eq!(values, vec![1.5]);
+    assert!(errors.is_empty());
+
+    let (values, errors) = util::parse_decimal_strings("1.5\0", STRICT);
+    assert!(values.is_empty());
+    assert_eq!(errors[0].message, "invalid character in DS value");
+}
+
+#[test]
+fn decimal_string_errors_are_recorded_per_value() {
+    // 空值只在严格模式下是错误
+    let (values, errors) = util::parse_decimal_strings("1\\\\3\\x\\1e400", LENIENT);
+    assert_eq!(values, vec![1.0, 3.0]);
+    assert_eq!(error_indexes(&errors), vec![3, 4]);
+    assert_eq!(errors[0].raw, "x");
+    assert_eq!(errors[1].message, "DS value out of range");
+
+    let (values, errors) = util::parse_decimal_strings("1\\\\3\\x\\1e400", STRICT);
+    assert_eq!(values, vec![1.0, 3.0]);
+    assert_eq!(error_indexes(&errors), vec![1, 3, 4]);
+    assert_eq!(errors[0].message, "empty value");
+
+    // 严格模式下每个值最多16字节
+    let (values, errors) = util::parse_decimal_strings("1.00000000000000001", STRICT);
+    assert!(values.is_empty());
+    assert_eq!(errors[0].message, "DS value exceeds 16 bytes");
+
+    let (values, _) = util::parse_decimal_strings("1.00000000000000001", LENIENT);
+    assert_eq!(values, vec![1.0]);
+}
+
+#[test]
+fn integer_strings_in_lenient_and_strict_modes() {
+    let raw = " 12 \\2147483648\\12.0\\1e3\\99999999999999999999\\1.5\\abc";
+
+    // 宽松模式接受超出i32的值和小数部分为0的写法
+    let (values, errors) = util::parse_integer_strings(raw, LENIENT);
+    assert_eq!(values, vec![12, 2147483648, 12, 1000]);
+    assert_eq!(error_indexes(&errors), vec![4, 5, 6]);
+    assert_eq!(errors[0].message, "IS value out of range");
+    assert_eq!(errors[1].message, "not an integer");
+
+    // 严格模式只接受i32范围内的整数
+    let (values, errors) = util::parse_integer_strings(raw, STRICT);
+    assert_eq!(values, vec![12]);
+    assert_eq!(error_indexes(&errors), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(errors[0].message, "IS value out of range");
+    assert_eq!(errors[1].message, "invalid character in IS value");
+    assert_eq!(errors[3].message, "IS value exceeds 12 bytes");
+
+    let (values, errors) = util::parse_integer_strings("-2147483648\\", STRICT);
+    assert_eq!(values, vec![-2147483648]);
+    assert_eq!(errors[0].message, "empty value");
+
+    let (values, errors) = util::parse_integer_strings("-2147483648\\", LENIENT);
+    assert_eq!(values, vec![-2147483648]);
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn value_errors_are_kept_on_the_data_element() {
+    // (0018,0050) DS "1.5\x "，显式小端
+    let mut buffer = vec![0x18, 0x00, 0x50, 0x00, b'D', b'S', 6, 0];
+    buffer.extend(b"1.5\\x ");
+
+    let lenient = ParseOptions {
+        numeric_mode: LENIENT,
+        ..ParseOptions::default()
+    };
+    let (data_element, length) = service::get_data_element_with_options(&buffer, &lenient).unwrap();
+
+    assert_eq!(length, buffer.len());
+    assert!(matches!(data_element.data, DicomValue::Double(ref v) if v == &vec![1.5]));
+    assert_eq!(error_indexes(&data_element.value_errors), vec![1]);
+    assert_eq!(data_element.value_errors[0].raw, "x ");
+
+    // 严格模式下整个元素解析失败
+    let strict = ParseOptions {
+        numeric_mode: STRICT,
+        ..ParseOptions::default()
+    };
+    assert!(service::get_data_element_with_options(&buffer, &strict).is_err());
+}
@@ -0,0 +1,139 @@
+use dicom_parser::{
+    model::{DataElement, DicomValue, RenderOptions},
+    overlay, render,
+};
+
+mod common;
+
+use common::{element, words, Grayscale};
+
+// 4x4、像素值全为0的灰度图像，pixels可以在高位中嵌入overlay
+fn build_dataset(pixels: &[u16]) -> Vec<DataElement> {
+    Grayscale {
+        rows: 4,
+        columns: 4,
+        bits_stored: 12,
+        ..Default::default()
+    }
+    .build(
+        words(pixels),
+        vec![
+            element("0028,1050", "DS", DicomValue::Double(vec![2048.0])),
+            element("0028,1051", "DS", DicomValue::Double(vec![4096.0])),
+        ],
+    )
+}
+
+// 2x2的overlay，位于图像的第2行第3列，只有左上角和右下角两个点
+fn overlay_elements(group: &str) -> Vec<DataElement> {
+    vec![
+        element(&format!("{},0010", group), "US", DicomValue::U16(vec![2])),
+        element(&format!("{},0011", group), "US", DicomValue::U16(vec![2])),
+        element(
+            &format!("{},0022", group),
+            "LO",
+            DicomValue::String("MARK".to_string()),
+        ),
+        element(
+            &format!("{},0040", group),
+            "CS",
+            DicomValue::String("G".to_string()),
+        ),
+        element(
+            &format!("{},0050", group),
+            "SS",
+            DicomValue::I16(vec![2, 3]),
+        ),
+        element(&format!("{},0100", group), "US", DicomValue::U16(vec![1])),
+        element(&format!("{},0102", group), "US", DicomValue::U16(vec![0])),
+        element(
+            &format!("{},3000", group),
+            "OW",
+            DicomValue::Bytes(vec![0b1001, 0]),
+        ),
+    ]
+}
+
+#[test]
+fn overlay_attributes_are_parsed_for_every_group() {
+    let mut data_elements = build_dataset(&[0; 16]);
+    data_elements.extend(overlay_elements("6000"));
+    data_elements.extend(overlay_elements("601E"));
+
+    let overlays = overlay::get_overlays(&data_elements).unwrap();
+
+    assert_eq!(overlays.len(), 2);
+    assert_eq!(overlays[1].group, 0x601E);
+    assert_eq!(overlays[0].origin, (2, 3));
+    assert_eq!(overlays[0].description.as_deref(), Some("MARK"));
+    assert_eq!(overlays[0].data, vec![true, false, false, true]);
+}
+
+#[test]
+fn overlay_is_placed_at_its_origin() {
+    let mut data_elements = build_dataset(&[0; 16]);
+    data_elements.extend(overlay_elements("6002"));
+
+    let overlay = overlay::get_overlay(&data_elements, 0x6002).unwrap();
+    let mask = overlay::overlay_mask(&overlay, 0, 4, 4).unwrap();
+
+    let visible = mask
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| **v)
+        .map(|(index, _)| index)
+        .collect::<Vec<usize>>();
+
+    // 第2行第3列和第3行第4列
+    assert_eq!(visible, vec![4 + 2, 2 * 4 + 3]);
+
+    // 单帧的overlay不显示在第二帧上
+    assert!(overlay::overlay_mask(&overlay, 1, 4, 4).is_none());
+}
+
+#[test]
+fn embedded_overlay_is_read_from_unused_high_bits() {
+    // 第15位是overlay，低12位是像素值
+    let pixels = (0..16_u16)
+        .map(|v| if v % 5 == 0 { 0x8000 | v } else { v })
+        .collect::<Vec<u16>>();
+
+    let mut data_elements = build_dataset(&pixels);
+    data_elements.extend([
+        element("6000,0010", "US", DicomValue::U16(vec![4])),
+        element("6000,0011", "US", DicomValue::U16(vec![4])),
+        element("6000,0100", "US", DicomValue::U16(vec![16])),
+        element("6000,0102", "US", DicomValue::U16(vec![15])),
+    ]);
+
+    let overlay = overlay::get_overlay(&data_elements, 0x6000).unwrap();
+
+    assert_eq!(
+        overlay.data,
+        (0..16).map(|v| v % 5 == 0).collect::<Vec<bool>>()
+    );
+}
+
+#[test]
+fn overlays_are_burned_in_with_the_chosen_color() {
+    let mut data_elements = build_dataset(&[0; 16]);
+    data_elements.extend(overlay_elements("6000"));
+
+    let image = render::render(
+        &data_elements,
+        &RenderOptions {
+            overlay_color: Some([255, 0, 0]),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .to_rgb8();
+
+    assert_eq!(image.get_pixel(2, 1).0, [255, 0, 0]);
+    assert_eq!(image.get_pixel(3, 2).0, [255, 0, 0]);
+    assert_eq!(image.get_pixel(3, 1).0, [0, 0, 0]);
+
+    // 默认不画overlay
+    let image = render::render(&data_elements, &RenderOptions::default()).unwrap();
+    assert_eq!(image.color(), image::ColorType::L8);
+}
@@ -0,0 +1,202 @@
+use dicom_parser::{
+    export, lut,
+    model::{
+        DataElement, DicomValue, ExportFormat, ModalityTransform, PaddingDisplay, PixelPadding,
+        RenderOptions, VoiTransform,
+    },
+    padding, pixel, render,
+};
+
+mod common;
+
+use common::{element, words, Grayscale};
+
+// 2x3的16位有符号CT图像，第一行是扫描野之外的填充像素
+fn build_dataset(padding: Vec<DataElement>) -> Vec<DataElement> {
+    Grayscale {
+        rows: 2,
+        columns: 3,
+        pixel_representation: 1,
+        ..Default::default()
+    }
+    .build(words(&[-2000_i16, -2000, -1999, 0, 50, 100]), padding)
+}
+
+fn padding_value() -> Vec<DataElement> {
+    vec![element("0028,0120", "SS", DicomValue::I16(vec![-2000]))]
+}
+
+fn padding_range() -> Vec<DataElement> {
+    vec![
+        element("0028,0120", "US", DicomValue::U16(vec![-2000_i16 as u16])),
+        element("0028,0121", "US", DicomValue::U16(vec![-1999_i16 as u16])),
+    ]
+}
+
+#[test]
+fn padding_value_and_range_limit_are_read_as_stored_values() {
+    assert_eq!(
+        padding::get_pixel_padding(&build_dataset(Vec::new()), 1).unwrap(),
+        None
+    );
+
+    assert_eq!(
+        padding::get_pixel_padding(&build_dataset(padding_value()), 1).unwrap(),
+        Some(PixelPadding {
+            lower: -2000,
+            upper: -2000
+        })
+    );
+
+    // 用US编码的负值按照Pixel Representation重新解释
+    let range = padding::get_pixel_padding(&build_dataset(padding_range()), 1)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        range,
+        PixelPadding {
+            lower: -2000,
+            upper: -1999
+        }
+    );
+    assert_eq!(
+        padding::padding_mask(&range, &[-2001, -2000, -1999, 0]),
+        vec![false, true, true, false]
+    );
+}
+
+#[test]
+fn padding_is_excluded_from_the_automatic_window() {
+    let data_elements = build_dataset(padding_range());
+
+    let frames = pixel::frames(&data_elements).unwrap();
+    let values = pixel::frame_to_values(&frames.frame(0).unwrap(), frames.description()).unwrap();
+
+    let transform = lut::get_voi_transform_or_auto(
+        &data_elements,
+        1,
+        &values,
+        &ModalityTransform::Rescale {
+            slope: 1.0,
+            intercept: 0.0,
+        },
+        Default::default(),
+    )
+    .unwrap();
+
+    match transform {
+        VoiTransform::Window(window) => {
+            assert_eq!(window.center, 50.0);
+            assert_eq!(window.width, 100.0);
+        }
+        _ => panic!("expected an automatic window"),
+    }
+}
+
+#[test]
+fn padding_is_rendered_black_or_transparent() {
+    let data_elements = build_dataset(padding_value());
+
+    // 默认和其他像素一样经过显示流程，不增加alpha通道
+    let image = render::render(&data_elements, &RenderOptions::default()).unwrap();
+    assert_eq!(image.color(), image::ColorType::L8);
+
+    let image = render::render(
+        &data_elements,
+        &RenderOptions {
+            padding: PaddingDisplay::Black,
+            invert: true,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .to_luma8();
+
+    assert_eq!(image.get_pixel(0, 0).0, [0]);
+    // -1999不是填充像素，反转之后是白色
+    assert_eq!(image.get_pixel(2, 0).0, [255]);
+
+    let image = render::render(
+        &data_elements,
+        &RenderOptions {
+            padding: PaddingDisplay::Transparent,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(image.color(), image::ColorType::La8);
+
+    let image = image.to_luma_alpha8();
+    assert_eq!(image.get_pixel(1, 0).0[1], 0);
+    assert_eq!(image.get_pixel(2, 0).0[1], 255);
+    assert_eq!(image.get_pixel(2, 1).0, [255, 255]);
+}
+
+#[test]
+fn padding_does_not_shift_exported_values() {
+    let file_path = std::env::temp_dir().join(format!("padding_export_{}.png", std::process::id()));
+    let sidecar_path = file_path.with_extension("json");
+
+    export::export_frame(
+        &build_dataset(padding_value()),
+        0,
+        &file_path,
+        ExportFormat::Png16,
+    )
+    .unwrap();
+
+    let sidecar = std::fs::read_to_string(&sidecar_path).unwrap();
+    let image = image::open(&file_path).unwrap().to_luma16();
+
+    std::fs::remove_file(&file_path).unwrap();
+    std::fs::remove_file(&sidecar_path).unwrap();
+
+    assert!(sidecar.contains("\"value_offset\": -1999"));
+    // 填充像素保存为0
+    assert_eq!(image.get_pixel(0, 0).0, [0]);
+    assert_eq!(image.get_pixel(2, 1).0, [2099]);
+}
+
+#[test]
+fn shutter_and_overlay_are_drawn_over_padding() {
+    let mut data_elements = build_dataset(padding_value());
+    // 只保留第3列，shutter之外显示为白色
+    data_elements.extend(vec![
+        element(
+            "0018,1600",
+            "CS",
+            DicomValue::String("RECTANGULAR".to_string()),
+        ),
+        element("0018,1602", "IS", DicomValue::I64(vec![3])),
+        element("0018,1604", "IS", DicomValue::I64(vec![3])),
+        element("0018,1606", "IS", DicomValue::I64(vec![1])),
+        element("0018,1608", "IS", DicomValue::I64(vec![2])),
+        element("0018,1622", "US", DicomValue::U16(vec![0xFFFF])),
+    ]);
+    // 第1行第2列的1x1 overlay
+    data_elements.extend(vec![
+        element("6000,0010", "US", DicomValue::U16(vec![1])),
+        element("6000,0011", "US", DicomValue::U16(vec![1])),
+        element("6000,0040", "CS", DicomValue::String("G".to_string())),
+        element("6000,0050", "SS", DicomValue::I16(vec![1, 2])),
+        element("6000,0100", "US", DicomValue::U16(vec![1])),
+        element("6000,0102", "US", DicomValue::U16(vec![0])),
+        element("6000,3000", "OW", DicomValue::Bytes(vec![1, 0])),
+    ]);
+
+    let image = render::render(
+        &data_elements,
+        &RenderOptions {
+            padding: PaddingDisplay::Black,
+            overlay_color: Some([255, 0, 0]),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .to_rgb8();
+
+    assert_eq!(image.get_pixel(0, 0).0, [255, 255, 255]);
+    assert_eq!(image.get_pixel(1, 0).0, [255, 0, 0]);
+}
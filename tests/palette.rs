@@ -0,0 +1,104 @@
+use dicom_parser::{
+    color,
+    model::{DataElement, DicomValue},
+    util,
+};
+
+fn words(values: &[u16]) -> DicomValue {
+    DicomValue::Bytes(values.iter().flat_map(|v| v.to_le_bytes()).collect())
+}
+
+fn descriptor(tag: &str, values: [u16; 3]) -> DataElement {
+    util::new_data_element(tag, "US", DicomValue::U16(values.to_vec())).unwrap()
+}
+
+#[test]
+fn segmented_lut_is_expanded() {
+    // 离散段[0, 10]，线性段到50（4个值），间接段重复第一个离散段
+    let segmented = [0, 2, 0, 10, 1, 4, 50, 2, 1, 0, 0];
+
+    assert_eq!(
+        color::expand_segmented_lut(&segmented).unwrap(),
+        vec![0, 10, 20, 30, 40, 50, 0, 10]
+    );
+}
+
+#[test]
+fn segmented_lut_rejects_unknown_opcodes() {
+    assert!(color::expand_segmented_lut(&[0, 1, 5, 7, 1]).is_err());
+    assert!(color::expand_segmented_lut(&[1, 2, 100]).is_err());
+}
+
+#[test]
+fn standard_palette_maps_values_to_rgb() {
+    // 16位表项，从像素值10开始映射3个值
+    let data_elements = vec![
+        descriptor("0028,1101", [3, 10, 16]),
+        descriptor("0028,1102", [3, 10, 16]),
+        descriptor("0028,1103", [3, 10, 16]),
+        util::new_data_element("0028,1201", "OW", words(&[0xff00, 0x0000, 0x0000])).unwrap(),
+        util::new_data_element("0028,1202", "OW", words(&[0x0000, 0xff00, 0x0000])).unwrap(),
+        util::new_data_element("0028,1203", "OW", words(&[0x0000, 0x0000, 0xffff])).unwrap(),
+    ];
+
+    let luts = color::get_palette_color_luts(&data_elements, 0).unwrap();
+
+    // 超出范围的值使用第一个或最后一个表项
+    assert_eq!(
+        color::palette_to_rgb(&[0, 10, 11, 12, 200], &luts),
+        vec![255, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 255]
+    );
+}
+
+#[test]
+fn eight_bit_palette_stored_as_bytes() {
+    let data_elements = vec![
+        descriptor("0028,1101", [2, 0, 8]),
+        descriptor("0028,1102", [2, 0, 8]),
+        descriptor("0028,1103", [2, 0, 8]),
+        util::new_data_element("0028,1201", "OW", DicomValue::Bytes(vec![1, 2])).unwrap(),
+        util::new_data_element("0028,1202", "OW", DicomValue::Bytes(vec![3, 4])).unwrap(),
+        util::new_data_element("0028,1203", "OW", DicomValue::Bytes(vec![5, 6])).unwrap(),
+    ];
+
+    let luts = color::get_palette_color_luts(&data_elements, 0).unwrap();
+
+    assert_eq!(
+        color::palette_to_rgb(&[0, 1], &luts),
+        vec![1, 3, 5, 2, 4, 6]
+    );
+}
+
+#[test]
+fn segmented_palette_is_used_when_lut_data_is_missing() {
+    let data_elements = vec![
+        descriptor("0028,1101", [3, 0, 8]),
+        descriptor("0028,1102", [3, 0, 8]),
+        descriptor("0028,1103", [3, 0, 8]),
+        util::new_data_element("0028,1221", "OW", words(&[0, 1, 0, 1, 2, 200])).unwrap(),
+        util::new_data_element("0028,1222", "OW", words(&[0, 3, 7, 7, 7])).unwrap(),
+        util::new_data_element("0028,1223", "OW", words(&[0, 1, 255, 1, 2, 255])).unwrap(),
+    ];
+
+    let luts = color::get_palette_color_luts(&data_elements, 0).unwrap();
+
+    assert_eq!(
+        color::palette_to_rgb(&[0, 1, 2], &luts),
+        vec![0, 7, 255, 100, 7, 255, 200, 7, 255]
+    );
+}
+
+#[test]
+fn signed_first_mapped_value() {
+    // 描述符中的0xFFFE在像素有符号时表示-2
+    let data_elements = vec![descriptor("0028,1101", [4, 0xfffe, 8])];
+
+    assert_eq!(
+        color::get_lut_descriptor(&data_elements, "0028,1101", 1).unwrap(),
+        (4, -2, 8)
+    );
+    assert_eq!(
+        color::get_lut_descriptor(&data_elements, "0028,1101", 0).unwrap(),
+        (4, 0xfffe, 8)
+    );
+}
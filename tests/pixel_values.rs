@@ -0,0 +1,129 @@
+use dicom_parser::{
+    model::{DataElement, DicomValue, PixelDescription},
+    pixel, util,
+};
+
+fn description(
+    bits_allocated: u16,
+    bits_stored: u16,
+    high_bit: u16,
+    pixel_representation: u16,
+    columns: u16,
+) -> PixelDescription {
+    PixelDescription {
+        rows: 1,
+        columns,
+        samples_per_pixel: 1,
+        bits_allocated,
+        bits_stored,
+        high_bit,
+        pixel_representation,
+        planar_configuration: 0,
+        number_of_frames: 1,
+        photometric_interpretation: "MONOCHROME2".to_string(),
+    }
+}
+
+fn words(values: &[u16]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[test]
+fn signed_values_are_sign_extended_at_bits_stored() {
+    // CT常见的12位有符号数据，高4位是无关的数据（比如覆盖层）
+    let frame = words(&[0x0fff, 0x0800, 0x07ff, 0xf001]);
+
+    assert_eq!(
+        pixel::frame_to_values(&frame, &description(16, 12, 11, 1, 4)).unwrap(),
+        vec![-1, -2048, 2047, 1]
+    );
+    assert_eq!(
+        pixel::frame_to_values(&frame, &description(16, 12, 11, 0, 4)).unwrap(),
+        vec![4095, 2048, 2047, 1]
+    );
+}
+
+#[test]
+fn high_bit_positions_the_stored_bits() {
+    // 有效位左对齐（High Bit为15），低4位被丢弃
+    let frame = words(&[0xfff0, 0x8000, 0x001f]);
+
+    assert_eq!(
+        pixel::frame_to_values(&frame, &description(16, 12, 15, 0, 3)).unwrap(),
+        vec![4095, 2048, 1]
+    );
+    assert_eq!(
+        pixel::frame_to_values(&frame, &description(16, 12, 15, 1, 3)).unwrap(),
+        vec![-1, -2048, 1]
+    );
+}
+
+#[test]
+fn eight_and_thirty_two_bit_samples() {
+    assert_eq!(
+        pixel::frame_to_values(&[0x00, 0x7f, 0x80, 0xff], &description(8, 8, 7, 1, 4)).unwrap(),
+        vec![0, 127, -128, -1]
+    );
+
+    let frame = [0xff_u32, 0xffff_fffe, 0x8000_0000]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<u8>>();
+
+    assert_eq!(
+        pixel::frame_to_values(&frame, &description(32, 32, 31, 1, 3)).unwrap(),
+        vec![255, -2, i32::MIN as i64]
+    );
+    assert_eq!(
+        pixel::frame_to_values(&frame, &description(32, 32, 31, 0, 3)).unwrap(),
+        vec![255, 0xffff_fffe, 0x8000_0000]
+    );
+}
+
+#[test]
+fn invalid_high_bit_is_rejected() {
+    assert!(pixel::frame_to_values(&words(&[0]), &description(16, 12, 16, 0, 1)).is_err());
+    assert!(pixel::frame_to_values(&words(&[0]), &description(16, 12, 10, 0, 1)).is_err());
+}
+
+fn one_bit_dataset(pixel_datas: Vec<u8>) -> Vec<DataElement> {
+    vec![
+        util::new_data_element("0028,0004", "CS", DicomValue::String("MONOCHROME2".into()))
+            .unwrap(),
+        util::new_data_element("0028,0008", "IS", DicomValue::I64(vec![2])).unwrap(),
+        util::new_data_element("0028,0010", "US", DicomValue::U16(vec![3])).unwrap(),
+        util::new_data_element("0028,0011", "US", DicomValue::U16(vec![3])).unwrap(),
+        util::new_data_element("0028,0100", "US", DicomValue::U16(vec![1])).unwrap(),
+        util::new_data_element("0028,0101", "US", DicomValue::U16(vec![1])).unwrap(),
+        util::new_data_element("0028,0102", "US", DicomValue::U16(vec![0])).unwrap(),
+        util::new_data_element("7FE0,0010", "OB", DicomValue::Bytes(pixel_datas)).unwrap(),
+    ]
+}
+
+#[test]
+fn one_bit_frames_are_not_byte_aligned() {
+    // 每帧3x3=9位，第二帧从第一个字节之后的第1位开始
+    // 第一帧：对角线100 010 001；第二帧：全部为1
+    let bits = [1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1];
+
+    let mut pixel_datas = vec![0_u8; 4];
+    for (index, bit) in bits.iter().enumerate() {
+        pixel_datas[index / 8] |= bit << (index % 8);
+    }
+
+    let data_elements = one_bit_dataset(pixel_datas);
+    let frames = pixel::frames(&data_elements).unwrap();
+    let description = frames.description().clone();
+
+    let values = frames
+        .map(|frame| pixel::frame_to_values(&frame.unwrap(), &description).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(values[0], vec![1, 0, 0, 0, 1, 0, 0, 0, 1]);
+    assert_eq!(values[1], vec![1; 9]);
+}
+
+#[test]
+fn truncated_one_bit_data_is_rejected() {
+    assert!(pixel::frames(&one_bit_dataset(vec![0xff, 0xff])).is_err());
+}
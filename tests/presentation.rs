@@ -0,0 +1,246 @@
+use dicom_parser::{
+    model::{DataElement, DicomValue, RenderOptions, TransferSyntax},
+    presentation,
+};
+
+mod common;
+
+use common::{element, sequence, string};
+
+const ROWS: u32 = 4;
+const COLUMNS: u32 = 6;
+const SOP_INSTANCE_UID: &str = "1.2.3.4";
+
+// 4x6的8位灰度图像，每个像素的值都不同
+fn build_image() -> Vec<DataElement> {
+    vec![
+        string(
+            "0002,0010",
+            "UI",
+            TransferSyntax::ExplicitVrLittleEndian.uid(),
+        ),
+        string("0008,0018", "UI", SOP_INSTANCE_UID),
+        string("0028,0004", "CS", "MONOCHROME2"),
+        element("0028,0010", "US", DicomValue::U16(vec![ROWS as u16])),
+        element("0028,0011", "US", DicomValue::U16(vec![COLUMNS as u16])),
+        element("0028,0100", "US", DicomValue::U16(vec![8])),
+        element("0028,0101", "US", DicomValue::U16(vec![8])),
+        element("0028,1050", "DS", DicomValue::Double(vec![128.0])),
+        element("0028,1051", "DS", DicomValue::Double(vec![256.0])),
+        element(
+            "7FE0,0010",
+            "OB",
+            DicomValue::Bytes((0..ROWS * COLUMNS).map(|v| (v * 10) as u8).collect()),
+        ),
+    ]
+}
+
+fn referenced_image(sop_instance_uid: &str) -> DataElement {
+    sequence(
+        "0008,1140",
+        vec![vec![string("0008,1155", "UI", sop_instance_uid)]],
+    )
+}
+
+fn build_presentation_state(extra: Vec<DataElement>) -> Vec<DataElement> {
+    let mut result = vec![sequence(
+        "0008,1115",
+        vec![vec![referenced_image(SOP_INSTANCE_UID)]],
+    )];
+
+    result.extend(extra);
+    result
+}
+
+fn render(presentation_state: &[DataElement]) -> image::RgbImage {
+    presentation::render(
+        &build_image(),
+        presentation_state,
+        &RenderOptions::default(),
+    )
+    .unwrap()
+    .to_rgb8()
+}
+
+#[test]
+fn softcopy_voi_and_presentation_lut_shape_are_applied() {
+    let plain = render(&build_presentation_state(Vec::new()));
+
+    let presentation_state = build_presentation_state(vec![
+        sequence(
+            "0028,3110",
+            vec![vec![
+                element("0028,1050", "DS", DicomValue::Double(vec![50.0])),
+                element("0028,1051", "DS", DicomValue::Double(vec![20.0])),
+            ]],
+        ),
+        string("2050,0020", "CS", "INVERSE"),
+    ]);
+
+    let state =
+        presentation::get_presentation_state(&presentation_state, &build_image(), 0).unwrap();
+    assert_eq!(state.invert, Some(true));
+    assert!(state.voi.is_some());
+
+    let image = render(&presentation_state);
+
+    // 窄窗之下的像素反转之后是白色，之上的是黑色
+    assert_eq!(image.get_pixel(0, 0).0, [255; 3]);
+    assert_eq!(image.get_pixel(5, 3).0, [0; 3]);
+    assert_ne!(plain.get_pixel(0, 0).0, [255; 3]);
+}
+
+#[test]
+fn rotation_and_flip_are_applied() {
+    let plain = render(&build_presentation_state(Vec::new()));
+
+    let image = render(&build_presentation_state(vec![
+        element("0070,0042", "US", DicomValue::U16(vec![90])),
+        string("0070,0041", "CS", "Y"),
+    ]));
+
+    assert_eq!((image.width(), image.height()), (ROWS, COLUMNS));
+
+    // 先水平翻转再顺时针旋转90度，相当于沿副对角线转置
+    for y in 0..ROWS {
+        for x in 0..COLUMNS {
+            assert_eq!(
+                image.get_pixel(ROWS - 1 - y, COLUMNS - 1 - x),
+                plain.get_pixel(x, y)
+            );
+        }
+    }
+}
+
+#[test]
+fn displayed_area_is_cropped_and_padded() {
+    let plain = render(&build_presentation_state(Vec::new()));
+
+    let image = render(&build_presentation_state(vec![sequence(
+        "0070,005A",
+        vec![vec![
+            element("0070,0052", "SL", DicomValue::I32(vec![2, 2])),
+            element("0070,0053", "SL", DicomValue::I32(vec![7, 3])),
+            string("0070,0100", "CS", "SCALE TO FIT"),
+        ]],
+    )]));
+
+    assert_eq!((image.width(), image.height()), (6, 2));
+    assert_eq!(image.get_pixel(0, 0), plain.get_pixel(1, 1));
+    // 超出图像右边的一列填充黑色
+    assert_eq!(image.get_pixel(5, 1).0, [0; 3]);
+}
+
+#[test]
+fn shutters_hide_the_outside() {
+    let image = render(&build_presentation_state(vec![
+        string("0018,1600", "CS", "RECTANGULAR"),
+        element("0018,1602", "IS", DicomValue::I64(vec![2])),
+        element("0018,1604", "IS", DicomValue::I64(vec![5])),
+        element("0018,1606", "IS", DicomValue::I64(vec![2])),
+        element("0018,1608", "IS", DicomValue::I64(vec![3])),
+    ]));
+
+    assert_eq!(image.get_pixel(5, 3).0, [0; 3]);
+    assert_ne!(image.get_pixel(4, 2).0, [0; 3]);
+}
+
+#[test]
+fn annotations_are_drawn_in_the_layer_color() {
+    let presentation_state = build_presentation_state(vec![
+        sequence(
+            "0070,0001",
+            vec![vec![
+                string("0070,0002", "CS", "MEASURE"),
+                sequence(
+                    "0070,0009",
+                    vec![vec![
+                        string("0070,0005", "CS", "PIXEL"),
+                        element("0070,0020", "US", DicomValue::U16(vec![2])),
+                        element("0070,0021", "US", DicomValue::U16(vec![2])),
+                        element(
+                            "0070,0022",
+                            "FL",
+                            DicomValue::Float(vec![0.5, 1.5, 5.5, 1.5]),
+                        ),
+                        string("0070,0023", "CS", "POLYLINE"),
+                        string("0070,0024", "CS", "N"),
+                    ]],
+                ),
+            ]],
+        ),
+        sequence(
+            "0070,0060",
+            vec![vec![
+                string("0070,0002", "CS", "MEASURE"),
+                element("0070,0062", "IS", DicomValue::I64(vec![1])),
+                element("0070,0066", "US", DicomValue::U16(vec![0xFFFF])),
+            ]],
+        ),
+    ]);
+
+    let image = render(&presentation_state);
+
+    for x in 0..COLUMNS {
+        assert_eq!(image.get_pixel(x, 1).0, [255; 3]);
+    }
+    assert_ne!(image.get_pixel(0, 2).0, [255; 3]);
+
+    // 旋转180度之后线跟着图像移到倒数第二行
+    let mut rotated = presentation_state.clone();
+    rotated.push(element("0070,0042", "US", DicomValue::U16(vec![180])));
+
+    let image = render(&rotated);
+    assert_eq!(image.get_pixel(2, ROWS - 2).0, [255; 3]);
+}
+
+#[test]
+fn text_is_drawn_inside_its_bounding_box() {
+    let image = presentation::render(
+        &build_image()
+            .into_iter()
+            .map(|v| {
+                if v.tag == "0028,0010" || v.tag == "0028,0011" {
+                    element(&v.tag, "US", DicomValue::U16(vec![16]))
+                } else if v.tag == "7FE0,0010" {
+                    element("7FE0,0010", "OB", DicomValue::Bytes(vec![0; 256]))
+                } else {
+                    v
+                }
+            })
+            .collect::<Vec<_>>(),
+        &build_presentation_state(vec![sequence(
+            "0070,0001",
+            vec![vec![sequence(
+                "0070,0008",
+                vec![vec![
+                    string("0070,0003", "CS", "PIXEL"),
+                    string("0070,0006", "ST", "L"),
+                    element("0070,0010", "FL", DicomValue::Float(vec![2.0, 2.0])),
+                    element("0070,0011", "FL", DicomValue::Float(vec![14.0, 14.0])),
+                ]],
+            )]],
+        )]),
+        &RenderOptions::default(),
+    )
+    .unwrap()
+    .to_rgb8();
+
+    // L的竖线在box的第一列，横线在第7行
+    assert_eq!(image.get_pixel(2, 2).0, [255; 3]);
+    assert_eq!(image.get_pixel(2, 8).0, [255; 3]);
+    assert_eq!(image.get_pixel(6, 8).0, [255; 3]);
+    assert_eq!(image.get_pixel(6, 2).0, [0; 3]);
+}
+
+#[test]
+fn unreferenced_images_are_rejected() {
+    let presentation_state = vec![sequence("0008,1115", vec![vec![referenced_image("9.9.9")]])];
+
+    assert!(presentation::render(
+        &build_image(),
+        &presentation_state,
+        &RenderOptions::default()
+    )
+    .is_err());
+}
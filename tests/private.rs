@@ -0,0 +1,206 @@
+use dicom_parser::{
+    accessor,
+    model::{DicomValue, ParseOptions, TransferSyntax},
+    service, util,
+    writer::{self, WriteOptions},
+};
+
+mod common;
+
+use common::{element, string};
+
+#[test]
+fn private_tags_do_not_match_standard_names() {
+    assert_eq!(
+        util::get_tag_human_name("6000,0010").unwrap(),
+        "Overlay Rows"
+    );
+
+    // 6001是私有组，不能被60xx,0010匹配
+    assert_eq!(
+        util::get_tag_human_name("6001,0010").unwrap(),
+        "Private Creator"
+    );
+    assert_eq!(util::get_tag_human_name("6001,1001").unwrap(), "unknown");
+    assert_eq!(util::get_tag_human_name("0019,100A").unwrap(), "unknown");
+}
+
+#[test]
+fn private_tags_are_resolved_by_the_private_creator() {
+    // 同一个group中两个厂商的block，Private Creator的值末尾带有填充的空格
+    let data_elements = vec![
+        string("0019,0010", "LO", "AGFA"),
+        string("0019,0011", "LO", "SIEMENS MR HEADER "),
+        element("0019,1060", "US", DicomValue::U16(vec![3])),
+        element("0019,110A", "US", DicomValue::U16(vec![36])),
+        element("0019,1160", "UN", DicomValue::Bytes(vec![0; 2])),
+        element("0019,120A", "UN", DicomValue::Bytes(vec![0; 2])),
+    ];
+
+    assert_eq!(
+        accessor::get_private_creator(&data_elements, "0019,110A").as_deref(),
+        Some("SIEMENS MR HEADER")
+    );
+
+    let private_tag = accessor::get_private_tag(&data_elements, "0019,110A").unwrap();
+    assert_eq!(
+        (private_tag.vr.as_str(), private_tag.name.as_str()),
+        ("US", "Number of Images in Mosaic")
+    );
+    assert_eq!(
+        accessor::get_private_tag(&data_elements, "0019,1060")
+            .unwrap()
+            .name,
+        "Total Number of Series"
+    );
+
+    // 字典中没有这一项，以及没有Private Creator
+    assert!(accessor::get_private_tag(&data_elements, "0019,1160").is_none());
+    assert!(accessor::get_private_tag(&data_elements, "0019,120A").is_none());
+    assert!(accessor::get_private_creator(&data_elements, "0019,0010").is_none());
+
+    assert_eq!(
+        util::get_private_tag(0x2005, "Philips MR Imaging DD 001", 0x0E)
+            .unwrap()
+            .name,
+        "Scale Slope"
+    );
+}
+
+#[test]
+fn implicit_vr_private_elements_use_the_dictionary_vr() {
+    let data_elements = vec![
+        string(
+            "0002,0010",
+            "UI",
+            TransferSyntax::ImplicitVrLittleEndian.uid(),
+        ),
+        string("0019,0010", "LO", "SIEMENS MR HEADER"),
+        element("0019,100A", "US", DicomValue::U16(vec![36])),
+        element("0019,100C", "IS", DicomValue::I64(vec![1000])),
+        element("0019,10FF", "OB", DicomValue::Bytes(vec![1, 2, 3, 4])),
+        element(
+            "2001,105F",
+            "SQ",
+            DicomValue::Sequence(vec![vec![
+                string("2001,0010", "LO", "Philips Imaging DD 001"),
+                element("2001,1035", "SS", DicomValue::I16(vec![-2])),
+            ]]),
+        ),
+        string("2001,0010", "LO", "Philips Imaging DD 001"),
+    ];
+
+    let options = WriteOptions {
+        transfer_syntax: TransferSyntax::ImplicitVrLittleEndian,
+        ..Default::default()
+    };
+    let file = writer::write_to_bytes(&data_elements, &options).unwrap();
+    let parsed = service::parse_file_content(&file, &ParseOptions::default()).unwrap();
+
+    let count = accessor::find_data_element(&parsed, "0019,100A").unwrap();
+    assert_eq!(count.vr, "US");
+    assert_eq!(count.tag_for_human, "Number of Images in Mosaic");
+    assert_eq!(count.data, DicomValue::U16(vec![36]));
+    assert_eq!(accessor::get_i64(&parsed, "0019,100C").unwrap(), 1000);
+
+    // 字典中没有的私有元素仍然保留原始字节
+    let unknown = accessor::find_data_element(&parsed, "0019,10FF").unwrap();
+    assert_eq!(unknown.vr, "implicit");
+    assert_eq!(unknown.data, DicomValue::Bytes(vec![1, 2, 3, 4]));
+
+    // 定义长度的私有SQ以及item中的私有元素
+    let items = accessor::get_items(&parsed, "2001,105F").unwrap();
+    let stack_slice = accessor::find_data_element(&items[0], "2001,1035").unwrap();
+    assert_eq!(stack_slice.tag_for_human, "Stack Slice Number");
+    assert_eq!(stack_slice.data, DicomValue::I16(vec![-2]));
+
+    // 重新写出的文件和原来的一致
+    assert_eq!(writer::write_to_bytes(&parsed, &options).unwrap(), file);
+}
+
+#[test]
+fn private_dictionaries_can_be_added() {
+    let file_path =
+        std::env::temp_dir().join(format!("dicom_parser_private_{}.txt", std::process::id()));
+
+    std::fs::write(
+        &file_path,
+        "# 测试用的私有字典\n\n0011,xx01\tACME 1.0\tDS\tAcme Dose\n0011,xx02\tACME 1.0\tLO\tAcme Room\n",
+    )
+    .unwrap();
+
+    let count = util::add_private_dictionary(file_path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&file_path).unwrap();
+
+    assert_eq!(count, 2);
+
+    let data_elements = vec![
+        string("0011,0020", "LO", "ACME 1.0"),
+        string("0011,2001", "DS", "1.5"),
+    ];
+    let private_tag = accessor::get_private_tag(&data_elements, "0011,2001").unwrap();
+    assert_eq!(
+        (private_tag.group, private_tag.element_offset),
+        (0x0011, 0x01)
+    );
+    assert_eq!(private_tag.name, "Acme Dose");
+
+    // 偶数group、缺少字段和element不是xxee形式的行
+    assert!(util::parse_private_dictionary("0010,xx01\tACME\tLO\tName").is_err());
+    assert!(util::parse_private_dictionary("0011,xx01\tACME\tLO").is_err());
+    assert!(util::parse_private_dictionary("0011,1001\tACME\tLO\tName").is_err());
+}
+
+#[test]
+fn wrong_dictionary_vr_keeps_the_raw_bytes() {
+    let file_path =
+        std::env::temp_dir().join(format!("dicom_parser_wrong_vr_{}.txt", std::process::id()));
+
+    // 故意写错的VR：实际数据是4字节，字典中是FD和SQ
+    std::fs::write(
+        &file_path,
+        "0011,xx10\tWRONG VR 1.0\tFD\tWrong Double\n0011,xx11\tWRONG VR 1.0\tSQ\tWrong Sequence\n0011,xx12\tWRONG VR 1.0\tUS\tRight Count\n",
+    )
+    .unwrap();
+
+    util::add_private_dictionary(file_path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&file_path).unwrap();
+
+    let data_elements = vec![
+        string(
+            "0002,0010",
+            "UI",
+            TransferSyntax::ImplicitVrLittleEndian.uid(),
+        ),
+        string("0011,0010", "LO", "WRONG VR 1.0"),
+        element("0011,1010", "OB", DicomValue::Bytes(vec![1, 2, 3, 4])),
+        element("0011,1011", "OB", DicomValue::Bytes(vec![1, 2, 3, 4])),
+        element("0011,1012", "US", DicomValue::U16(vec![36])),
+    ];
+
+    let options = WriteOptions {
+        transfer_syntax: TransferSyntax::ImplicitVrLittleEndian,
+        ..Default::default()
+    };
+    let file = writer::write_to_bytes(&data_elements, &options).unwrap();
+    let parsed = service::parse_file_content(&file, &ParseOptions::default()).unwrap();
+
+    for tag in ["0011,1010", "0011,1011"] {
+        let data_element = accessor::find_data_element(&parsed, tag).unwrap();
+
+        assert_eq!(data_element.vr, "implicit");
+        assert_eq!(data_element.data, DicomValue::Bytes(vec![1, 2, 3, 4]));
+        assert_eq!(data_element.value_errors.len(), 1);
+        assert_eq!(data_element.value_errors[0].raw, "01020304");
+    }
+
+    let double = accessor::find_data_element(&parsed, "0011,1010").unwrap();
+    assert_eq!(double.tag_for_human, "Wrong Double");
+    assert!(double.value_errors[0]
+        .message
+        .starts_with("can not be parsed as FD from the private dictionary"));
+
+    // 其他私有元素不受影响
+    assert_eq!(accessor::get_u16(&parsed, "0011,1012").unwrap(), 36);
+    assert_eq!(writer::write_to_bytes(&parsed, &options).unwrap(), file);
+}
@@ -0,0 +1,138 @@
+use dicom_parser::{
+    model::{
+        DataElement, DicomValue, OutputBitDepth, RenderOptions, RenderSize, VoiLutFunction, Window,
+    },
+    render,
+};
+
+mod common;
+
+use common::{element, words, Grayscale};
+
+// 2帧、每帧2x4的16位灰度图像，第二帧的像素值比第一帧大1000
+fn build_dataset() -> Vec<DataElement> {
+    let pixels = (0..2_u16)
+        .flat_map(|frame| (0..8_u16).map(move |v| frame * 1000 + v * 100))
+        .collect::<Vec<u16>>();
+
+    Grayscale {
+        rows: 2,
+        columns: 4,
+        frames: 2,
+        ..Default::default()
+    }
+    .build(
+        words(&pixels),
+        vec![
+            element("0028,1050", "DS", DicomValue::Double(vec![350.0, 5000.0])),
+            element("0028,1051", "DS", DicomValue::Double(vec![700.0, 10000.0])),
+        ],
+    )
+}
+
+fn luma8(options: &RenderOptions) -> Vec<u8> {
+    render::render(&build_dataset(), options)
+        .unwrap()
+        .to_luma8()
+        .into_raw()
+}
+
+#[test]
+fn frame_and_preset_are_selectable() {
+    let first = luma8(&RenderOptions::default());
+
+    assert_eq!(first[0], 0);
+    assert_eq!(first[7], 255);
+
+    // 第二帧在第一个预设下全部超出窗口
+    let second = luma8(&RenderOptions {
+        frame_index: 1,
+        ..Default::default()
+    });
+    assert!(second.iter().all(|v| *v == 255));
+
+    // 第二个预设是很宽的窗口，像素都在中间偏下
+    let wide = luma8(&RenderOptions {
+        voi_index: 1,
+        ..Default::default()
+    });
+    assert!(wide.iter().all(|v| *v < 128));
+
+    assert!(render::render(
+        &build_dataset(),
+        &RenderOptions {
+            voi_index: 2,
+            ..Default::default()
+        }
+    )
+    .is_err());
+}
+
+#[test]
+fn window_override_and_inversion() {
+    let pixels = luma8(&RenderOptions {
+        window: Some(Window {
+            center: 100.0,
+            width: 200.0,
+            function: VoiLutFunction::LinearExact,
+            explanation: None,
+        }),
+        invert: true,
+        ..Default::default()
+    });
+
+    assert_eq!(pixels[0], 255);
+    assert_eq!(pixels[1], 128);
+    assert_eq!(pixels[2], 0);
+}
+
+#[test]
+fn sixteen_bit_output_and_scaling() {
+    let image = render::render(
+        &build_dataset(),
+        &RenderOptions {
+            bit_depth: OutputBitDepth::Sixteen,
+            size: RenderSize::Scale(2.0),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!((image.width(), image.height()), (8, 4));
+    assert_eq!(image.color(), image::ColorType::L16);
+
+    let thumbnail = render::render(
+        &build_dataset(),
+        &RenderOptions {
+            size: RenderSize::Fit {
+                width: 2,
+                height: 2,
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!((thumbnail.width(), thumbnail.height()), (2, 1));
+}
+
+#[test]
+fn images_are_encoded_in_memory() {
+    let bytes = render::render_to_bytes(
+        &build_dataset(),
+        &RenderOptions {
+            format: image::ImageFormat::Jpeg,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(&bytes[..2], &[0xFF, 0xD8]);
+
+    let image = image::load_from_memory(
+        &render::render_to_bytes(&build_dataset(), &RenderOptions::default()).unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!((image.width(), image.height()), (4, 2));
+}
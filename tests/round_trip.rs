@@ -0,0 +1,462 @@
+use dicom_parser::{
+    model::{DicomValue, ParseOptions, TransferSyntax},
+    service, util,
+    writer::{self, WriteOptions},
+};
+
+// 手工拼装合成的dicom文件，不依赖writer，保证读写两端都能被独立地验证
+struct Builder {
+    transfer_syntax: TransferSyntax,
+    buffer: Vec<u8>,
+}
+
+impl Builder {
+    fn new(transfer_syntax: TransferSyntax) -> Builder {
+        Builder {
+            transfer_syntax,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn little_endian(&self) -> bool {
+        self.transfer_syntax.is_little_endian()
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        if self.little_endian() {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        if self.little_endian() {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        }
+    }
+
+    fn tag(&mut self, group: u16, element: u16) {
+        let bytes = [self.u16_bytes(group), self.u16_bytes(element)].concat();
+        self.buffer.extend_from_slice(&bytes);
+    }
+
+    fn header(&mut self, group: u16, element: u16, vr: &str, length: u32) {
+        self.tag(group, element);
+
+        if !self.transfer_syntax.is_explicit_vr() {
+            let bytes = self.u32_bytes(length);
+            self.buffer.extend_from_slice(&bytes);
+        } else if util::is_long_length_vr(vr) {
+            self.buffer.extend_from_slice(vr.as_bytes());
+            self.buffer.extend_from_slice(&[0, 0]);
+            let bytes = self.u32_bytes(length);
+            self.buffer.extend_from_slice(&bytes);
+        } else {
+            self.buffer.extend_from_slice(vr.as_bytes());
+            let bytes = self.u16_bytes(length as u16);
+            self.buffer.extend_from_slice(&bytes);
+        }
+    }
+
+    fn element(&mut self, group: u16, element: u16, vr: &str, value: &[u8]) -> &mut Builder {
+        self.header(group, element, vr, value.len() as u32);
+        self.buffer.extend_from_slice(value);
+        self
+    }
+
+    fn text(&mut self, group: u16, element: u16, vr: &str, value: &str) -> &mut Builder {
+        self.element(group, element, vr, value.as_bytes())
+    }
+
+    // 把每个值的小端字节按照当前的字节序拼接起来
+    fn numbers(&self, le_bytes: Vec<Vec<u8>>) -> Vec<u8> {
+        le_bytes
+            .into_iter()
+            .flat_map(|mut v| {
+                if !self.little_endian() {
+                    v.reverse();
+                }
+                v
+            })
+            .collect()
+    }
+
+    // items中每一项为(item的内容, item是否为未定义长度)
+    fn sequence(
+        &mut self,
+        group: u16,
+        element: u16,
+        items: Vec<(Vec<u8>, bool)>,
+        undefined_length: bool,
+    ) -> &mut Builder {
+        let mut content = Builder::new(self.transfer_syntax);
+
+        for (item, undefined_item_length) in items {
+            content.tag(0xfffe, 0xe000);
+
+            if undefined_item_length {
+                let bytes = content.u32_bytes(0xffffffff);
+                content.buffer.extend_from_slice(&bytes);
+                content.buffer.extend_from_slice(&item);
+                content.tag(0xfffe, 0xe00d);
+                let bytes = content.u32_bytes(0);
+                content.buffer.extend_from_slice(&bytes);
+            } else {
+                let bytes = content.u32_bytes(item.len() as u32);
+                content.buffer.extend_from_slice(&bytes);
+                content.buffer.extend_from_slice(&item);
+            }
+        }
+
+        if undefined_length {
+            self.header(group, element, "SQ", 0xffffffff);
+            self.buffer.extend_from_slice(&content.buffer);
+            self.tag(0xfffe, 0xe0dd);
+            let bytes = self.u32_bytes(0);
+            self.buffer.extend_from_slice(&bytes);
+        } else {
+            self.header(group, element, "SQ", content.buffer.len() as u32);
+            self.buffer.extend_from_slice(&content.buffer);
+        }
+
+        self
+    }
+}
+
+fn build_file(transfer_syntax: TransferSyntax) -> Vec<u8> {
+    // 文件元信息固定是显式小端
+    let mut meta = Builder::new(TransferSyntax::ExplicitVrLittleEndian);
+    meta.element(0x0002, 0x0001, "OB", &[0x00, 0x01])
+        .text(0x0002, 0x0002, "UI", "1.2.840.10008.5.1.4.1.1.7\0")
+        .text(0x0002, 0x0003, "UI", "1.2.3.4.5\0")
+        .text(
+            0x0002,
+            0x0010,
+            "UI",
+            &format!("{}\0", transfer_syntax.uid()),
+        )
+        .text(0x0002, 0x0012, "UI", "1.2.3.4\0")
+        .text(0x0002, 0x0013, "SH", "SYNTHETIC ");
+
+    let mut file = vec![0x41; 128];
+    file.extend_from_slice(b"DICM");
+
+    let mut group_length = Builder::new(TransferSyntax::ExplicitVrLittleEndian);
+    group_length.element(
+        0x0002,
+        0x0000,
+        "UL",
+        &(meta.buffer.len() as u32).to_le_bytes(),
+    );
+    file.extend_from_slice(&group_length.buffer);
+    file.extend_from_slice(&meta.buffer);
+
+    // 嵌套的SQ，item中包含另一个未定义长度的SQ
+    let mut code = Builder::new(transfer_syntax);
+    code.text(0x0008, 0x0100, "SH", "121322")
+        .text(0x0008, 0x0102, "SH", "DCM ")
+        .text(0x0008, 0x0104, "LO", "Source image");
+    let code = code.buffer;
+
+    let mut nested_item = Builder::new(transfer_syntax);
+    nested_item
+        .text(0x0008, 0x1150, "UI", "1.2.840.10008.5.1.4.1.1.2\0")
+        .sequence(0x0040, 0xa170, vec![(code.clone(), true)], true);
+    let nested_item = nested_item.buffer;
+
+    let mut plain_item = Builder::new(transfer_syntax);
+    plain_item.text(0x0008, 0x1155, "UI", "1.2.3.4.6\0");
+    let plain_item = plain_item.buffer;
+
+    let mut group_0008 = Builder::new(transfer_syntax);
+    group_0008
+        .text(0x0008, 0x0005, "CS", "ISO_IR 100")
+        .text(0x0008, 0x0012, "DA", "20240131")
+        .text(0x0008, 0x0013, "TM", "101530.1")
+        .text(0x0008, 0x0016, "UI", "1.2.840.10008.5.1.4.1.1.7\0")
+        .text(0x0008, 0x0018, "UI", "1.2.3.4.5\0")
+        .text(0x0008, 0x002a, "DT", "20240131101530.5")
+        // 首尾带空格的字符串
+        .text(0x0008, 0x0050, "SH", " ACC1 ")
+        .text(0x0008, 0x0054, "AE", "STORESCP")
+        .text(0x0008, 0x0090, "PN", "Doe^John")
+        .text(0x0008, 0x0119, "UC", "Long Code Value ")
+        .text(0x0008, 0x0120, "UR", "http://example.com/a ")
+        // 奇数长度的编码（不符合标准，但是实际文件中会出现）
+        .text(0x0008, 0x1010, "SH", "ABC")
+        .sequence(
+            0x0008,
+            0x1140,
+            vec![(nested_item, true), (plain_item.clone(), false)],
+            true,
+        );
+    let group_0008 = group_0008.buffer;
+
+    let mut dataset = Builder::new(transfer_syntax);
+    let group_0008_length = dataset.numbers(vec![(group_0008.len() as u32).to_le_bytes().to_vec()]);
+    dataset.element(0x0008, 0x0000, "UL", &group_0008_length);
+    dataset.buffer.extend_from_slice(&group_0008);
+
+    let us = dataset.numbers(vec![
+        0_u16.to_le_bytes().to_vec(),
+        256_u16.to_le_bytes().to_vec(),
+        256_u16.to_le_bytes().to_vec(),
+        0_u16.to_le_bytes().to_vec(),
+    ]);
+    let ss = dataset.numbers(vec![(-5_i16).to_le_bytes().to_vec()]);
+    let sl = dataset.numbers(vec![(-70000_i32).to_le_bytes().to_vec()]);
+    let fl = dataset.numbers(vec![1.25_f32.to_le_bytes().to_vec()]);
+    let fd = dataset.numbers(vec![
+        1.5_f64.to_le_bytes().to_vec(),
+        (-2.25_f64).to_le_bytes().to_vec(),
+    ]);
+    let sv = dataset.numbers(vec![(-1_i64 << 40).to_le_bytes().to_vec()]);
+    let uv = dataset.numbers(vec![(1_u64 << 63).to_le_bytes().to_vec()]);
+    let at = dataset.numbers(vec![
+        0x0028_u16.to_le_bytes().to_vec(),
+        0x0010_u16.to_le_bytes().to_vec(),
+    ]);
+    let two = dataset.numbers(vec![2_u16.to_le_bytes().to_vec()]);
+    let ul = dataset.numbers(vec![123456_u32.to_le_bytes().to_vec()]);
+    let od = dataset.numbers(vec![0.5_f64.to_le_bytes().to_vec()]);
+    let of = dataset.numbers(vec![
+        0.5_f32.to_le_bytes().to_vec(),
+        2.0_f32.to_le_bytes().to_vec(),
+    ]);
+    let ol = dataset.numbers(vec![7_u32.to_le_bytes().to_vec()]);
+    let ov = dataset.numbers(vec![9_u64.to_le_bytes().to_vec()]);
+    let ow = dataset.numbers(vec![
+        1_u16.to_le_bytes().to_vec(),
+        2_u16.to_le_bytes().to_vec(),
+        3_u16.to_le_bytes().to_vec(),
+        1000_u16.to_le_bytes().to_vec(),
+    ]);
+
+    dataset
+        // 私有tag
+        .text(0x0009, 0x0010, "LO", "ACME 1.0")
+        .element(0x0009, 0x1001, "UN", &[0x01, 0x02, 0x03])
+        .element(0x0009, 0x1002, "OB", &[0xde, 0xad, 0xbe, 0xef])
+        .text(0x0010, 0x1010, "AS", "045Y")
+        .text(0x0018, 0x0050, "DS", "+1.50E+01 ")
+        .element(0x0018, 0x1310, "US", &us)
+        // 没有item的SQ
+        .sequence(0x0018, 0x6011, Vec::new(), false)
+        .element(0x0018, 0x6020, "SL", &sl)
+        .element(0x0018, 0x9219, "SS", &ss)
+        .element(0x0018, 0x9306, "FL", &fl)
+        .text(0x0020, 0x0012, "IS", "0012")
+        .text(0x0020, 0x0013, "IS", "7 ")
+        .text(0x0020, 0x4000, "LT", "line1\r\nline2")
+        .element(0x0020, 0x9165, "AT", &at)
+        .element(0x0028, 0x0010, "US", &two)
+        .element(0x0028, 0x0011, "US", &two)
+        .text(0x0028, 0x1052, "DS", "-1024 ")
+        // 空值
+        .text(0x0028, 0x1053, "DS", "")
+        .element(0x0040, 0x9212, "FD", &fd)
+        .text(0x0040, 0xa160, "UT", "free text")
+        .sequence(0x0040, 0xa730, vec![(plain_item, false)], false)
+        .text(0x0040, 0xe001, "ST", "ST text ")
+        .element(0x0054, 0x0100, "UL", &ul)
+        .element(0x0066, 0x0016, "OF", &of)
+        .element(0x0066, 0x0040, "OL", &ol)
+        .element(0x0066, 0x0041, "OD", &od)
+        .element(0x0066, 0x0042, "OV", &ov)
+        .element(0x0072, 0x0082, "SV", &sv)
+        .element(0x0072, 0x0083, "UV", &uv)
+        .element(0x7fe0, 0x0010, "OW", &ow);
+
+    file.extend_from_slice(&dataset.buffer);
+
+    file
+}
+
+// 字节不一致时只打印第一个不同的位置附近的内容，方便定位
+fn assert_bytes_eq(actual: &[u8], expected: &[u8]) {
+    if let Some(index) = (0..actual.len().min(expected.len())).find(|i| actual[*i] != expected[*i])
+    {
+        let start = index.saturating_sub(16);
+        panic!(
+            "first difference at byte {}\n  actual: {:02X?}\nexpected: {:02X?}",
+            index,
+            &actual[start..(index + 16).min(actual.len())],
+            &expected[start..(index + 16).min(expected.len())]
+        );
+    }
+
+    assert_eq!(actual.len(), expected.len(), "length differs");
+}
+
+fn round_trip(file: &[u8], transfer_syntax: TransferSyntax) -> Vec<u8> {
+    let data_elements = service::parse_file_content(file, &ParseOptions::default()).unwrap();
+
+    let options = WriteOptions {
+        transfer_syntax,
+        preamble: Some(file[..128].try_into().unwrap()),
+        ..Default::default()
+    };
+
+    writer::write_to_bytes(&data_elements, &options).unwrap()
+}
+
+#[test]
+fn explicit_vr_little_endian_round_trip_is_byte_exact() {
+    let file = build_file(TransferSyntax::ExplicitVrLittleEndian);
+
+    assert_bytes_eq(
+        &round_trip(&file, TransferSyntax::ExplicitVrLittleEndian),
+        &file,
+    );
+}
+
+#[test]
+fn explicit_vr_big_endian_round_trip_is_byte_exact() {
+    let file = build_file(TransferSyntax::ExplicitVrBigEndian);
+
+    assert_bytes_eq(
+        &round_trip(&file, TransferSyntax::ExplicitVrBigEndian),
+        &file,
+    );
+}
+
+#[test]
+fn implicit_vr_little_endian_round_trip_is_byte_exact() {
+    let file = build_file(TransferSyntax::ImplicitVrLittleEndian);
+
+    assert_bytes_eq(
+        &round_trip(&file, TransferSyntax::ImplicitVrLittleEndian),
+        &file,
+    );
+}
+
+#[test]
+fn big_endian_and_back_is_byte_exact() {
+    let file = build_file(TransferSyntax::ExplicitVrLittleEndian);
+
+    let big_endian = round_trip(&file, TransferSyntax::ExplicitVrBigEndian);
+
+    assert_bytes_eq(
+        &round_trip(&big_endian, TransferSyntax::ExplicitVrLittleEndian),
+        &file,
+    );
+}
+
+#[test]
+fn values_are_decoded_in_both_byte_orders() {
+    for transfer_syntax in [
+        TransferSyntax::ExplicitVrLittleEndian,
+        TransferSyntax::ExplicitVrBigEndian,
+    ] {
+        let file = build_file(transfer_syntax);
+        let data_elements = service::parse_file_content(&file, &ParseOptions::default()).unwrap();
+
+        let find = |tag: &str| {
+            data_elements
+                .iter()
+                .find(|v| v.tag == tag)
+                .unwrap()
+                .data
+                .clone()
+        };
+
+        assert_eq!(find("0018,1310"), DicomValue::U16(vec![0, 256, 256, 0]));
+        assert_eq!(find("0018,6020"), DicomValue::I32(vec![-70000]));
+        assert_eq!(find("0040,9212"), DicomValue::Double(vec![1.5, -2.25]));
+        assert_eq!(find("0018,0050"), DicomValue::Double(vec![15.0]));
+        assert_eq!(find("0020,0012"), DicomValue::I64(vec![12]));
+        assert_eq!(
+            find("0020,9165"),
+            DicomValue::U16Pair(("0028".to_string(), "0010".to_string()))
+        );
+        assert_eq!(
+            find("7FE0,0010"),
+            DicomValue::Bytes(vec![1, 0, 2, 0, 3, 0, 232, 3])
+        );
+
+        match find("0008,1140") {
+            DicomValue::Sequence(items) => {
+                assert_eq!(items.len(), 2);
+                assert_eq!(items[0].len(), 2);
+                assert_eq!(items[1].len(), 1);
+            }
+            other => panic!("unexpected value {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn modified_values_are_re_encoded() {
+    let file = build_file(TransferSyntax::ExplicitVrLittleEndian);
+    let mut data_elements = service::parse_file_content(&file, &ParseOptions::default()).unwrap();
+
+    for data_element in data_elements.iter_mut() {
+        if data_element.tag == "0018,0050" {
+            data_element.data = DicomValue::Double(vec![2.5]);
+        }
+
+        if data_element.tag == "0008,0050" {
+            data_element.data = DicomValue::String("ACCESSION2".to_string());
+        }
+    }
+
+    util::set_data_element(
+        &mut data_elements,
+        util::new_data_element(
+            "0010,0010",
+            "PN",
+            DicomValue::String("Roe^Jane".to_string()),
+        )
+        .unwrap(),
+    );
+
+    let written = writer::write_to_bytes(&data_elements, &WriteOptions::default()).unwrap();
+    let reread = service::parse_file_content(&written, &ParseOptions::default()).unwrap();
+
+    let find = |tag: &str| reread.iter().find(|v| v.tag == tag).unwrap().data.clone();
+
+    assert_eq!(find("0018,0050"), DicomValue::Double(vec![2.5]));
+    assert_eq!(
+        find("0010,0010"),
+        DicomValue::String("Roe^Jane".to_string())
+    );
+    assert!(written.windows(4).any(|v| v == b"2.5 "));
+
+    // " ACC1 "变成了"ACCESSION2"，group length也要跟着变长4个字节
+    let original = service::parse_file_content(&file, &ParseOptions::default()).unwrap();
+    let original_group_length = match &original.iter().find(|v| v.tag == "0008,0000").unwrap().data
+    {
+        DicomValue::U32(v) => v[0],
+        other => panic!("unexpected value {:?}", other),
+    };
+    assert_eq!(
+        find("0008,0000"),
+        DicomValue::U32(vec![original_group_length + 4])
+    );
+}
+
+#[test]
+fn truncated_files_are_errors() {
+    for transfer_syntax in [
+        TransferSyntax::ExplicitVrLittleEndian,
+        TransferSyntax::ExplicitVrBigEndian,
+        TransferSyntax::ImplicitVrLittleEndian,
+    ] {
+        let file = build_file(transfer_syntax);
+
+        // 最后一个元素是像素数据，截断在其值的中间
+        let result = service::parse_file_content(&file[..file.len() - 1], &ParseOptions::default());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("exceeds the remaining"));
+
+        // 截断在任何位置都不能panic（每个元素都要查tag名称，逐字节截断太慢，这里间隔取样）
+        for length in (132..file.len()).step_by(61) {
+            let _ = service::parse_file_content(&file[..length], &ParseOptions::default());
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use dicom_parser::{
+    model::{DataElement, DicomValue, RenderOptions, Shutter, TransferSyntax},
+    render, shutter,
+};
+
+mod common;
+
+use common::{element, string};
+
+// 5x5、像素值全为255的8位灰度图像
+fn build_dataset(shutters: Vec<DataElement>) -> Vec<DataElement> {
+    let mut result = vec![
+        string(
+            "0002,0010",
+            "UI",
+            TransferSyntax::ExplicitVrLittleEndian.uid(),
+        ),
+        string("0028,0004", "CS", "MONOCHROME2"),
+        element("0028,0010", "US", DicomValue::U16(vec![5])),
+        element("0028,0011", "US", DicomValue::U16(vec![5])),
+        element("0028,0100", "US", DicomValue::U16(vec![8])),
+        element("0028,0101", "US", DicomValue::U16(vec![8])),
+        element("0028,1050", "DS", DicomValue::Double(vec![128.0])),
+        element("0028,1051", "DS", DicomValue::Double(vec![256.0])),
+        element("7FE0,0010", "OB", DicomValue::Bytes(vec![255; 25])),
+    ];
+
+    result.extend(shutters);
+    result
+}
+
+fn visible_points(mask: &[bool]) -> Vec<(usize, usize)> {
+    mask.iter()
+        .enumerate()
+        .filter(|(_, v)| **v)
+        .map(|(index, _)| (index / 5 + 1, index % 5 + 1))
+        .collect()
+}
+
+#[test]
+fn rectangular_and_circular_shutters_are_combined() {
+    let data_elements = build_dataset(vec![
+        string("0018,1600", "CS", "RECTANGULAR\\CIRCULAR"),
+        element("0018,1602", "IS", DicomValue::I64(vec![1])),
+        element("0018,1604", "IS", DicomValue::I64(vec![5])),
+        element("0018,1606", "IS", DicomValue::I64(vec![3])),
+        element("0018,1608", "IS", DicomValue::I64(vec![5])),
+        element("0018,1610", "IS", DicomValue::I64(vec![3, 3])),
+        element("0018,1612", "IS", DicomValue::I64(vec![1])),
+    ]);
+
+    assert_eq!(shutter::get_shutters(&data_elements).unwrap().len(), 2);
+
+    let mask = shutter::get_shutter_mask(&data_elements).unwrap().unwrap();
+
+    // 圆的下半部分
+    assert_eq!(visible_points(&mask), vec![(3, 2), (3, 3), (3, 4), (4, 3)]);
+
+    // 没有shutter时没有mask
+    assert!(shutter::get_shutter_mask(&build_dataset(Vec::new()))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn polygonal_shutter_keeps_the_inside_and_the_edges() {
+    let data_elements = build_dataset(vec![
+        string("0018,1600", "CS", "POLYGONAL"),
+        element("0018,1620", "IS", DicomValue::I64(vec![1, 1, 1, 3, 3, 1])),
+    ]);
+
+    let mask = shutter::get_shutter_mask(&data_elements).unwrap().unwrap();
+
+    assert_eq!(
+        visible_points(&mask),
+        vec![(1, 1), (1, 2), (1, 3), (2, 1), (2, 2), (3, 1)]
+    );
+}
+
+#[test]
+fn bitmap_shutter_hides_the_overlay_points() {
+    let data_elements = build_dataset(vec![
+        string("0018,1600", "CS", "BITMAP"),
+        element("0018,1623", "US", DicomValue::U16(vec![0x6002])),
+        element("6002,0010", "US", DicomValue::U16(vec![5])),
+        element("6002,0011", "US", DicomValue::U16(vec![1])),
+        string("6002,0040", "CS", "G"),
+        element("6002,0050", "SS", DicomValue::I16(vec![1, 1])),
+        element("6002,0100", "US", DicomValue::U16(vec![1])),
+        element("6002,0102", "US", DicomValue::U16(vec![0])),
+        // 第一列全部被遮住
+        element("6002,3000", "OW", DicomValue::Bytes(vec![0b11111, 0])),
+    ]);
+
+    let shutters = shutter::get_shutters(&data_elements).unwrap();
+    assert!(matches!(&shutters[..], [Shutter::Bitmap(overlay)] if overlay.group == 0x6002));
+
+    let mask = shutter::get_shutter_mask(&data_elements).unwrap().unwrap();
+
+    for (index, visible) in mask.iter().enumerate() {
+        assert_eq!(*visible, index % 5 != 0);
+    }
+}
+
+#[test]
+fn shutters_are_rendered_with_the_presentation_value_or_color() {
+    let shutters = vec![
+        string("0018,1600", "CS", "RECTANGULAR"),
+        element("0018,1602", "IS", DicomValue::I64(vec![2])),
+        element("0018,1604", "IS", DicomValue::I64(vec![4])),
+        element("0018,1606", "IS", DicomValue::I64(vec![2])),
+        element("0018,1608", "IS", DicomValue::I64(vec![4])),
+    ];
+
+    // 默认为黑色
+    let image = render::render(&build_dataset(shutters.clone()), &RenderOptions::default())
+        .unwrap()
+        .to_luma8();
+    assert_eq!(image.get_pixel(0, 0).0, [0]);
+    assert_eq!(image.get_pixel(2, 2).0, [255]);
+
+    let mut gray = shutters.clone();
+    gray.push(element("0018,1622", "US", DicomValue::U16(vec![0x8000])));
+
+    let image = render::render(&build_dataset(gray), &RenderOptions::default()).unwrap();
+    assert_eq!(image.color(), image::ColorType::L8);
+    assert_eq!(image.to_luma8().get_pixel(4, 4).0, [128]);
+
+    // CIELab的白色
+    let mut color = shutters;
+    color.push(element(
+        "0018,1624",
+        "US",
+        DicomValue::U16(vec![0xFFFF, 0x8080, 0x8080]),
+    ));
+
+    let image = render::render(&build_dataset(color), &RenderOptions::default())
+        .unwrap()
+        .to_rgb8();
+    assert_eq!(image.get_pixel(0, 4).0, [255; 3]);
+}
@@ -0,0 +1,169 @@
+use dicom_parser::{
+    accessor,
+    model::{DataElement, DicomValue, ParseOptions, TransferSyntax},
+    service, transcode, util,
+    writer::{self, WriteOptions},
+};
+
+// 2帧、每帧3x2、16位的RGB图像，像素值中包含重复和不重复的字节，覆盖RLE的两种run
+fn build_dataset(transfer_syntax: TransferSyntax) -> Vec<DataElement> {
+    let pixels = (0..2 * 3 * 2 * 3)
+        .flat_map(|v: u16| (if v.is_multiple_of(4) { 0x0101 } else { v * 300 }).to_le_bytes())
+        .collect::<Vec<u8>>();
+
+    vec![
+        util::new_data_element(
+            "0002,0010",
+            "UI",
+            DicomValue::String(transfer_syntax.uid().to_string()),
+        )
+        .unwrap(),
+        util::new_data_element(
+            "0008,0016",
+            "UI",
+            DicomValue::String("1.2.840.10008.5.1.4.1.1.7".to_string()),
+        )
+        .unwrap(),
+        util::new_data_element(
+            "0010,0010",
+            "PN",
+            DicomValue::String("Doe^John".to_string()),
+        )
+        .unwrap(),
+        util::new_data_element("0028,0002", "US", DicomValue::U16(vec![3])).unwrap(),
+        util::new_data_element("0028,0004", "CS", DicomValue::String("RGB".to_string())).unwrap(),
+        util::new_data_element("0028,0006", "US", DicomValue::U16(vec![0])).unwrap(),
+        util::new_data_element("0028,0008", "IS", DicomValue::I64(vec![2])).unwrap(),
+        util::new_data_element("0028,0010", "US", DicomValue::U16(vec![2])).unwrap(),
+        util::new_data_element("0028,0011", "US", DicomValue::U16(vec![3])).unwrap(),
+        util::new_data_element("0028,0100", "US", DicomValue::U16(vec![16])).unwrap(),
+        util::new_data_element("0028,0101", "US", DicomValue::U16(vec![16])).unwrap(),
+        util::new_data_element("0028,0102", "US", DicomValue::U16(vec![15])).unwrap(),
+        util::new_data_element("0028,0103", "US", DicomValue::U16(vec![0])).unwrap(),
+        util::new_data_element("7FE0,0010", "OW", DicomValue::Bytes(pixels)).unwrap(),
+    ]
+}
+
+fn write_and_read(
+    data_elements: &[DataElement],
+    transfer_syntax: TransferSyntax,
+) -> Vec<DataElement> {
+    let options = WriteOptions {
+        transfer_syntax,
+        ..Default::default()
+    };
+
+    let file = writer::write_to_bytes(data_elements, &options).unwrap();
+
+    service::parse_file_content(&file, &ParseOptions::default()).unwrap()
+}
+
+fn pixel_data(data_elements: &[DataElement]) -> DicomValue {
+    accessor::find_data_element(data_elements, "7FE0,0010")
+        .unwrap()
+        .data
+        .clone()
+}
+
+#[test]
+fn implicit_vr_is_converted_to_explicit_vr() {
+    let source = build_dataset(TransferSyntax::ImplicitVrLittleEndian);
+    let implicit = write_and_read(&source, TransferSyntax::ImplicitVrLittleEndian);
+
+    // 隐式vr的文件中也能按照字典查出标准的vr
+    let rows = accessor::find_data_element(&implicit, "0028,0010").unwrap();
+    assert_eq!(rows.vr, "US");
+    assert_eq!(rows.data, DicomValue::U16(vec![2]));
+
+    let explicit = transcode::transcode(&implicit, TransferSyntax::ExplicitVrLittleEndian).unwrap();
+    let explicit = write_and_read(&explicit, TransferSyntax::ExplicitVrLittleEndian);
+
+    assert_eq!(
+        accessor::get_str(&explicit, "0002,0010").unwrap(),
+        TransferSyntax::ExplicitVrLittleEndian.uid()
+    );
+    assert_eq!(
+        accessor::get_str(&explicit, "0010,0010").unwrap(),
+        "Doe^John"
+    );
+    assert_eq!(pixel_data(&explicit), pixel_data(&source));
+}
+
+#[test]
+fn big_endian_is_converted_to_little_endian() {
+    let source = build_dataset(TransferSyntax::ExplicitVrBigEndian);
+    let big_endian = write_and_read(&source, TransferSyntax::ExplicitVrBigEndian);
+
+    let little_endian =
+        transcode::transcode(&big_endian, TransferSyntax::ExplicitVrLittleEndian).unwrap();
+    let little_endian = write_and_read(&little_endian, TransferSyntax::ExplicitVrLittleEndian);
+
+    assert_eq!(pixel_data(&little_endian), pixel_data(&source));
+    assert_eq!(accessor::get_u16(&little_endian, "0028,0011").unwrap(), 3);
+}
+
+#[test]
+fn native_is_converted_to_rle_and_back() {
+    let source = build_dataset(TransferSyntax::ExplicitVrLittleEndian);
+
+    let rle = transcode::transcode(&source, TransferSyntax::RleLossless).unwrap();
+    let rle = write_and_read(&rle, TransferSyntax::RleLossless);
+
+    assert_eq!(
+        accessor::get_str(&rle, "0002,0010").unwrap(),
+        TransferSyntax::RleLossless.uid()
+    );
+
+    // Basic Offset Table加上每帧一个fragment
+    match pixel_data(&rle) {
+        DicomValue::Fragments(fragments) => {
+            assert_eq!(fragments.len(), 3);
+            assert_eq!(fragments[0].len(), 8);
+        }
+        value => panic!("pixel data is not encapsulated: {:?}", value),
+    }
+
+    let native = transcode::transcode(&rle, TransferSyntax::ExplicitVrLittleEndian).unwrap();
+    let native = write_and_read(&native, TransferSyntax::ExplicitVrLittleEndian);
+
+    assert_eq!(pixel_data(&native), pixel_data(&source));
+    assert_eq!(accessor::get_u16(&native, "0028,0006").unwrap(), 0);
+}
+
+#[test]
+fn planar_configuration_is_interleaved_after_rle() {
+    let mut source = build_dataset(TransferSyntax::ExplicitVrLittleEndian);
+
+    util::set_data_element(
+        &mut source,
+        util::new_data_element("0028,0006", "US", DicomValue::U16(vec![1])).unwrap(),
+    );
+
+    let rle = transcode::transcode(&source, TransferSyntax::RleLossless).unwrap();
+    let native = transcode::transcode(&rle, TransferSyntax::ExplicitVrLittleEndian).unwrap();
+
+    // 按平面存储的第一个像素是R0、G0、B0分别位于三个平面的开头
+    let (source_pixels, native_pixels) = match (pixel_data(&source), pixel_data(&native)) {
+        (DicomValue::Bytes(a), DicomValue::Bytes(b)) => (a, b),
+        _ => panic!("pixel data is not native"),
+    };
+
+    let plane = 3 * 2 * 2;
+    assert_eq!(native_pixels[0..2], source_pixels[0..2]);
+    assert_eq!(native_pixels[2..4], source_pixels[plane..plane + 2]);
+    assert_eq!(native_pixels[4..6], source_pixels[2 * plane..2 * plane + 2]);
+    assert_eq!(accessor::get_u16(&native, "0028,0006").unwrap(), 0);
+}
+
+#[test]
+fn encapsulated_pixel_data_is_not_written_as_native() {
+    let source = build_dataset(TransferSyntax::ExplicitVrLittleEndian);
+    let rle = transcode::transcode(&source, TransferSyntax::RleLossless).unwrap();
+
+    let options = WriteOptions {
+        transfer_syntax: TransferSyntax::ExplicitVrLittleEndian,
+        ..Default::default()
+    };
+
+    assert!(writer::write_to_bytes(&rle, &options).is_err());
+}
@@ -0,0 +1,340 @@
+use std::path::{Path, PathBuf};
+
+mod common;
+
+use common::{element, string};
+
+use dicom_parser::{
+    model::{DataElement, DicomValue, NpyData, TransferSyntax, VolumeIssue},
+    volume,
+    writer::{self, WriteOptions},
+};
+
+// 2x3的16位有符号轴位图像，像素值为value..value+5
+fn build_slice(
+    series_instance_uid: &str,
+    instance_number: i64,
+    position: [f64; 3],
+    orientation: &str,
+    value: i16,
+    intercept: f64,
+) -> Vec<DataElement> {
+    let pixels = (value..value + 6)
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<u8>>();
+
+    vec![
+        string(
+            "0002,0010",
+            "UI",
+            TransferSyntax::ExplicitVrLittleEndian.uid(),
+        ),
+        string("0020,000E", "UI", series_instance_uid),
+        element("0020,0013", "IS", DicomValue::I64(vec![instance_number])),
+        element("0020,0032", "DS", DicomValue::Double(position.to_vec())),
+        string("0020,0037", "DS", orientation),
+        string("0028,0004", "CS", "MONOCHROME2"),
+        element("0028,0010", "US", DicomValue::U16(vec![2])),
+        element("0028,0011", "US", DicomValue::U16(vec![3])),
+        element("0028,0030", "DS", DicomValue::Double(vec![0.5, 0.25])),
+        element("0028,0100", "US", DicomValue::U16(vec![16])),
+        element("0028,0101", "US", DicomValue::U16(vec![16])),
+        element("0028,0102", "US", DicomValue::U16(vec![15])),
+        element("0028,0103", "US", DicomValue::U16(vec![1])),
+        element("0028,1052", "DS", DicomValue::Double(vec![intercept])),
+        element("0028,1053", "DS", DicomValue::Double(vec![1.0])),
+        element("7FE0,0010", "OW", DicomValue::Bytes(pixels)),
+    ]
+}
+
+const AXIAL: &str = "1\\0\\0\\0\\1\\0";
+
+fn write_directory(name: &str, slices: &[(&str, Vec<DataElement>)]) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!(
+        "dicom_parser_volume_{}_{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    for (file_name, data_elements) in slices {
+        writer::write_file(
+            directory.join(file_name).to_str().unwrap(),
+            data_elements,
+            &WriteOptions::default(),
+        )
+        .unwrap();
+    }
+
+    directory
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().unwrap().to_str().unwrap()
+}
+
+#[test]
+fn slices_are_sorted_along_the_normal() {
+    // Instance Number和文件名的顺序都与空间位置不同
+    let directory = write_directory(
+        "sorted",
+        &[
+            (
+                "a.dcm",
+                build_slice("1.2.3", 1, [-10.0, -20.0, 5.0], AXIAL, 200, -1024.0),
+            ),
+            (
+                "b.dcm",
+                build_slice("1.2.3", 2, [-10.0, -20.0, 0.0], AXIAL, 0, -1024.0),
+            ),
+            (
+                "c.dcm",
+                build_slice("1.2.3", 3, [-10.0, -20.0, 2.5], AXIAL, 100, -1024.0),
+            ),
+        ],
+    );
+
+    let volumes = volume::read_volumes(&directory).unwrap().0;
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(volumes.len(), 1);
+
+    let volume = &volumes[0];
+
+    assert_eq!(volume.series_instance_uid, "1.2.3");
+    assert_eq!(volume.shape, [3, 2, 3]);
+    assert_eq!(
+        volume
+            .file_paths
+            .iter()
+            .map(|v| file_name(v))
+            .collect::<Vec<&str>>(),
+        vec!["b.dcm", "c.dcm", "a.dcm"]
+    );
+    assert!(volume.issues.is_empty());
+
+    // Rescale相同，保存存储值
+    assert_eq!(
+        (volume.rescale_slope, volume.rescale_intercept),
+        (1.0, -1024.0)
+    );
+    match &volume.data {
+        NpyData::I16(data) => {
+            assert_eq!(data.len(), 18);
+            assert_eq!((data[0], data[6], data[12]), (0, 100, 200));
+        }
+        data => panic!("unexpected data {:?}", data),
+    }
+
+    assert_eq!(volume.spacing, [0.25, 0.5, 2.5]);
+    assert_eq!(
+        volume.affine,
+        [
+            [0.25, 0.0, 0.0, -10.0],
+            [0.0, 0.5, 0.0, -20.0],
+            [0.0, 0.0, 2.5, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ]
+    );
+    assert_eq!(
+        volume
+            .attributes
+            .iter()
+            .filter(|v| v.tag == "7FE0,0010")
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn duplicates_gaps_and_inconsistent_spacing_are_reported() {
+    let directory = write_directory(
+        "issues",
+        &[
+            (
+                "1.dcm",
+                build_slice("1.2.3", 1, [0.0, 0.0, 0.0], AXIAL, 0, 0.0),
+            ),
+            (
+                "2.dcm",
+                build_slice("1.2.3", 2, [0.0, 0.0, 2.0], AXIAL, 0, 0.0),
+            ),
+            (
+                "3.dcm",
+                build_slice("1.2.3", 3, [0.0, 0.0, 2.0], AXIAL, 0, 0.0),
+            ),
+            (
+                "4.dcm",
+                build_slice("1.2.3", 4, [0.0, 0.0, 4.0], AXIAL, 0, 0.0),
+            ),
+            (
+                "5.dcm",
+                build_slice("1.2.3", 5, [0.0, 0.0, 6.0], AXIAL, 0, 0.0),
+            ),
+            // 缺了8mm处的一层
+            (
+                "6.dcm",
+                build_slice("1.2.3", 6, [0.0, 0.0, 10.0], AXIAL, 0, 0.0),
+            ),
+            (
+                "7.dcm",
+                build_slice("1.2.3", 7, [0.0, 0.0, 12.5], AXIAL, 0, 0.0),
+            ),
+        ],
+    );
+
+    let volume = volume::read_volumes(&directory).unwrap().0.remove(0);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(volume.shape[0], 6);
+    assert_eq!(volume.spacing[2], 2.0);
+
+    let mut duplicates = 0;
+    let mut gaps = Vec::new();
+    let mut inconsistent = Vec::new();
+
+    for issue in &volume.issues {
+        match issue {
+            VolumeIssue::Duplicate { file_path, kept } => {
+                duplicates += 1;
+                assert_eq!((file_name(file_path), file_name(kept)), ("3.dcm", "2.dcm"));
+            }
+            VolumeIssue::Gap {
+                between, distance, ..
+            } => gaps.push((*between, *distance)),
+            VolumeIssue::InconsistentSpacing {
+                between, distance, ..
+            } => inconsistent.push((*between, *distance)),
+            issue => panic!("unexpected issue {:?}", issue),
+        }
+    }
+
+    assert_eq!(duplicates, 1);
+    assert_eq!(gaps, vec![((3, 4), 4.0)]);
+    assert_eq!(inconsistent, vec![((4, 5), 2.5)]);
+}
+
+#[test]
+fn gantry_tilt_shears_the_affine() {
+    // 每层沿着y方向移动1mm，沿着z方向移动2mm
+    let directory = write_directory(
+        "tilt",
+        &(0..3)
+            .map(|index| {
+                (
+                    ["1.dcm", "2.dcm", "3.dcm"][index],
+                    build_slice(
+                        "1.2.3",
+                        index as i64 + 1,
+                        [0.0, index as f64, index as f64 * 2.0],
+                        AXIAL,
+                        0,
+                        0.0,
+                    ),
+                )
+            })
+            .collect::<Vec<(&str, Vec<DataElement>)>>(),
+    );
+
+    let volume = volume::read_volumes(&directory).unwrap().0.remove(0);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    match &volume.issues[..] {
+        [VolumeIssue::GantryTilt { angle }] => {
+            assert!((angle - 0.5_f64.atan().to_degrees()).abs() < 1e-9)
+        }
+        issues => panic!("unexpected issues {:?}", issues),
+    }
+
+    // 层间距是法线方向上的距离，slice方向的向量指向下一层的位置
+    assert_eq!(volume.spacing[2], 2.0);
+    let slice_vector = [0, 1, 2].map(|axis| volume.affine[axis][2]);
+    for (actual, expected) in slice_vector.iter().zip([0.0, 1.0, 2.0]) {
+        assert!((actual - expected).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn series_are_grouped_and_localizers_are_excluded() {
+    let directory = write_directory(
+        "grouped",
+        &[
+            (
+                "a1.dcm",
+                build_slice("1.2.3", 1, [0.0, 0.0, 0.0], AXIAL, 10, 0.0),
+            ),
+            (
+                "a2.dcm",
+                build_slice("1.2.3", 2, [0.0, 0.0, 3.0], AXIAL, 20, 5.0),
+            ),
+            // 同一个序列中的矢状位定位像
+            (
+                "a3.dcm",
+                build_slice("1.2.3", 3, [0.0, 0.0, 0.0], "0\\1\\0\\0\\0\\-1", 0, 0.0),
+            ),
+            (
+                "b1.dcm",
+                build_slice("1.2.4", 1, [0.0, 0.0, 0.0], AXIAL, 0, 0.0),
+            ),
+        ],
+    );
+
+    let volumes = volume::read_volumes(&directory).unwrap().0;
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(
+        volumes
+            .iter()
+            .map(|v| (v.series_instance_uid.as_str(), v.shape[0]))
+            .collect::<Vec<(&str, usize)>>(),
+        vec![("1.2.3", 2), ("1.2.4", 1)]
+    );
+
+    match &volumes[0].issues[..] {
+        [VolumeIssue::Excluded { file_path, .. }] => assert_eq!(file_name(file_path), "a3.dcm"),
+        issues => panic!("unexpected issues {:?}", issues),
+    }
+
+    // Rescale Intercept不同，保存Modality LUT的输出值
+    assert_eq!(
+        (volumes[0].rescale_slope, volumes[0].rescale_intercept),
+        (1.0, 0.0)
+    );
+    match &volumes[0].data {
+        NpyData::F32(data) => assert_eq!((data[0], data[6]), (10.0, 25.0)),
+        data => panic!("unexpected data {:?}", data),
+    }
+
+    // 只有一层时层间距使用Slice Thickness，没有时为1
+    assert_eq!(volumes[1].spacing[2], 1.0);
+}
+
+#[test]
+fn series_that_can_not_be_assembled_does_not_stop_the_others() {
+    // 第二个序列没有Image Orientation (Patient)，无法组成体数据
+    let mut broken = build_slice("1.2.4", 1, [0.0, 0.0, 0.0], AXIAL, 0, 0.0);
+    broken.retain(|v| v.tag != "0020,0037");
+
+    let directory = write_directory(
+        "failures",
+        &[
+            (
+                "a.dcm",
+                build_slice("1.2.3", 1, [0.0, 0.0, 0.0], AXIAL, 0, -1024.0),
+            ),
+            (
+                "b.dcm",
+                build_slice("1.2.3", 2, [0.0, 0.0, 2.5], AXIAL, 100, -1024.0),
+            ),
+            ("c.dcm", broken),
+        ],
+    );
+
+    let (volumes, failures) = volume::read_volumes(&directory).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(volumes.len(), 1);
+    assert_eq!(volumes[0].series_instance_uid, "1.2.3");
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, "1.2.4");
+}
@@ -0,0 +1,153 @@
+use dicom_parser::{
+    model::{DataElement, DicomValue, ParseOptions, TransferSyntax},
+    service,
+    writer::{self, WriteOptions},
+};
+
+mod common;
+
+use common::{element, string};
+
+fn round_trip(
+    data_elements: &[DataElement],
+    options: &WriteOptions,
+) -> (Vec<u8>, Vec<DataElement>) {
+    let buffer = writer::write_to_bytes(data_elements, options).unwrap();
+    let result = service::parse_file_content(&buffer, &ParseOptions::default()).unwrap();
+
+    (buffer, result)
+}
+
+fn find<'a>(data_elements: &'a [DataElement], tag: &str) -> &'a DicomValue {
+    &data_elements.iter().find(|v| v.tag == tag).unwrap().data
+}
+
+fn sequence_dataset() -> Vec<DataElement> {
+    let nested = element(
+        "0008,1199",
+        "SQ",
+        DicomValue::Sequence(vec![vec![string("0008,1150", "UI", "1.2.3")]]),
+    );
+
+    vec![
+        string("0008,0060", "CS", "MR"),
+        element(
+            "0008,1140",
+            "SQ",
+            DicomValue::Sequence(vec![
+                vec![string("0008,1155", "UI", "1.2.3.4"), nested],
+                vec![element("0020,0013", "IS", DicomValue::I64(vec![7]))],
+            ]),
+        ),
+        string("0010,0010", "PN", "Doe^John"),
+    ]
+}
+
+// 检查sequence_dataset写出再读回后的结构
+fn assert_sequence_dataset(data_elements: &[DataElement]) {
+    let DicomValue::Sequence(items) = find(data_elements, "0008,1140") else {
+        panic!("0008,1140 is not a sequence");
+    };
+
+    assert_eq!(items.len(), 2);
+    assert!(matches!(find(&items[0], "0008,1155"), DicomValue::String(v) if v == "1.2.3.4"));
+    assert!(matches!(find(&items[1], "0020,0013"), DicomValue::I64(v) if v == &vec![7]));
+
+    let DicomValue::Sequence(nested) = find(&items[0], "0008,1199") else {
+        panic!("0008,1199 is not a sequence");
+    };
+    assert!(matches!(find(&nested[0], "0008,1150"), DicomValue::String(v) if v == "1.2.3"));
+
+    // SQ之后的元素也要能正常读到
+    assert!(matches!(find(data_elements, "0010,0010"), DicomValue::String(v) if v == "Doe^John"));
+}
+
+#[test]
+fn group_lengths_are_recomputed() {
+    let data_elements = vec![
+        element("0002,0000", "UL", DicomValue::U32(vec![9999])),
+        element("0008,0000", "UL", DicomValue::U32(vec![9999])),
+        string("0008,0060", "CS", "MR"),
+        string("0008,0016", "UI", "1.2.3"),
+    ];
+
+    let (_, result) = round_trip(&data_elements, &WriteOptions::default());
+
+    // (0008,0016) 8 + 6, (0008,0060) 8 + 2
+    assert!(matches!(find(&result, "0008,0000"), DicomValue::U32(v) if v == &vec![24]));
+
+    // 文件元信息的group length等于其后所有0002组元素的长度之和
+    let meta_length = result
+        .iter()
+        .filter(|v| v.tag_group == "0002" && v.tag != "0002,0000")
+        .map(|v| writer::encode_data_element(v, TransferSyntax::ExplicitVrLittleEndian, false))
+        .map(|v| v.unwrap().len() as u32)
+        .sum::<u32>();
+    assert!(matches!(find(&result, "0002,0000"), DicomValue::U32(v) if v == &vec![meta_length]));
+    assert!(
+        matches!(find(&result, "0002,0002"), DicomValue::String(v) if v == "1.2.3"),
+        "media storage sop class uid is taken from the dataset"
+    );
+}
+
+#[test]
+fn odd_length_values_are_padded() {
+    let data_elements = vec![
+        string("0008,0016", "UI", "1.2.3"),
+        string("0008,0060", "CS", "MRI"),
+        element("0009,0010", "OB", DicomValue::Bytes(vec![1, 2, 3])),
+    ];
+
+    let (buffer, result) = round_trip(&data_elements, &WriteOptions::default());
+
+    // UI/OB使用\0填充，其余字符串使用空格填充
+    let contains = |bytes: &[u8]| buffer.windows(bytes.len()).any(|v| v == bytes);
+    assert!(contains(b"UI\x06\x001.2.3\0"));
+    assert!(contains(b"CS\x04\x00MRI "));
+    assert!(contains(b"OB\x00\x00\x04\x00\x00\x00\x01\x02\x03\x00"));
+
+    assert!(matches!(find(&result, "0008,0016"), DicomValue::String(v) if v == "1.2.3"));
+    assert!(matches!(find(&result, "0008,0060"), DicomValue::String(v) if v == "MRI"));
+
+    // 每个元素的长度都是偶数
+    for data_element in &data_elements {
+        let encoded = writer::encode_data_element(
+            data_element,
+            TransferSyntax::ExplicitVrLittleEndian,
+            false,
+        )
+        .unwrap();
+        assert!(encoded.len().is_multiple_of(2));
+    }
+}
+
+#[test]
+fn defined_length_sequences_round_trip() {
+    let options = WriteOptions {
+        undefined_sequence_length: false,
+        ..WriteOptions::default()
+    };
+
+    let (buffer, result) = round_trip(&sequence_dataset(), &options);
+
+    assert_sequence_dataset(&result);
+    assert!(!buffer.windows(4).any(|v| v == [0xfe, 0xff, 0xdd, 0xe0]));
+    assert!(!buffer.windows(4).any(|v| v == [0xfe, 0xff, 0x0d, 0xe0]));
+}
+
+#[test]
+fn undefined_length_sequences_round_trip() {
+    let options = WriteOptions {
+        undefined_sequence_length: true,
+        ..WriteOptions::default()
+    };
+
+    let (buffer, result) = round_trip(&sequence_dataset(), &options);
+
+    assert_sequence_dataset(&result);
+
+    // 两层SQ各有一个Seq. Delim. Tag，三个item各有一个Item Delim. Tag
+    let count = |bytes: [u8; 8]| buffer.windows(8).filter(|v| *v == bytes).count();
+    assert_eq!(count([0xfe, 0xff, 0xdd, 0xe0, 0, 0, 0, 0]), 2);
+    assert_eq!(count([0xfe, 0xff, 0x0d, 0xe0, 0, 0, 0, 0]), 3);
+}